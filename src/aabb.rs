@@ -0,0 +1,120 @@
+use crate::ray::Ray;
+use crate::vec3::{self, Point3};
+
+// Axis-aligned bounding box
+#[derive(Copy, Clone, Default)]
+pub struct Aabb {
+    minimum: Point3,
+    maximum: Point3,
+}
+
+impl Aabb {
+    pub fn new(a: Point3, b: Point3) -> Aabb {
+        Aabb {
+            minimum: a,
+            maximum: b,
+        }
+    }
+
+    pub fn min(&self) -> Point3 {
+        self.minimum
+    }
+
+    pub fn max(&self) -> Point3 {
+        self.maximum
+    }
+
+    // Slab test: intersect the ray's t interval with the interval between each pair of planes
+    pub fn hit(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+        for a in 0..3 {
+            let orig = component(r.origin(), a);
+            let inv_d = component(r.inv_direction(), a);
+            let mut t0 = (component(self.minimum, a) - orig) * inv_d;
+            let mut t1 = (component(self.maximum, a) - orig) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max <= t_min {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+fn component(v: Point3, axis: usize) -> f64 {
+    match axis {
+        0 => v.x(),
+        1 => v.y(),
+        _ => v.z(),
+    }
+}
+
+// Smallest box containing both boxes
+pub fn surrounding_box(box0: Aabb, box1: Aabb) -> Aabb {
+    Aabb::new(
+        vec3::min(box0.min(), box1.min()),
+        vec3::max(box0.max(), box1.max()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Vec3;
+
+    // The slab test as it was, dividing by the direction on every call
+    fn hit_dividing(bbox: &Aabb, r: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+        for a in 0..3 {
+            let orig = component(r.origin(), a);
+            let d = component(r.direction(), a);
+            let mut t0 = (component(bbox.min(), a) - orig) / d;
+            let mut t1 = (component(bbox.max(), a) - orig) / d;
+            if 1.0 / d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max <= t_min {
+                return false;
+            }
+        }
+        true
+    }
+
+    #[test]
+    fn cached_inverse_gives_the_same_hits_for_axis_parallel_rays() {
+        let bbox = Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        let directions = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        ];
+        let (mut hits, mut misses) = (0, 0);
+        // Origins inside, outside and exactly on the faces of the box
+        for x in [-3.0, -1.0, -0.5, 0.0, 1.0, 2.0] {
+            for y in [-2.0, -1.0, 0.25, 1.0, 3.0] {
+                for z in [-5.0, -1.0, 0.0, 0.75, 1.0, 5.0] {
+                    for direction in directions {
+                        let r = Ray::new(Point3::new(x, y, z), direction, 0.0);
+                        let expected = hit_dividing(&bbox, &r, 0.001, f64::INFINITY);
+                        assert_eq!(bbox.hit(&r, 0.001, f64::INFINITY), expected);
+                        if expected {
+                            hits += 1;
+                        } else {
+                            misses += 1;
+                        }
+                    }
+                }
+            }
+        }
+        assert!(hits > 0 && misses > 0);
+    }
+}
@@ -0,0 +1,297 @@
+use crate::color::{self, Color};
+use crate::common;
+use crate::shapes::Sphere;
+use crate::texture::{ImageTexture, Texture};
+use crate::vec3::{self, Point3, Vec3};
+use std::io;
+
+// Color seen by rays that leave the scene without hitting anything
+pub trait Background: Send + Sync {
+    fn sample(&self, direction: Vec3) -> Color;
+
+    // Whether sample_light can pick directions towards the background's light, so it can
+    // be sampled directly like the scene's lights. Otherwise it's only found by rays that
+    // happen to leave the scene.
+    fn can_sample_light(&self) -> bool {
+        false
+    }
+
+    // Random direction towards the background, with the light coming from there and the
+    // probability density it was picked with. None for backgrounds that can't be sampled.
+    fn sample_light(&self) -> Option<(Vec3, Color, f64)> {
+        None
+    }
+
+    // Probability density of sample_light picking `direction`
+    fn pdf_value(&self, _direction: Vec3) -> f64 {
+        0.0
+    }
+}
+
+// Vertical blend from `bottom` straight down to `top` straight up, the horizon gets
+// the average of the two. The default is white to light blue.
+pub struct GradientSky {
+    pub top: Color,
+    pub bottom: Color,
+}
+
+impl GradientSky {
+    pub fn new(top: Color, bottom: Color) -> GradientSky {
+        GradientSky { top, bottom }
+    }
+}
+
+impl Default for GradientSky {
+    fn default() -> Self {
+        GradientSky::new(Color::new(0.5, 0.7, 1.0), Color::new(1.0, 1.0, 1.0))
+    }
+}
+
+impl Background for GradientSky {
+    fn sample(&self, direction: Vec3) -> Color {
+        let unit_direction = vec3::unit_vector(direction);
+        let t = 0.5 * (unit_direction.y() + 1.0);
+        vec3::lerp(self.bottom, self.top, t)
+    }
+}
+
+// The same color in every direction, black is typical for scenes lit by their own lights
+pub struct SolidBackground(pub Color);
+
+impl Background for SolidBackground {
+    fn sample(&self, _direction: Vec3) -> Color {
+        self.0
+    }
+}
+
+// Equirectangular (latitude/longitude) image wrapped around the scene. Its light is
+// sampled directly, picking pixels in proportion to how bright they are, so a small bright
+// sun lights the scene without the noise of waiting for rays to stumble onto it.
+pub struct EnvironmentMap {
+    image: ImageTexture,
+    width: usize,
+    height: usize,
+    // Probability of sample_light picking each pixel, row by row from the top: its
+    // luminance times sin(theta), as rows towards the poles cover less of the sphere.
+    // Empty if there is nothing to sample (the image is black).
+    pixel_probabilities: Vec<f64>,
+    // Running totals of the probabilities of the rows, and of the pixels within each row
+    // (as a share of that row), for picking one by binary search
+    row_cdf: Vec<f64>,
+    pixel_cdf: Vec<f64>,
+}
+
+impl EnvironmentMap {
+    // A missing or unreadable image is an error rather than a magenta sky
+    pub fn load(path: &str) -> io::Result<EnvironmentMap> {
+        // A picture like any other, so its bytes are decoded to linear light: a map written
+        // by the renderer lights the scene with the colors it shows
+        let image =
+            ImageTexture::open(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(EnvironmentMap::from_image(image))
+    }
+
+    fn from_image(image: ImageTexture) -> EnvironmentMap {
+        let (width, height) = image.size();
+
+        let mut weights = Vec::with_capacity(width * height);
+        for j in 0..height {
+            let v = 1.0 - (j as f64 + 0.5) / height as f64;
+            let sin_theta = (v * common::PI).sin();
+            for i in 0..width {
+                let u = (i as f64 + 0.5) / width as f64;
+                let pixel = image.value(u, v, &Point3::default());
+                weights.push(color::luminance(pixel).max(0.0) * sin_theta);
+            }
+        }
+        let total: f64 = weights.iter().sum();
+
+        let mut environment = EnvironmentMap {
+            image,
+            width,
+            height,
+            pixel_probabilities: Vec::new(),
+            row_cdf: Vec::new(),
+            pixel_cdf: Vec::new(),
+        };
+        if total > 0.0 && total.is_finite() {
+            environment.pixel_probabilities = weights.iter().map(|w| w / total).collect();
+            let mut row_total = 0.0;
+            for row in weights.chunks(width) {
+                let row_sum: f64 = row.iter().sum();
+                row_total += row_sum / total;
+                environment.row_cdf.push(row_total);
+                let mut pixel_total = 0.0;
+                for w in row {
+                    pixel_total += if row_sum > 0.0 { w / row_sum } else { 0.0 };
+                    environment.pixel_cdf.push(pixel_total);
+                }
+            }
+        }
+        environment
+    }
+
+    // Index of the first running total above a random number, i.e. an entry picked with
+    // the probability it adds to the total
+    fn pick(cdf: &[f64]) -> usize {
+        let r = common::random_double() * cdf.last().copied().unwrap_or(0.0);
+        cdf.partition_point(|&total| total <= r).min(cdf.len() - 1)
+    }
+}
+
+impl Background for EnvironmentMap {
+    fn sample(&self, direction: Vec3) -> Color {
+        // Same mapping as a sphere's surface coordinates, seen from the inside
+        let unit_direction = vec3::unit_vector(direction);
+        let (u, v) = Sphere::get_sphere_uv(unit_direction);
+        self.image.value(u, v, &unit_direction)
+    }
+
+    fn can_sample_light(&self) -> bool {
+        !self.pixel_probabilities.is_empty()
+    }
+
+    fn sample_light(&self) -> Option<(Vec3, Color, f64)> {
+        if !self.can_sample_light() {
+            return None;
+        }
+
+        // A row, then a pixel in it, then a random spot in that pixel
+        let j = Self::pick(&self.row_cdf);
+        let i = Self::pick(&self.pixel_cdf[j * self.width..(j + 1) * self.width]);
+        let u = (i as f64 + common::random_double()) / self.width as f64;
+        let v = 1.0 - (j as f64 + common::random_double()) / self.height as f64;
+
+        // The inverse of get_sphere_uv
+        let (phi, theta) = (2.0 * common::PI * u, common::PI * v);
+        let direction = Vec3::new(
+            phi.cos() * theta.sin(),
+            -theta.cos(),
+            phi.sin() * theta.sin(),
+        );
+        Some((direction, self.sample(direction), self.pdf_value(direction)))
+    }
+
+    fn pdf_value(&self, direction: Vec3) -> f64 {
+        if !self.can_sample_light() {
+            return 0.0;
+        }
+
+        let unit_direction = vec3::unit_vector(direction);
+        let (u, v) = Sphere::get_sphere_uv(unit_direction);
+        let i = ((u * self.width as f64) as usize).min(self.width - 1);
+        let j = (((1.0 - v) * self.height as f64) as usize).min(self.height - 1);
+        let sin_theta = (1.0 - unit_direction.y() * unit_direction.y())
+            .max(0.0)
+            .sqrt();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+
+        // A pixel covers 2 pi / width by pi / height of (phi, theta), which is
+        // sin(theta) * 2 pi^2 / (width * height) of solid angle
+        let pixel_solid_angle =
+            sin_theta * 2.0 * common::PI * common::PI / (self.width * self.height) as f64;
+        self.pixel_probabilities[j * self.width + i] / pixel_solid_angle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    // Writes a P6 image to a file of its own in the temp directory
+    fn temp_ppm(
+        name: &str,
+        width: usize,
+        height: usize,
+        pixel: impl Fn(usize, usize) -> u8,
+    ) -> String {
+        let mut data = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+        for j in 0..height {
+            for i in 0..width {
+                data.extend_from_slice(&[pixel(i, j); 3]);
+            }
+        }
+        let path = std::env::temp_dir().join(format!("{}-{}.ppm", name, std::process::id()));
+        fs::write(&path, data).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn solid_background_is_the_same_everywhere() {
+        let color = Color::new(0.2, 0.4, 0.6);
+        let background = SolidBackground(color);
+        for direction in [
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(1.0, 2.0, -3.0),
+            Vec3::new(-0.1, 0.0, 0.0),
+        ] {
+            let seen = background.sample(direction);
+            assert!((seen - color).length() == 0.0);
+        }
+        assert!(!background.can_sample_light());
+    }
+
+    #[test]
+    fn environment_map_shows_the_colors_of_the_image() {
+        let path = temp_ppm("gray-environment", 8, 4, |_, _| 128);
+        let environment = EnvironmentMap::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        for direction in [Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.2, -0.5)] {
+            let seen = environment.sample(direction);
+            assert_eq!(color::to_srgb_bytes(seen), [128, 128, 128]);
+        }
+    }
+
+    #[test]
+    fn missing_environment_map_is_an_error() {
+        let error = EnvironmentMap::load("no/such/sky.ppm").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("no/such/sky.ppm"));
+    }
+
+    #[test]
+    fn gradient_sky_runs_from_bottom_to_top() {
+        let (top, bottom) = (Color::new(0.1, 0.2, 0.9), Color::new(1.0, 0.9, 0.8));
+        let sky = GradientSky::new(top, bottom);
+        let same = |a: Color, b: Color| (a - b).length() < 1e-12;
+        assert!(same(sky.sample(Vec3::new(0.0, 3.0, 0.0)), top));
+        assert!(same(sky.sample(Vec3::new(0.0, -0.5, 0.0)), bottom));
+        // Halfway at the horizon
+        assert!(same(
+            sky.sample(Vec3::new(1.0, 0.0, 0.0)),
+            0.5 * (top + bottom)
+        ));
+    }
+
+    #[test]
+    fn environment_light_is_sampled_towards_its_one_bright_pixel() {
+        // A dim 16 x 8 map with a single white pixel a bit above the horizon
+        let path = temp_ppm("sun-environment", 16, 8, |i, j| {
+            if (i, j) == (5, 2) {
+                255
+            } else {
+                2
+            }
+        });
+        let environment = EnvironmentMap::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(environment.can_sample_light());
+
+        common::seed_rng(14);
+        let n = 2000;
+        let towards_sun = (0..n)
+            .filter(|_| {
+                let (direction, light, pdf) = environment.sample_light().unwrap();
+                assert!((pdf - environment.pdf_value(direction)).abs() < 1e-9 * pdf);
+                color::to_srgb_bytes(light) == [255, 255, 255]
+            })
+            .count();
+        assert!(towards_sun * 100 > n * 95, "{} of {}", towards_sun, n);
+    }
+}
@@ -0,0 +1,179 @@
+use std::cmp::Ordering;
+
+use crate::aabb::{self, Aabb};
+use crate::common;
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::vec3::Point3;
+
+// Bounding volume hierarchy: a binary tree of boxes so a ray only visits the
+// objects whose boxes it actually passes through
+pub struct BvhNode {
+    left: Box<dyn Hittable>,
+    right: Option<Box<dyn Hittable>>,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+    pub fn new(mut objects: Vec<Box<dyn Hittable>>) -> BvhNode {
+        assert!(!objects.is_empty(), "BvhNode needs at least one object");
+
+        // Split along a random axis, sorting the objects by their centroids
+        let axis = common::random_int_range(0, 2) as usize;
+        objects.sort_by(|a, b| box_compare(a.as_ref(), b.as_ref(), axis));
+
+        let (left, right): (Box<dyn Hittable>, Option<Box<dyn Hittable>>) = match objects.len() {
+            1 => (objects.pop().unwrap(), None),
+            2 => {
+                let right = objects.pop().unwrap();
+                (objects.pop().unwrap(), Some(right))
+            }
+            n => {
+                let right_half = objects.split_off(n / 2);
+                (
+                    Box::new(BvhNode::new(objects)),
+                    Some(Box::new(BvhNode::new(right_half))),
+                )
+            }
+        };
+
+        let left_box = bounding_box_of(left.as_ref());
+        let bbox = match &right {
+            Some(right) => aabb::surrounding_box(left_box, bounding_box_of(right.as_ref())),
+            None => left_box,
+        };
+
+        BvhNode { left, right, bbox }
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        // Missing the box means missing everything inside it
+        if !self.bbox.hit(r, t_min, t_max) {
+            return false;
+        }
+
+        let hit_left = self.left.hit(r, t_min, t_max, rec);
+        // Only look for something closer than the left hit on the right side
+        let t_max = if hit_left { rec.t } else { t_max };
+        let hit_right = match &self.right {
+            Some(right) => right.hit(r, t_min, t_max, rec),
+            None => false,
+        };
+
+        hit_left || hit_right
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+fn bounding_box_of(object: &dyn Hittable) -> Aabb {
+    object
+        .bounding_box()
+        .expect("No bounding box in BvhNode constructor")
+}
+
+fn box_compare(a: &dyn Hittable, b: &dyn Hittable, axis: usize) -> Ordering {
+    let centroid = |object: &dyn Hittable| {
+        let c: Point3 = object
+            .centroid()
+            .expect("No bounding box in BvhNode constructor");
+        match axis {
+            0 => c.x(),
+            1 => c.y(),
+            _ => c.z(),
+        }
+    };
+
+    centroid(a)
+        .partial_cmp(&centroid(b))
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::hittable_list::HittableList;
+    use crate::material::Lambertian;
+    use crate::shapes::Sphere;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+    use std::sync::Arc;
+
+    // A sphere that counts how many times it is tested against a ray
+    struct Counted {
+        sphere: Sphere,
+        tests: Arc<AtomicUsize>,
+    }
+
+    impl Hittable for Counted {
+        fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+            self.tests.fetch_add(1, AtomicOrdering::Relaxed);
+            self.sphere.hit(r, t_min, t_max, rec)
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            self.sphere.bounding_box()
+        }
+    }
+
+    // 500 small spheres on a 10 x 10 x 5 grid, all counting into `tests`
+    fn grid(tests: &Arc<AtomicUsize>) -> Vec<Box<dyn Hittable>> {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        (0..500)
+            .map(|n| {
+                let center =
+                    Point3::new((n % 10) as f64, (n / 10 % 10) as f64, -((n / 100) as f64));
+                Box::new(Counted {
+                    sphere: Sphere::new(center, 0.3, material.clone()),
+                    tests: tests.clone(),
+                }) as Box<dyn Hittable>
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bvh_tests_far_fewer_objects_than_a_list() {
+        common::seed_rng(1);
+        let (list_tests, bvh_tests) =
+            (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let list: HittableList = grid(&list_tests).into_iter().collect();
+        let bvh = BvhNode::new(grid(&bvh_tests));
+
+        // Rays from in front of the grid towards random points on its front face
+        let origin = Point3::new(4.5, 4.5, 10.0);
+        let mut hits = 0;
+        for _ in 0..200 {
+            let target = Point3::new(
+                common::random_double() * 9.0,
+                common::random_double() * 9.0,
+                0.0,
+            );
+            let r = Ray::new(origin, target - origin, 0.0);
+            let (mut list_rec, mut bvh_rec) = (HitRecord::new(), HitRecord::new());
+            let hit = list.hit(&r, 0.001, f64::INFINITY, &mut list_rec);
+            assert_eq!(hit, bvh.hit(&r, 0.001, f64::INFINITY, &mut bvh_rec));
+            if hit {
+                hits += 1;
+                assert!((list_rec.t - bvh_rec.t).abs() < 1e-9);
+            }
+        }
+        assert!(hits > 0);
+
+        // The list tests every sphere for every ray
+        let (list_tests, bvh_tests) = (
+            list_tests.load(AtomicOrdering::Relaxed),
+            bvh_tests.load(AtomicOrdering::Relaxed),
+        );
+        assert_eq!(list_tests, 200 * 500);
+        assert!(
+            bvh_tests * 20 < list_tests,
+            "{} spheres tested, {} with a list",
+            bvh_tests,
+            list_tests
+        );
+    }
+}
@@ -1,18 +1,85 @@
 use crate::common;
-use crate::ray::Ray;
+use crate::ray::{Ray, RayKind};
 use crate::vec3::{self, Point3, Vec3};
 
+#[derive(Copy, Clone, PartialEq)]
+pub enum Projection {
+    // Pinhole/thin lens camera, rays spread out from the lens
+    Perspective,
+    // Parallel rays, objects keep their size regardless of distance
+    Orthographic,
+    // 360 degree panorama around the camera position: the image's x runs through all
+    // longitudes and its y from straight down to straight up, best rendered at 2:1
+    Equirectangular,
+    // Circular image of everything within fov / 2 degrees of the view direction, with the
+    // angle from the center growing evenly with the distance from the image center. The
+    // circle fills the image height; pixels outside it get no rays.
+    Fisheye { fov: f64 },
+}
+
+// How open the shutter is over time, which decides how much each moment of a motion
+// blurred object's path counts
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ShutterCurve {
+    // Opens at once and stays fully open: every time between time0 and time1 counts the same
+    Box,
+    // Opens gradually until halfway and closes the same way, so motion streaks fade out
+    // at both ends like those of a real mechanical shutter
+    Triangle,
+}
+
+impl ShutterCurve {
+    pub fn from_name(name: &str) -> Option<ShutterCurve> {
+        match name {
+            "box" => Some(ShutterCurve::Box),
+            "triangle" => Some(ShutterCurve::Triangle),
+            _ => None,
+        }
+    }
+
+    // Random time in [time0, time1] distributed like the curve
+    pub fn sample(self, time0: f64, time1: f64) -> f64 {
+        let fraction = match self {
+            ShutterCurve::Box => common::random_double(),
+            // The average of two uniform numbers has a triangular distribution
+            ShutterCurve::Triangle => (common::random_double() + common::random_double()) / 2.0,
+        };
+        time0 + (time1 - time0) * fraction
+    }
+
+    // The time in [time0, time1] the number `u` in [0, 1) stands for, distributed like the
+    // curve (the inverse of its running total), so evenly spread numbers give evenly
+    // spread times
+    pub fn time_at(self, time0: f64, time1: f64, u: f64) -> f64 {
+        let fraction = match self {
+            ShutterCurve::Box => u,
+            ShutterCurve::Triangle if u < 0.5 => (u / 2.0).sqrt(),
+            ShutterCurve::Triangle => 1.0 - ((1.0 - u) / 2.0).sqrt(),
+        };
+        time0 + (time1 - time0) * fraction
+    }
+}
+
 pub struct Camera {
+    projection: Projection,
     origin: Point3,
     lower_left_corner: Point3,
     horizontal: Vec3,
     vertical: Vec3,
     u: Vec3,
     v: Vec3,
+    w: Vec3,
     lens_radius: f64,
+    // Number of straight aperture blades, which gives out of focus highlights their
+    // polygon shape; 0 (or anything below 3) for a round aperture
+    aperture_blades: u32,
+    time0: f64, // Shutter open/close times
+    time1: f64,
+    shutter_curve: ShutterCurve,
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         lookfrom: Point3,
         lookat: Point3,
@@ -21,6 +88,8 @@ impl Camera {
         aspect_ratio: f64,
         aperture: f64,
         focus_dist: f64,
+        time0: f64,
+        time1: f64,
     ) -> Camera {
         let theta = common::degrees_to_radians(vfov);
         let h = f64::tan(theta / 2.0);
@@ -39,23 +108,542 @@ impl Camera {
         let lens_radius = aperture / 2.0;
 
         Camera {
+            projection: Projection::Perspective,
             origin,
             lower_left_corner,
             horizontal,
             vertical,
             u,
             v,
+            w,
             lens_radius,
+            aperture_blades: 0,
+            time0,
+            time1,
+            shutter_curve: ShutterCurve::Box,
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * vec3::random_in_unit_disk();
+    // Camera looking along lookat - lookfrom with parallel rays, starting from an
+    // image rectangle centered on lookfrom that is viewport_height world units tall
+    pub fn orthographic(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        viewport_height: f64,
+        aspect_ratio: f64,
+        time0: f64,
+        time1: f64,
+    ) -> Camera {
+        let viewport_width = aspect_ratio * viewport_height;
+
+        let w = vec3::unit_vector(lookfrom - lookat);
+        let u = vec3::unit_vector(vec3::cross(vup, w));
+        let v = vec3::cross(w, u);
+
+        let origin = lookfrom;
+        let horizontal = viewport_width * u;
+        let vertical = viewport_height * v;
+        let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0;
+
+        Camera {
+            projection: Projection::Orthographic,
+            origin,
+            lower_left_corner,
+            horizontal,
+            vertical,
+            u,
+            v,
+            w,
+            lens_radius: 0.0,
+            aperture_blades: 0,
+            time0,
+            time1,
+            shutter_curve: ShutterCurve::Box,
+        }
+    }
+
+    // Panorama of everything around lookfrom, with lookat in the center of the image
+    pub fn equirectangular(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        time0: f64,
+        time1: f64,
+    ) -> Camera {
+        let w = vec3::unit_vector(lookfrom - lookat);
+        let u = vec3::unit_vector(vec3::cross(vup, w));
+        let v = vec3::cross(w, u);
+
+        Camera {
+            projection: Projection::Equirectangular,
+            origin: lookfrom,
+            lower_left_corner: lookfrom,
+            horizontal: Vec3::default(),
+            vertical: Vec3::default(),
+            u,
+            v,
+            w,
+            lens_radius: 0.0,
+            aperture_blades: 0,
+            time0,
+            time1,
+            shutter_curve: ShutterCurve::Box,
+        }
+    }
+
+    // Fisheye looking from lookfrom towards lookat, fov degrees across the image circle
+    pub fn fisheye(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        fov: f64,
+        aspect_ratio: f64,
+        time0: f64,
+        time1: f64,
+    ) -> Camera {
+        let w = vec3::unit_vector(lookfrom - lookat);
+        let u = vec3::unit_vector(vec3::cross(vup, w));
+        let v = vec3::cross(w, u);
+
+        // The image rectangle in units of the circle's radius
+        let horizontal = 2.0 * aspect_ratio * u;
+        let vertical = 2.0 * v;
+
+        Camera {
+            projection: Projection::Fisheye { fov },
+            origin: lookfrom,
+            lower_left_corner: lookfrom - horizontal / 2.0 - vertical / 2.0,
+            horizontal,
+            vertical,
+            u,
+            v,
+            w,
+            lens_radius: 0.0,
+            aperture_blades: 0,
+            time0,
+            time1,
+            shutter_curve: ShutterCurve::Box,
+        }
+    }
+
+    pub fn builder() -> CameraBuilder {
+        CameraBuilder::new()
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    // Moment a ray is sent at, while the shutter is open
+    fn ray_time(&self) -> f64 {
+        self.shutter_curve.sample(self.time0, self.time1)
+    }
+
+    // Point on the lens (of radius 1) a ray starts from
+    fn lens_sample(&self) -> Vec3 {
+        if self.aperture_blades >= 3 {
+            vec3::random_in_unit_polygon(self.aperture_blades)
+        } else {
+            vec3::random_in_unit_disk()
+        }
+    }
+
+    // Point on the lens (of radius 1) the numbers (a, b) in [0, 1) x [0, 1) stand for,
+    // covering it evenly: `a` goes around the lens and `b` outwards from the center
+    fn lens_point(&self, (a, b): (f64, f64)) -> Vec3 {
+        let r = b.sqrt();
+        if self.aperture_blades >= 3 {
+            // Like random_in_unit_polygon, `a` picks the triangle and where across it
+            let sides = self.aperture_blades as f64;
+            let k = (a * sides).floor();
+            let s = a * sides - k;
+            let corner = |k: f64| {
+                let angle = 2.0 * common::PI * k / sides;
+                Vec3::new(angle.cos(), angle.sin(), 0.0)
+            };
+            r * ((1.0 - s) * corner(k) + s * corner(k + 1.0))
+        } else {
+            let angle = 2.0 * common::PI * a;
+            Vec3::new(r * angle.cos(), r * angle.sin(), 0.0)
+        }
+    }
+
+    // Ray through the point (s, t) of the image, both from 0 to 1 starting at the bottom
+    // left. None where the camera sees nothing (outside a fisheye's image circle), those
+    // parts of the image stay black.
+    pub fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        self.projected_ray(s, t, || self.lens_sample(), || self.ray_time())
+            .map(|ray| ray.with_kind(RayKind::Camera))
+    }
+
+    // Like get_ray, but with the point on the lens and the moment in the shutter interval
+    // picked by the numbers `lens` and `shutter` in [0, 1) instead of at random, for
+    // samplers that spread those out themselves (see SampleStrategy::lens_and_time)
+    pub fn get_ray_at(&self, s: f64, t: f64, lens: (f64, f64), shutter: f64) -> Option<Ray> {
+        self.projected_ray(
+            s,
+            t,
+            || self.lens_point(lens),
+            || self.shutter_curve.time_at(self.time0, self.time1, shutter),
+        )
+        .map(|ray| ray.with_kind(RayKind::Camera))
+    }
+
+    // The ray through (s, t), starting from the point on the lens `lens` gives (only used
+    // with depth of field) at the moment `time` gives
+    fn projected_ray(
+        &self,
+        s: f64,
+        t: f64,
+        lens: impl FnOnce() -> Vec3,
+        time: impl FnOnce() -> f64,
+    ) -> Option<Ray> {
+        match self.projection {
+            Projection::Orthographic => {
+                return Some(Ray::new(
+                    self.lower_left_corner + s * self.horizontal + t * self.vertical,
+                    -self.w,
+                    time(),
+                ));
+            }
+            Projection::Equirectangular => {
+                // Longitude from -pi (left edge) to pi, latitude from -pi/2 (bottom) to pi/2
+                let longitude = (s - 0.5) * 2.0 * common::PI;
+                let latitude = (t - 0.5) * common::PI;
+                let around = longitude.sin() * self.u - longitude.cos() * self.w;
+                return Some(Ray::new(
+                    self.origin,
+                    latitude.cos() * around + latitude.sin() * self.v,
+                    time(),
+                ));
+            }
+            Projection::Fisheye { fov } => {
+                let offset =
+                    self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin;
+                let radius = offset.length();
+                if radius > 1.0 {
+                    return None;
+                }
+
+                // Tilt away from the view direction by the angle matching the radius, towards
+                // the point in the image
+                let theta = radius * common::degrees_to_radians(fov) / 2.0;
+                let sideways = vec3::unit_vector(offset);
+                return Some(Ray::new(
+                    self.origin,
+                    theta.sin() * sideways - theta.cos() * self.w,
+                    time(),
+                ));
+            }
+            Projection::Perspective => {}
+        }
+
+        let rd = self.lens_radius * lens();
         let offset = self.u * rd.x() + self.v * rd.y();
 
-        Ray::new(
+        Some(Ray::new(
             self.origin + offset,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
-        )
+            time(),
+        ))
+    }
+}
+
+// Step-by-step alternative to the long Camera::new argument list
+//
+// Defaults: looking from the origin down -Z, vup = +Y, 90 degree vertical fov,
+// 16:9 aspect ratio, no depth of field (aperture 0), focus on the look-at point
+// and a shutter that is open only at time 0.
+pub struct CameraBuilder {
+    lookfrom: Point3,
+    lookat: Point3,
+    vup: Vec3,
+    vfov: f64,
+    aspect_ratio: f64,
+    aperture: f64,
+    aperture_blades: u32,
+    focus_dist: Option<f64>,
+    ortho_height: Option<f64>,
+    equirectangular: bool,
+    fisheye_fov: Option<f64>,
+    time0: f64,
+    time1: f64,
+    shutter_curve: ShutterCurve,
+}
+
+impl Default for CameraBuilder {
+    fn default() -> Self {
+        CameraBuilder {
+            lookfrom: Point3::new(0.0, 0.0, 0.0),
+            lookat: Point3::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 90.0,
+            aspect_ratio: 16.0 / 9.0,
+            aperture: 0.0,
+            aperture_blades: 0,
+            focus_dist: None,
+            ortho_height: None,
+            equirectangular: false,
+            fisheye_fov: None,
+            time0: 0.0,
+            time1: 0.0,
+            shutter_curve: ShutterCurve::Box,
+        }
+    }
+}
+
+impl CameraBuilder {
+    pub fn new() -> CameraBuilder {
+        Default::default()
+    }
+
+    pub fn look_from(mut self, lookfrom: Point3) -> Self {
+        self.lookfrom = lookfrom;
+        self
+    }
+
+    pub fn look_at(mut self, lookat: Point3) -> Self {
+        self.lookat = lookat;
+        self
+    }
+
+    pub fn vup(mut self, vup: Vec3) -> Self {
+        self.vup = vup;
+        self
+    }
+
+    // Vertical field-of-view in degrees
+    pub fn vfov(mut self, vfov: f64) -> Self {
+        self.vfov = vfov;
+        self
+    }
+
+    pub fn aspect_ratio(mut self, aspect_ratio: f64) -> Self {
+        self.aspect_ratio = aspect_ratio;
+        self
+    }
+
+    pub fn aperture(mut self, aperture: f64) -> Self {
+        self.aperture = aperture;
+        self
+    }
+
+    // Polygonal aperture with this many blades (e.g. 6 for hexagonal bokeh), 0 for round
+    pub fn aperture_blades(mut self, blades: u32) -> Self {
+        self.aperture_blades = blades;
+        self
+    }
+
+    pub fn focus_dist(mut self, focus_dist: f64) -> Self {
+        self.focus_dist = Some(focus_dist);
+        self
+    }
+
+    // Switch to an orthographic projection whose image is viewport_height units tall
+    // (vfov, aperture and focus_dist are then ignored)
+    pub fn orthographic(mut self, viewport_height: f64) -> Self {
+        self.ortho_height = Some(viewport_height);
+        self
+    }
+
+    // Switch to a 360 degree panorama around lookfrom (vfov, aperture, focus_dist and
+    // orthographic are then ignored)
+    pub fn equirectangular(mut self) -> Self {
+        self.equirectangular = true;
+        self
+    }
+
+    // Switch to a fisheye with fov degrees across its image circle, e.g. 180 for a full
+    // hemisphere (vfov, aperture, focus_dist and orthographic are then ignored)
+    pub fn fisheye(mut self, fov: f64) -> Self {
+        self.fisheye_fov = Some(fov);
+        self
+    }
+
+    // Time interval the shutter is open for, rays get a random time in [time0, time1]
+    pub fn shutter(mut self, time0: f64, time1: f64) -> Self {
+        self.time0 = time0;
+        self.time1 = time1;
+        self
+    }
+
+    // How the ray times are spread over the shutter interval (default Box, evenly)
+    pub fn shutter_curve(mut self, curve: ShutterCurve) -> Self {
+        self.shutter_curve = curve;
+        self
+    }
+
+    pub fn build(self) -> Camera {
+        let shutter_curve = self.shutter_curve;
+        let mut camera = self.build_projection();
+        camera.shutter_curve = shutter_curve;
+        camera
+    }
+
+    fn build_projection(self) -> Camera {
+        if self.equirectangular {
+            return Camera::equirectangular(
+                self.lookfrom,
+                self.lookat,
+                self.vup,
+                self.time0,
+                self.time1,
+            );
+        }
+        if let Some(fov) = self.fisheye_fov {
+            return Camera::fisheye(
+                self.lookfrom,
+                self.lookat,
+                self.vup,
+                fov,
+                self.aspect_ratio,
+                self.time0,
+                self.time1,
+            );
+        }
+        if let Some(viewport_height) = self.ortho_height {
+            return Camera::orthographic(
+                self.lookfrom,
+                self.lookat,
+                self.vup,
+                viewport_height,
+                self.aspect_ratio,
+                self.time0,
+                self.time1,
+            );
+        }
+
+        let focus_dist = self
+            .focus_dist
+            .unwrap_or_else(|| (self.lookfrom - self.lookat).length());
+
+        let mut camera = Camera::new(
+            self.lookfrom,
+            self.lookat,
+            self.vup,
+            self.vfov,
+            self.aspect_ratio,
+            self.aperture,
+            focus_dist,
+            self.time0,
+            self.time1,
+        );
+        camera.aperture_blades = self.aperture_blades;
+        camera
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let camera = Camera::builder()
+            .look_from(Point3::new(0.0, 0.0, 5.0))
+            .look_at(Point3::new(0.0, 0.0, 0.0))
+            .orthographic(2.0)
+            .build();
+        let a = camera.get_ray(0.2, 0.3).unwrap();
+        let b = camera.get_ray(0.9, 0.6).unwrap();
+
+        let direction = |r: &Ray| vec3::unit_vector(r.direction());
+        assert!((direction(&a) - direction(&b)).length() < 1e-12);
+        assert!((direction(&a) - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-12);
+        assert!((a.origin() - b.origin()).length() > 0.1);
+    }
+
+    #[test]
+    fn six_blade_lens_points_stay_inside_the_hexagon() {
+        common::seed_rng(1);
+        let camera = Camera::builder().aperture(1.0).aperture_blades(6).build();
+        // Inside means no further out than the middle of each side, in that side's direction
+        let inside = |p: Vec3| {
+            (0..6).all(|k| {
+                let angle = common::PI / 3.0 * (k as f64 + 0.5);
+                let side = Vec3::new(angle.cos(), angle.sin(), 0.0);
+                vec3::dot(p, side) <= (common::PI / 6.0).cos() + 1e-12
+            })
+        };
+
+        let mut furthest: f64 = 0.0;
+        for _ in 0..10000 {
+            let p = camera.lens_sample();
+            assert!(inside(p), "{} is outside", p);
+            furthest = furthest.max(p.length());
+        }
+        // Right out into the corners, further than the circle inside the hexagon
+        assert!(furthest > 0.95);
+
+        for i in 0..50 {
+            for j in 0..50 {
+                let p = camera.lens_point((i as f64 / 50.0, j as f64 / 50.0));
+                assert!(inside(p), "{} is outside", p);
+            }
+        }
+    }
+
+    #[test]
+    fn middle_of_a_panorama_looks_forward() {
+        let camera = Camera::builder()
+            .look_from(Point3::new(1.0, 2.0, 3.0))
+            .look_at(Point3::new(1.0, 2.0, -7.0))
+            .equirectangular()
+            .build();
+        let r = camera.get_ray(0.5, 0.5).unwrap();
+        assert!((r.origin() - Point3::new(1.0, 2.0, 3.0)).length() < 1e-12);
+        assert!((vec3::unit_vector(r.direction()) - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-9);
+
+        // A quarter of the way across is 90 degrees to the side, the top edge straight up
+        let left = camera.get_ray(0.25, 0.5).unwrap().direction();
+        assert!(vec3::dot(vec3::unit_vector(left), Vec3::new(0.0, 0.0, -1.0)).abs() < 1e-9);
+        let up = vec3::unit_vector(camera.get_ray(0.5, 1.0).unwrap().direction());
+        assert!((up - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn fisheye_looks_forward_in_the_middle_and_sees_nothing_past_the_circle() {
+        let camera = Camera::builder()
+            .look_from(Point3::new(0.0, 0.0, 0.0))
+            .look_at(Point3::new(0.0, 0.0, -1.0))
+            .aspect_ratio(16.0 / 9.0)
+            .fisheye(180.0)
+            .build();
+        let r = camera.get_ray(0.5, 0.5).unwrap();
+        assert!((vec3::unit_vector(r.direction()) - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-9);
+
+        // The circle touches the top and bottom edges, the corners are outside it
+        assert!(camera.get_ray(0.0, 0.0).is_none());
+        assert!(camera.get_ray(1.0, 1.0).is_none());
+        // Its edge is 90 degrees out for a 180 degree fisheye
+        let top = vec3::unit_vector(camera.get_ray(0.5, 0.999999).unwrap().direction());
+        assert!(top.z().abs() < 1e-5 && top.y() > 0.99, "{}", top);
+    }
+
+    #[test]
+    fn triangle_shutter_times_cluster_around_the_middle() {
+        common::seed_rng(11);
+        let n = 20_000;
+        // Share of the times in the middle half of a shutter open from 1 to 3
+        let middle = |times: &mut dyn Iterator<Item = f64>| {
+            times
+                .inspect(|t| assert!((1.0..=3.0).contains(t)))
+                .filter(|t| (1.5..2.5).contains(t))
+                .count() as f64
+                / n as f64
+        };
+
+        // Box: half of them. Triangle: all but the two corners of 1/8 each.
+        let uniform = middle(&mut (0..n).map(|_| ShutterCurve::Box.sample(1.0, 3.0)));
+        let triangle = middle(&mut (0..n).map(|_| ShutterCurve::Triangle.sample(1.0, 3.0)));
+        assert!((uniform - 0.5).abs() < 0.02, "box {}", uniform);
+        assert!((triangle - 0.75).abs() < 0.02, "triangle {}", triangle);
+
+        // Evenly spread numbers mapped through the curve split the same way
+        let spread = middle(
+            &mut (0..n).map(|i| ShutterCurve::Triangle.time_at(1.0, 3.0, i as f64 / n as f64)),
+        );
+        assert!((spread - 0.75).abs() < 1e-3, "spread {}", spread);
     }
 }
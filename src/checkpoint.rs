@@ -0,0 +1,164 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+
+const MAGIC: &str = "RTCHECKPOINT";
+
+// A render in progress: the sum of all samples taken so far in every pixel (and of their
+// squares, for the variance), and how many samples each pixel has had. Sums rather than
+// averages, so a resumed render simply keeps adding samples and the mean comes out the
+// same as without the interruption.
+pub struct Checkpoint {
+    pub sums: Framebuffer,
+    pub squares: Framebuffer,
+    pub samples: i32,
+}
+
+impl Checkpoint {
+    // A render that has not started yet
+    pub fn new(width: usize, height: usize) -> Checkpoint {
+        Checkpoint {
+            sums: Framebuffer::new(width, height),
+            squares: Framebuffer::new(width, height),
+            samples: 0,
+        }
+    }
+
+    // Write a small text header followed by the sums and then the squares as little endian
+    // 64 bit floats, so they load back exactly. The file is written next to `path` first
+    // and then renamed, so a crash while saving leaves the previous checkpoint intact.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let temp_path = format!("{}.tmp", path);
+        let mut out = BufWriter::new(File::create(&temp_path)?);
+        write!(
+            out,
+            "{}\n{} {} {}\n",
+            MAGIC, self.sums.width, self.sums.height, self.samples
+        )?;
+        for buffer in [&self.sums, &self.squares] {
+            for y in 0..buffer.height {
+                for x in 0..buffer.width {
+                    let value = buffer.get(x, y);
+                    for component in [value.x(), value.y(), value.z()] {
+                        out.write_all(&component.to_le_bytes())?;
+                    }
+                }
+            }
+        }
+        out.into_inner()?.sync_all()?;
+        fs::rename(&temp_path, path)
+    }
+
+    pub fn load(path: &str) -> io::Result<Checkpoint> {
+        let mut data = Vec::new();
+        BufReader::new(File::open(path)?).read_to_end(&mut data)?;
+
+        // Header: the magic line and a "width height samples" line
+        let mut lines = data.splitn(3, |&b| b == b'\n');
+        let (Some(magic), Some(size), Some(body)) = (lines.next(), lines.next(), lines.next())
+        else {
+            return Err(invalid_data("not a checkpoint file"));
+        };
+        if magic != MAGIC.as_bytes() {
+            return Err(invalid_data("not a checkpoint file"));
+        }
+        let numbers: Vec<usize> = String::from_utf8_lossy(size)
+            .split_whitespace()
+            .map(|n| n.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid_data("bad checkpoint header"))?;
+        let [width, height, samples] = numbers[..] else {
+            return Err(invalid_data("bad checkpoint header"));
+        };
+        let samples = i32::try_from(samples).map_err(|_| invalid_data("bad sample count"))?;
+        // Two buffers of three 8 byte floats per pixel; a made up header can ask for more
+        // than fits in a usize
+        let expected = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(2 * 3 * 8))
+            .ok_or_else(|| invalid_data("checkpoint size is too large"))?;
+        if body.len() != expected {
+            return Err(invalid_data("checkpoint data does not match its size"));
+        }
+
+        let mut components = body
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()));
+        let mut read_buffer = || {
+            let mut buffer = Framebuffer::new(width, height);
+            for y in 0..height {
+                for x in 0..width {
+                    let mut next = || components.next().unwrap();
+                    buffer.set(x, y, Color::new(next(), next(), next()));
+                }
+            }
+            buffer
+        };
+        let sums = read_buffer();
+        let squares = read_buffer();
+        Ok(Checkpoint {
+            sums,
+            squares,
+            samples,
+        })
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}-{}.checkpoint", name, std::process::id()));
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn checkpoint_loads_back_exactly() {
+        let mut checkpoint = Checkpoint::new(3, 2);
+        checkpoint.samples = 48;
+        for (x, y) in [(0, 0), (2, 0), (1, 1)] {
+            let value = Color::new(0.1 * x as f64, 1.0 / 3.0, 1e10 + y as f64);
+            checkpoint.sums.set(x, y, value);
+            checkpoint.squares.set(x, y, value * value);
+        }
+        let path = temp_path("checkpoint-round-trip");
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.samples, 48);
+        assert_eq!((loaded.sums.width, loaded.sums.height), (3, 2));
+        for y in 0..2 {
+            for x in 0..3 {
+                for (a, b) in [
+                    (&loaded.sums, &checkpoint.sums),
+                    (&loaded.squares, &checkpoint.squares),
+                ] {
+                    assert_eq!(<[f64; 3]>::from(a.get(x, y)), <[f64; 3]>::from(b.get(x, y)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn other_files_are_not_checkpoints() {
+        let path = temp_path("checkpoint-garbage");
+        fs::write(&path, "P3\n1 1\n255\n0 0 0\n").unwrap();
+        assert!(Checkpoint::load(&path).is_err());
+        // The right header but the data cut short
+        fs::write(&path, format!("{}\n2 2 16\n{}", MAGIC, "x".repeat(20))).unwrap();
+        assert!(Checkpoint::load(&path).is_err());
+        // A size whose byte count doesn't fit in a usize is an error, not an overflow
+        fs::write(&path, format!("{}\n4611686018427387904 4 0\n", MAGIC)).unwrap();
+        let error = Checkpoint::load(&path).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+}
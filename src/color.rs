@@ -1,28 +1,152 @@
 use crate::common;
+use crate::tonemap::ToneMap;
 use crate::vec3::Vec3;
 use std::io::Write;
 
 pub type Color = Vec3;
 
-// Write the translated [0, 255] value of each color component
-pub fn write_color(out: &mut impl Write, pixel_color: Color, samples_per_pixel: i32) {
-    let mut r = pixel_color.x();
-    let mut g = pixel_color.y();
-    let mut b = pixel_color.z();
-
-    // after casting multiple rays per pixel, you get the averaged result on the multiple samples
-    let scale = 1.0 / samples_per_pixel as f64;
-    r = f64::sqrt(scale * r);
-    g = f64::sqrt(scale * g);
-    b = f64::sqrt(scale * b);
-
-    // Write the translated [0, 255] value of each color component
-    writeln!(
-        out,
-        "{} {} {}",
-        (256.0 * common::clamp(r, 0.0, 0.999)) as i32,
-        (256.0 * common::clamp(g, 0.0, 0.999)) as i32,
-        (256.0 * common::clamp(b, 0.0, 0.999)) as i32,
-    )
-    .expect("writing color");
+// Average the accumulated samples, tone map, gamma-correct (gamma 2) and write the pixel as "r g b"
+pub fn write_color(
+    out: &mut impl Write,
+    pixel_color: Color,
+    samples_per_pixel: i32,
+    tone_map: ToneMap,
+) {
+    let [r, g, b] = to_bytes(average(pixel_color, samples_per_pixel), tone_map);
+    writeln!(out, "{} {} {}", r, g, b).expect("writing color");
+}
+
+// Same as write_color, but as three raw bytes for binary (P6) PPM files
+pub fn write_color_binary(
+    out: &mut impl Write,
+    pixel_color: Color,
+    samples_per_pixel: i32,
+    tone_map: ToneMap,
+) {
+    out.write_all(&to_bytes(average(pixel_color, samples_per_pixel), tone_map))
+        .expect("writing color");
+}
+
+// after casting multiple rays per pixel, you get the averaged result on the multiple samples
+pub fn average(pixel_color: Color, samples_per_pixel: i32) -> Color {
+    (1.0 / samples_per_pixel as f64) * pixel_color
+}
+
+// Tone map and gamma-correct (gamma 2) an averaged linear color into 8 bit "r g b"
+pub fn to_bytes(pixel_color: Color, tone_map: ToneMap) -> [u8; 3] {
+    to_srgb_bytes(tone_map.apply(pixel_color))
+}
+
+// Gamma-correct (gamma 2, close enough to sRGB's curve) a linear color into 8 bit "r g b",
+// clipping anything brighter than white
+pub fn to_srgb_bytes(color: Color) -> [u8; 3] {
+    let r = f64::sqrt(color.x());
+    let g = f64::sqrt(color.y());
+    let b = f64::sqrt(color.z());
+
+    // Translate each color component to [0, 255]
+    [
+        (256.0 * common::clamp(r, 0.0, 0.999)) as u8,
+        (256.0 * common::clamp(g, 0.0, 0.999)) as u8,
+        (256.0 * common::clamp(b, 0.0, 0.999)) as u8,
+    ]
+}
+
+// The other way round: 8 bit "r g b" as written in images (or web colors) back to a linear
+// color, undoing the gamma 2. Bytes written by to_srgb_bytes come back unchanged.
+pub fn from_srgb_bytes(bytes: [u8; 3]) -> Color {
+    let linear = |c: u8| (c as f64 / 255.0).powi(2);
+    Color::new(linear(bytes[0]), linear(bytes[1]), linear(bytes[2]))
+}
+
+// How bright a linear color looks, with the Rec. 709 weights (green counts most, blue least)
+pub fn luminance(color: Color) -> f64 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+impl Color {
+    // Web color like "#ff8800" or "#f80" (the '#' is optional) as a linear color, undoing
+    // the same gamma 2 that is applied when writing images. None if it isn't one.
+    pub fn from_hex(hex: &str) -> Option<Color> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |text: &str| u8::from_str_radix(text, 16).ok();
+        let [r, g, b] = match digits.len() {
+            6 => [
+                channel(&digits[0..2])?,
+                channel(&digits[2..4])?,
+                channel(&digits[4..6])?,
+            ],
+            // "#f80" is short for "#ff8800"
+            3 => [
+                channel(&digits[0..1])? * 17,
+                channel(&digits[1..2])? * 17,
+                channel(&digits[2..3])? * 17,
+            ],
+            _ => return None,
+        };
+        Some(from_srgb_bytes([r, g, b]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_color_averages_and_gamma_corrects() {
+        // 100 samples adding up to (25, 100, 400) average to (0.25, 1, 4): gamma 2 makes
+        // that (0.5, 1, 2), and everything from 1 up is clipped to 255
+        let mut out = Vec::new();
+        write_color(&mut out, Color::new(25.0, 100.0, 400.0), 100, ToneMap::None);
+        assert_eq!(String::from_utf8(out).unwrap(), "128 255 255\n");
+
+        let mut out = Vec::new();
+        write_color_binary(&mut out, Color::new(25.0, 0.0, 100.0), 100, ToneMap::None);
+        assert_eq!(out, [128, 0, 255]);
+    }
+
+    #[test]
+    fn hex_colors_parse_in_both_lengths() {
+        let white = Color::from_hex("#ffffff").unwrap();
+        assert_eq!(<[f64; 3]>::from(white), [1.0, 1.0, 1.0]);
+        assert_eq!(
+            to_srgb_bytes(Color::from_hex("f80").unwrap()),
+            [255, 136, 0]
+        );
+        assert!(Color::from_hex("#ff88").is_none());
+        assert!(Color::from_hex("#gg0000").is_none());
+    }
+
+    #[test]
+    fn bytes_survive_a_round_trip_through_linear() {
+        for b in 0..=255u8 {
+            assert_eq!(to_srgb_bytes(from_srgb_bytes([b, b, b])), [b, b, b]);
+        }
+    }
+
+    #[test]
+    fn green_looks_brighter_than_red_and_red_than_blue() {
+        let green = luminance(Color::new(0.0, 1.0, 0.0));
+        let red = luminance(Color::new(1.0, 0.0, 0.0));
+        let blue = luminance(Color::new(0.0, 0.0, 1.0));
+        assert!(green > red && red > blue);
+        assert!((luminance(Color::new(1.0, 1.0, 1.0)) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn colors_convert_to_and_from_arrays() {
+        let c = Color::from([0.25, 0.5, 1.0]);
+        assert_eq!((c.x(), c.y(), c.z()), (0.25, 0.5, 1.0));
+        assert_eq!(<[f64; 3]>::from(c), [0.25, 0.5, 1.0]);
+        let array: [f64; 3] = Color::new(0.0, -1.0, 2.0).into();
+        assert_eq!(array, [0.0, -1.0, 2.0]);
+
+        // Bytes are gamma 2 encoded: 0.25 is stored as half of 255
+        assert_eq!(to_srgb_bytes(c), [128, 181, 255]);
+        let linear = from_srgb_bytes([0, 51, 255]);
+        assert!((linear - Color::new(0.0, 0.04, 1.0)).length() < 1e-12);
+    }
 }
@@ -1,13 +1,52 @@
-use rand;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
 pub use std::f64::consts::PI;
-pub use std::f64::INFINITY;
+pub const INFINITY: f64 = f64::INFINITY;
+
+thread_local! {
+    // Every thread draws from its own generator, seeded from the OS until seed_rng is called
+    static RNG: RefCell<SmallRng> = RefCell::new(initial_rng());
+}
+
+#[cfg(feature = "native")]
+fn initial_rng() -> SmallRng {
+    SmallRng::from_os_rng()
+}
+
+// Without the native feature there may be no OS to ask (WebAssembly in a browser), so
+// every thread starts from the same fixed seed instead
+#[cfg(not(feature = "native"))]
+fn initial_rng() -> SmallRng {
+    SmallRng::seed_from_u64(0)
+}
 
 pub fn degrees_to_radians(degree: f64) -> f64 {
     degree * PI / 180.0
 }
 
+// Restart this thread's random sequence, the same seed always gives the same numbers
+pub fn seed_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
+// Seed for one item (e.g. a pixel) of a render seeded with `seed`, so each item gets its
+// own reproducible sequence no matter in which order or on which thread it is rendered
+pub fn derive_seed(seed: u64, index: u64) -> u64 {
+    // splitmix64 finalizer
+    let mut z = seed.wrapping_add(index.wrapping_mul(0x9e3779b97f4a7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// Map hashed bits (e.g. from derive_seed) to [0, 1), the same way the generator does
+pub fn double_from_bits(bits: u64) -> f64 {
+    (bits >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
 pub fn random_double() -> f64 {
-    rand::random::<f64>()
+    RNG.with(|rng| rng.borrow_mut().random::<f64>())
 }
 
 pub fn random_double_range(min: f64, max: f64) -> f64 {
@@ -15,6 +54,11 @@ pub fn random_double_range(min: f64, max: f64) -> f64 {
     min + (max - min) * random_double()
 }
 
+pub fn random_int_range(min: i32, max: i32) -> i32 {
+    // it returns [min, max]
+    RNG.with(|rng| rng.borrow_mut().random_range(min..=max))
+}
+
 pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
     if x < min {
         return min;
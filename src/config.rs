@@ -0,0 +1,385 @@
+// Command line options for the renderer binary
+
+use crate::downsample::DownsampleFilter;
+use crate::image_format::ImageFormat;
+use crate::render_mode::RenderMode;
+use crate::sampling::SampleStrategy;
+use crate::tonemap::ToneMap;
+
+pub const USAGE: &str = "\
+Usage: ray-tracing [options]
+
+Options:
+  --output <path>   write a binary (P6) PPM to <path> instead of P3 to stdout
+  --format <name>   ppm, png or pfm: pfm writes the linear colors as floats, without tone
+                    mapping or gamma (default ppm)
+  --width <pixels>  image width, the height follows from the aspect ratio
+                    (default: the scene's own setting, usually 800)
+  --samples <n>     samples per pixel (default: the scene's own setting, usually 500)
+  --ray-epsilon <t> how close to its start a ray may hit something: raise it if surfaces
+                    show dark speckles (shadow acne), lower it if thin gaps leak light
+                    (default: the scene's own setting, usually 0.001)
+  --far-clip <d>    how far rays look for something to hit: anything further away is
+                    left out and the background shows instead (default: the scene's own
+                    setting, usually no limit)
+  --ssaa <n>        render n times wider and higher and scale down to the final size at
+                    the end, for clean edges cheaply (default 1, no supersampling)
+  --ssaa-filter <name>
+                    box or bicubic: how --ssaa scales down, box averages each n x n
+                    block, bicubic weighs in the neighboring blocks too (default box)
+  --sampling <name> random, stratified or halton: where in the pixel the samples go,
+                    stratified spreads them over a grid for less noise, halton also
+                    spreads out the lens and shutter time samples of depth of field and
+                    motion blur (default stratified)
+  --scene <name>    sphere, plane-cube, all-objects, all-objects-alt-camera, light or
+                    cornell-box (default all-objects-alt-camera)
+  --scene-file <path>
+                    render the scene described in a JSON file instead of --scene
+  --render-all      render every built-in scene, each to its own file in --out-dir
+  --render-scenes <names>
+                    like --render-all, but only the scenes in the comma separated list
+  --out-dir <path>  directory --render-all and --render-scenes write <scene>.<format> to,
+                    created if needed (default renders)
+  --reference-dir <path>
+                    compare each PPM --render-all writes to the file of the same name in
+                    <path> and fail if any pixel differs by more than --tolerance; with
+                    the environment variable BLESS=1 the renders replace those files
+  --tolerance <n>   largest difference (out of 255) --reference-dir allows in any color
+                    channel (default 2)
+  --mode <name>     beauty, depth, normal, albedo or occlusion: the rendered image, or a
+                    pass showing the first surface each pixel sees, for debugging or
+                    compositing (default beauty)
+  --debug-pixel <x,y>
+                    print each bounce of the first sample of the pixel x,y (counted from
+                    the top left) to stderr: where it hits, the material, the light found
+                    there and the attenuation, to see why the pixel has its color
+  --occlusion-distance <d>
+                    how far around a surface the occlusion pass looks for geometry
+                    covering it (default 1)
+  --denoise         smooth out the noise of the rendered image, guided by the normals and
+                    colors of the surfaces so edges stay sharp
+  --variance-output <path>
+                    also write a heatmap of how noisy each pixel still is (with --format
+                    pfm: the variance of each pixel's average), to see where more samples
+                    are needed
+  --tonemap <name>  none, reinhard or aces: how colors brighter than white are
+                    compressed instead of clipped (default none)
+  --checkpoint <path>
+                    save the progress to <path> every few samples per pixel, so a long
+                    render can be resumed after a crash
+  --resume <path>   continue the render saved in the checkpoint <path> (and keep saving
+                    to it) up to --samples; give the other options as in the first run
+  --cache-dir <dir> keep the render of the --scene-file in <dir>, and load it from there
+                    instead of rendering again when neither the scene (nor the files it
+                    names) nor the render settings changed
+  --threads <n>     how many threads render at once, 0 for one per core (default 0)
+  --seed <n>        seed for the random numbers, the same seed renders the same image
+                    (default: a different random seed every run)";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub output: Option<String>,
+    pub format: ImageFormat,
+    // None keeps the value the scene comes with
+    pub width: Option<i32>,
+    pub samples_per_pixel: Option<i32>,
+    // Supersampling factor, 1 renders at the final size
+    pub ssaa: usize,
+    pub ssaa_filter: DownsampleFilter,
+    pub ray_epsilon: Option<f64>,
+    pub far_clip: Option<f64>,
+    pub scene: String,
+    pub scene_file: Option<String>,
+    // Built-in scenes to render one after the other into `out_dir`, instead of `scene`
+    pub batch: Option<Vec<String>>,
+    pub out_dir: String,
+    // Where the reference images to compare the batch against are, if anywhere
+    pub reference_dir: Option<String>,
+    pub tolerance: u8,
+    pub checkpoint: Option<String>,
+    pub resume: Option<String>,
+    // Where renders of scene files are kept to be reused, if anywhere
+    pub cache_dir: Option<String>,
+    pub seed: Option<u64>,
+    // 0 for one per core
+    pub threads: usize,
+    pub tone_map: ToneMap,
+    pub sampling: SampleStrategy,
+    pub mode: RenderMode,
+    pub occlusion_distance: f64,
+    // Pixel (from the top left) whose first sample gets its path printed
+    pub debug_pixel: Option<(usize, usize)>,
+    pub denoise: bool,
+    // Where to write the per pixel variance, if anywhere
+    pub variance_output: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            output: None,
+            format: ImageFormat::Ppm,
+            width: None,
+            samples_per_pixel: None,
+            ssaa: 1,
+            ssaa_filter: DownsampleFilter::Box,
+            ray_epsilon: None,
+            far_clip: None,
+            scene: "all-objects-alt-camera".to_string(),
+            scene_file: None,
+            batch: None,
+            out_dir: "renders".to_string(),
+            reference_dir: None,
+            tolerance: 2,
+            checkpoint: None,
+            resume: None,
+            cache_dir: None,
+            seed: None,
+            threads: 0,
+            tone_map: ToneMap::None,
+            sampling: SampleStrategy::Stratified,
+            mode: RenderMode::Beauty,
+            occlusion_distance: 1.0,
+            debug_pixel: None,
+            denoise: false,
+            variance_output: None,
+        }
+    }
+}
+
+impl Config {
+    // Parse the arguments that follow the program name
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Config, String> {
+        let mut config = Config::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for '{}'", arg))
+            };
+
+            match arg.as_str() {
+                "--output" => config.output = Some(value()?),
+                "--format" => {
+                    let value = value()?;
+                    config.format = ImageFormat::from_name(&value)
+                        .ok_or_else(|| format!("unknown image format '{}'", value))?;
+                }
+                "--width" => config.width = Some(parse_positive(&arg, &value()?)?),
+                "--samples" => config.samples_per_pixel = Some(parse_positive(&arg, &value()?)?),
+                "--ssaa" => config.ssaa = parse_positive(&arg, &value()?)? as usize,
+                "--ssaa-filter" => {
+                    let value = value()?;
+                    config.ssaa_filter = DownsampleFilter::from_name(&value)
+                        .ok_or_else(|| format!("unknown downsampling filter '{}'", value))?;
+                }
+                "--ray-epsilon" => {
+                    let value = value()?;
+                    let epsilon = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|e| *e >= 0.0 && e.is_finite())
+                        .ok_or_else(|| {
+                            format!("'{}' expects a number of 0 or more, got '{}'", arg, value)
+                        })?;
+                    config.ray_epsilon = Some(epsilon);
+                }
+                "--far-clip" => {
+                    let value = value()?;
+                    let distance =
+                        value
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|d| *d > 0.0)
+                            .ok_or_else(|| {
+                                format!("'{}' expects a positive number, got '{}'", arg, value)
+                            })?;
+                    config.far_clip = Some(distance);
+                }
+                "--scene" => config.scene = value()?,
+                "--scene-file" => config.scene_file = Some(value()?),
+                "--render-all" => config.batch = Some(Vec::new()),
+                "--render-scenes" => {
+                    let names = value()?
+                        .split(',')
+                        .map(|name| name.trim().to_string())
+                        .filter(|name| !name.is_empty())
+                        .collect();
+                    config.batch = Some(names);
+                }
+                "--out-dir" => config.out_dir = value()?,
+                "--reference-dir" => config.reference_dir = Some(value()?),
+                "--tolerance" => {
+                    let value = value()?;
+                    config.tolerance = value.parse::<u8>().map_err(|_| {
+                        format!("'{}' expects a number from 0 to 255, got '{}'", arg, value)
+                    })?;
+                }
+                "--sampling" => {
+                    let value = value()?;
+                    config.sampling = SampleStrategy::from_name(&value)
+                        .ok_or_else(|| format!("unknown sampling strategy '{}'", value))?;
+                }
+                "--mode" => {
+                    let value = value()?;
+                    config.mode = RenderMode::from_name(&value)
+                        .ok_or_else(|| format!("unknown render mode '{}'", value))?;
+                }
+                "--occlusion-distance" => {
+                    let value = value()?;
+                    let distance = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|d| *d > 0.0 && d.is_finite())
+                        .ok_or_else(|| {
+                            format!("'{}' expects a positive number, got '{}'", arg, value)
+                        })?;
+                    config.occlusion_distance = distance;
+                }
+                "--debug-pixel" => {
+                    let value = value()?;
+                    let pixel = value.split_once(',').and_then(|(x, y)| {
+                        Some((
+                            x.trim().parse::<usize>().ok()?,
+                            y.trim().parse::<usize>().ok()?,
+                        ))
+                    });
+                    config.debug_pixel = Some(pixel.ok_or_else(|| {
+                        format!("'{}' expects x,y (e.g. 10,20), got '{}'", arg, value)
+                    })?);
+                }
+                "--denoise" => config.denoise = true,
+                "--variance-output" => config.variance_output = Some(value()?),
+                "--tonemap" => {
+                    let value = value()?;
+                    config.tone_map = ToneMap::from_name(&value)
+                        .ok_or_else(|| format!("unknown tone mapping '{}'", value))?;
+                }
+                "--checkpoint" => config.checkpoint = Some(value()?),
+                "--resume" => config.resume = Some(value()?),
+                "--cache-dir" => config.cache_dir = Some(value()?),
+                "--threads" => {
+                    let value = value()?;
+                    config.threads = value
+                        .parse::<usize>()
+                        .map_err(|_| format!("'{}' expects a number, got '{}'", arg, value))?;
+                }
+                "--seed" => {
+                    let value = value()?;
+                    let seed = value
+                        .parse::<u64>()
+                        .map_err(|_| format!("'{}' expects a number, got '{}'", arg, value))?;
+                    config.seed = Some(seed);
+                }
+                _ => return Err(format!("unknown argument '{}'", arg)),
+            }
+        }
+
+        // The supersampled image has to fit the i32 pixel counts the renderer works in
+        if let Some(width) = config.width {
+            if width.checked_mul(config.ssaa as i32).is_none() {
+                return Err(format!(
+                    "'--ssaa {}' makes the {} pixel wide image too large",
+                    config.ssaa, width
+                ));
+            }
+        }
+
+        // The options naming a single file can't be shared by a batch of scenes
+        if config.batch.is_some() {
+            for (option, given) in [
+                ("--output", config.output.is_some()),
+                ("--scene-file", config.scene_file.is_some()),
+                ("--variance-output", config.variance_output.is_some()),
+                ("--checkpoint", config.checkpoint.is_some()),
+                ("--resume", config.resume.is_some()),
+            ] {
+                if given {
+                    return Err(format!(
+                        "'{}' can't be used when rendering several scenes",
+                        option
+                    ));
+                }
+            }
+        }
+
+        if config.reference_dir.is_some() {
+            if config.batch.is_none() {
+                return Err(
+                    "'--reference-dir' needs '--render-all' or '--render-scenes'".to_string(),
+                );
+            }
+            if config.format != ImageFormat::Ppm {
+                return Err("'--reference-dir' only compares PPM images".to_string());
+            }
+        }
+
+        // Only scene files are cached: a built-in scene changes with the code, which the
+        // cache can't see
+        if config.cache_dir.is_some() {
+            if config.scene_file.is_none() {
+                return Err("'--cache-dir' needs '--scene-file'".to_string());
+            }
+            if config.resume.is_some() {
+                return Err("'--cache-dir' can't be used with '--resume'".to_string());
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn parse_positive(name: &str, value: &str) -> Result<i32, String> {
+    match value.parse::<i32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!(
+            "'{}' expects a positive number, got '{}'",
+            name, value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Config, String> {
+        Config::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn output_path_and_sizes_are_parsed() {
+        let config = parse(&["--output", "out.ppm", "--width", "400", "--samples", "10"]);
+        assert_eq!(
+            config,
+            Ok(Config {
+                output: Some("out.ppm".to_string()),
+                width: Some(400),
+                samples_per_pixel: Some(10),
+                ..Config::default()
+            })
+        );
+        assert_eq!(parse(&[]), Ok(Config::default()));
+    }
+
+    #[test]
+    fn bad_arguments_are_errors() {
+        assert!(parse(&["--output"]).is_err());
+        assert!(parse(&["--width", "0"]).is_err());
+        assert!(parse(&["--samples", "many"]).is_err());
+        assert!(parse(&["--frobnicate"]).is_err());
+        assert!(parse(&["--ssaa", "70000", "--width", "70000"]).is_err());
+        assert!(parse(&["--ssaa", "4", "--width", "70000"]).is_ok());
+    }
+
+    #[test]
+    fn threads_take_a_count() {
+        assert_eq!(parse(&["--threads", "4"]).unwrap().threads, 4);
+        assert_eq!(parse(&["--threads", "0"]).unwrap().threads, 0);
+        assert_eq!(parse(&[]).unwrap().threads, 0);
+        assert!(parse(&["--threads", "-1"]).is_err());
+        assert!(parse(&["--threads", "many"]).is_err());
+        assert!(parse(&["--threads"]).is_err());
+    }
+}
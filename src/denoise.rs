@@ -0,0 +1,169 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+
+// Passes of the filter, each one reaching twice as far as the one before
+const ITERATIONS: u32 = 5;
+
+// B3 spline weights of the 5x5 filter kernel, per axis
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// How different (squared distance) a neighbor's color, normal and albedo may be before its
+// weight drops off. The color tolerance halves every pass as the image gets cleaner.
+const COLOR_PHI: f64 = 0.01;
+const NORMAL_PHI: f64 = 0.02;
+const ALBEDO_PHI: f64 = 0.01;
+
+// Edge-avoiding a-trous wavelet filter (Dammertz et al. 2010): repeatedly blurs the noisy
+// image with a 5x5 kernel whose taps spread further apart every pass, but only averages
+// pixels whose normal and albedo (noise free guides from the first surface each pixel
+// sees) and color are alike. Noise within a surface is smoothed away while the edges of
+// objects, creases and texture details stay sharp.
+pub fn denoise(image: &Framebuffer, normals: &Framebuffer, albedo: &Framebuffer) -> Framebuffer {
+    let (width, height) = (image.width, image.height);
+    assert!(
+        (normals.width, normals.height) == (width, height)
+            && (albedo.width, albedo.height) == (width, height),
+        "guide buffers do not match the image size"
+    );
+
+    let mut current = image.clone();
+    let mut color_phi = COLOR_PHI;
+    for iteration in 0..ITERATIONS {
+        let step = 1i64 << iteration;
+        let guide = box_blur(&current);
+        let mut filtered = Framebuffer::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let color = guide.get(x, y);
+                let normal = normals.get(x, y);
+                let material = albedo.get(x, y);
+
+                let mut sum = Color::new(0.0, 0.0, 0.0);
+                let mut weight_sum = 0.0;
+                for (j, ky) in KERNEL.iter().enumerate() {
+                    let qy = y as i64 + (j as i64 - 2) * step;
+                    if qy < 0 || qy >= height as i64 {
+                        continue;
+                    }
+                    for (i, kx) in KERNEL.iter().enumerate() {
+                        let qx = x as i64 + (i as i64 - 2) * step;
+                        if qx < 0 || qx >= width as i64 {
+                            continue;
+                        }
+                        let (qx, qy) = (qx as usize, qy as usize);
+
+                        let q_color = current.get(qx, qy);
+                        let weight = kx
+                            * ky
+                            * edge_weight(color, guide.get(qx, qy), color_phi)
+                            * edge_weight(normal, normals.get(qx, qy), NORMAL_PHI)
+                            * edge_weight(material, albedo.get(qx, qy), ALBEDO_PHI);
+                        sum += weight * q_color;
+                        weight_sum += weight;
+                    }
+                }
+
+                // The pixel itself always has a weight, so weight_sum is never 0
+                filtered.set(x, y, sum / weight_sum);
+            }
+        }
+        current = filtered;
+        color_phi *= 0.5;
+    }
+    current
+}
+
+// The image averaged over 3x3 pixels and gamma corrected, for comparing colors: a single
+// noisy pixel shouldn't count as an edge, but a shadow's border should
+fn box_blur(image: &Framebuffer) -> Framebuffer {
+    let (width, height) = (image.width, image.height);
+    let mut blurred = Framebuffer::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = Color::new(0.0, 0.0, 0.0);
+            let mut count = 0.0;
+            for qy in y.saturating_sub(1)..(y + 2).min(height) {
+                for qx in x.saturating_sub(1)..(x + 2).min(width) {
+                    sum += image.get(qx, qy);
+                    count += 1.0;
+                }
+            }
+            blurred.set(x, y, display(sum / count));
+        }
+    }
+    blurred
+}
+
+// Colors are compared after gamma correction, where equal differences look about equally
+// different whether the surface is dark or bright
+fn display(c: Color) -> Color {
+    let gamma = |x: f64| x.max(0.0).sqrt();
+    Color::new(gamma(c.x()), gamma(c.y()), gamma(c.z()))
+}
+
+fn edge_weight(a: Color, b: Color, phi: f64) -> f64 {
+    f64::exp(-(a - b).length_squared() / phi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{random_double_range, seed_rng};
+
+    const SIZE: usize = 32;
+
+    // Two flat walls meeting at a crease down the middle: the left one dark, the right one
+    // bright, both with plenty of sampling noise
+    fn noisy_crease() -> (Framebuffer, Framebuffer, Framebuffer) {
+        seed_rng(3);
+        let mut image = Framebuffer::new(SIZE, SIZE);
+        let mut normals = Framebuffer::new(SIZE, SIZE);
+        let mut albedo = Framebuffer::new(SIZE, SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let left = x < SIZE / 2;
+                let level = if left { 0.2 } else { 0.6 };
+                let noise = random_double_range(-0.1, 0.1);
+                image.set(x, y, Color::new(1.0, 1.0, 1.0) * (level + noise));
+                let normal = if left {
+                    Color::new(1.0, 0.5, 0.5)
+                } else {
+                    Color::new(0.5, 0.5, 1.0)
+                };
+                normals.set(x, y, normal);
+                albedo.set(x, y, Color::new(0.7, 0.7, 0.7));
+            }
+        }
+        (image, normals, albedo)
+    }
+
+    // Mean and variance of the red channel over the columns [from, to)
+    fn column_stats(image: &Framebuffer, from: usize, to: usize) -> (f64, f64) {
+        let values: Vec<f64> = (0..SIZE)
+            .flat_map(|y| (from..to).map(move |x| (x, y)))
+            .map(|(x, y)| image.get(x, y).x())
+            .collect();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance =
+            values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / values.len() as f64;
+        (mean, variance)
+    }
+
+    #[test]
+    fn noise_is_smoothed_but_the_crease_stays_sharp() {
+        let (image, normals, albedo) = noisy_crease();
+        let denoised = denoise(&image, &normals, &albedo);
+
+        for (from, to) in [(0, SIZE / 2), (SIZE / 2, SIZE)] {
+            let (_, before) = column_stats(&image, from, to);
+            let (_, after) = column_stats(&denoised, from, to);
+            assert!(after < before / 10.0, "variance {before} -> {after}");
+        }
+
+        // The columns either side of the crease keep their own wall's brightness
+        let (left, _) = column_stats(&denoised, SIZE / 2 - 1, SIZE / 2);
+        let (right, _) = column_stats(&denoised, SIZE / 2, SIZE / 2 + 1);
+        assert!((left - 0.2).abs() < 0.03, "left of the crease {left}");
+        assert!((right - 0.6).abs() < 0.03, "right of the crease {right}");
+    }
+}
@@ -0,0 +1,184 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+
+// How an image rendered larger than asked for (--ssaa) is scaled down to the final size
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DownsampleFilter {
+    // Each pixel is the plain average of the block of pixels it covers
+    Box,
+    // Also takes in some of the neighboring blocks, weighted by a smooth curve (the
+    // Mitchell-Netravali cubic), for edges without the faint stair steps a box leaves.
+    // It can overshoot a little next to hard edges.
+    Bicubic,
+}
+
+impl DownsampleFilter {
+    pub fn from_name(name: &str) -> Option<DownsampleFilter> {
+        match name {
+            "box" => Some(DownsampleFilter::Box),
+            "bicubic" => Some(DownsampleFilter::Bicubic),
+            _ => None,
+        }
+    }
+}
+
+// `image` scaled down by `factor` each way, one pixel for every factor x factor block. The
+// image's width and height must be multiples of the factor.
+pub fn downsample(image: &Framebuffer, factor: usize, filter: DownsampleFilter) -> Framebuffer {
+    assert!(
+        factor > 0 && image.width.is_multiple_of(factor) && image.height.is_multiple_of(factor),
+        "{}x{} image can't be scaled down by {}",
+        image.width,
+        image.height,
+        factor
+    );
+    let (width, height) = (image.width / factor, image.height / factor);
+    let mut scaled = Framebuffer::new(width, height);
+
+    match filter {
+        DownsampleFilter::Box => {
+            let block_size = (factor * factor) as f64;
+            for y in 0..height {
+                for x in 0..width {
+                    let mut sum = Color::new(0.0, 0.0, 0.0);
+                    for dy in 0..factor {
+                        for dx in 0..factor {
+                            sum += image.get(x * factor + dx, y * factor + dy);
+                        }
+                    }
+                    scaled.set(x, y, sum / block_size);
+                }
+            }
+        }
+        DownsampleFilter::Bicubic => {
+            // The filter is the same curve across and down, so the rows are scaled down
+            // first and then the columns of the result
+            let across = cubic_weights(image.width, factor);
+            let down = cubic_weights(image.height, factor);
+            let mut rows = Framebuffer::new(width, image.height);
+            for y in 0..image.height {
+                for (x, weights) in across.iter().enumerate() {
+                    let sum = weights
+                        .iter()
+                        .fold(Color::new(0.0, 0.0, 0.0), |sum, &(from, weight)| {
+                            sum + weight * image.get(from, y)
+                        });
+                    rows.set(x, y, sum);
+                }
+            }
+            for (y, weights) in down.iter().enumerate() {
+                for x in 0..width {
+                    let sum = weights
+                        .iter()
+                        .fold(Color::new(0.0, 0.0, 0.0), |sum, &(from, weight)| {
+                            sum + weight * rows.get(x, from)
+                        });
+                    // The curve's negative parts can take pixels next to bright edges
+                    // below black
+                    let clamp = |c: f64| c.max(0.0);
+                    scaled.set(
+                        x,
+                        y,
+                        Color::new(clamp(sum.x()), clamp(sum.y()), clamp(sum.z())),
+                    );
+                }
+            }
+        }
+    }
+    scaled
+}
+
+// For each pixel of a row (or column) scaled down from `size` pixels, the pixels it takes
+// in and their weights, which add up to 1
+fn cubic_weights(size: usize, factor: usize) -> Vec<Vec<(usize, f64)>> {
+    (0..size / factor)
+        .map(|to| {
+            // The curve reaches 2 pixels of the result either way from the pixel's center
+            let center = (to as f64 + 0.5) * factor as f64;
+            let first = (to * factor).saturating_sub(2 * factor);
+            let last = ((to + 1) * factor + 2 * factor).min(size);
+            let mut weights: Vec<(usize, f64)> = (first..last)
+                .map(|from| {
+                    let distance = (from as f64 + 0.5 - center) / factor as f64;
+                    (from, mitchell(distance))
+                })
+                .filter(|&(_, weight)| weight != 0.0)
+                .collect();
+            // Near the edges of the image part of the curve falls outside it
+            let total: f64 = weights.iter().map(|&(_, weight)| weight).sum();
+            for (_, weight) in &mut weights {
+                *weight /= total;
+            }
+            weights
+        })
+        .collect()
+}
+
+// The Mitchell-Netravali cubic with B = C = 1/3, at `x` pixels (of the scaled down image)
+// from the center
+fn mitchell(x: f64) -> f64 {
+    const B: f64 = 1.0 / 3.0;
+    const C: f64 = 1.0 / 3.0;
+    let x = x.abs();
+    let value = if x < 1.0 {
+        (12.0 - 9.0 * B - 6.0 * C) * x * x * x
+            + (-18.0 + 12.0 * B + 6.0 * C) * x * x
+            + (6.0 - 2.0 * B)
+    } else if x < 2.0 {
+        (-B - 6.0 * C) * x * x * x
+            + (6.0 * B + 30.0 * C) * x * x
+            + (-12.0 * B - 48.0 * C) * x
+            + (8.0 * B + 24.0 * C)
+    } else {
+        0.0
+    };
+    value / 6.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 4x4 image whose pixel (x, y) has the brightness 4 * y + x
+    fn ramp() -> Framebuffer {
+        let mut image = Framebuffer::new(4, 4);
+        for y in 0..4 {
+            for x in 0..4 {
+                let value = (4 * y + x) as f64;
+                image.set(x, y, Color::new(value, value, value));
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn box_filter_averages_each_2x2_block() {
+        let scaled = downsample(&ramp(), 2, DownsampleFilter::Box);
+        assert_eq!((scaled.width, scaled.height), (2, 2));
+        // e.g. the top left block is 0, 1, 4 and 5
+        for (x, y, expected) in [(0, 0, 2.5), (1, 0, 4.5), (0, 1, 10.5), (1, 1, 12.5)] {
+            assert_eq!(<[f64; 3]>::from(scaled.get(x, y)), [expected; 3]);
+        }
+    }
+
+    #[test]
+    fn bicubic_filter_keeps_flat_areas_and_the_average() {
+        let mut flat = Framebuffer::new(8, 8);
+        for y in 0..8 {
+            for x in 0..8 {
+                flat.set(x, y, Color::new(0.25, 0.5, 1.0));
+            }
+        }
+        let scaled = downsample(&flat, 2, DownsampleFilter::Bicubic);
+        for y in 0..4 {
+            for x in 0..4 {
+                assert!((scaled.get(x, y) - Color::new(0.25, 0.5, 1.0)).length() < 1e-12);
+            }
+        }
+
+        // A ramp still rises along the rows and down the columns
+        let scaled = downsample(&ramp(), 2, DownsampleFilter::Bicubic);
+        assert!(scaled.get(0, 0).x() < scaled.get(1, 0).x());
+        assert!(scaled.get(1, 0).x() < scaled.get(0, 1).x());
+    }
+}
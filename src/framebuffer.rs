@@ -0,0 +1,246 @@
+use std::io::{self, Write};
+
+use crate::color::{self, Color};
+use crate::tile::Tile;
+use crate::tonemap::ToneMap;
+
+// The rendered image in linear color, before tone mapping and gamma correction.
+// (0, 0) is the top left pixel; rows run top to bottom, the way image files store them.
+#[derive(Clone)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    // A black image
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Color::new(0.0, 0.0, 0.0); width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        let index = self.index(x, y);
+        self.pixels[index] = color;
+    }
+
+    // Copy the colors of a tile's pixels, given in the order of Tile::pixels, into place
+    pub fn set_tile(&mut self, tile: &Tile, colors: &[Color]) {
+        assert_eq!(
+            colors.len(),
+            tile.width * tile.height,
+            "colors do not fill the tile"
+        );
+        for ((x, y), &color) in tile.pixels().zip(colors) {
+            self.set(x, y, color);
+        }
+    }
+
+    // Every pixel passed through `f`
+    pub fn map(&self, f: impl Fn(Color) -> Color) -> Framebuffer {
+        Framebuffer {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(|&c| f(c)).collect(),
+        }
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(
+            x < self.width && y < self.height,
+            "pixel ({}, {}) outside the {}x{} image",
+            x,
+            y,
+            self.width,
+            self.height
+        );
+        y * self.width + x
+    }
+
+    // Binary (P6) PPM, tone mapped and gamma corrected
+    pub fn to_ppm(&self, out: &mut impl Write, tone_map: ToneMap) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for &pixel in &self.pixels {
+            out.write_all(&color::to_bytes(pixel, tone_map))?;
+        }
+        Ok(())
+    }
+
+    // Plain text (P3) PPM, one "r g b" line per pixel
+    pub fn to_ppm_text(&self, out: &mut impl Write, tone_map: ToneMap) -> io::Result<()> {
+        write!(out, "P3\n{} {}\n255\n", self.width, self.height)?;
+        for &pixel in &self.pixels {
+            let [r, g, b] = color::to_bytes(pixel, tone_map);
+            writeln!(out, "{} {} {}", r, g, b)?;
+        }
+        Ok(())
+    }
+
+    // 8 bit RGB PNG, tone mapped and gamma corrected. The image data is stored without
+    // compression, which keeps the encoder small at the cost of larger files.
+    pub fn to_png(&self, out: &mut impl Write, tone_map: ToneMap) -> io::Result<()> {
+        // Every row starts with its filter type, 0 for none
+        let mut raw = Vec::with_capacity(self.height * (1 + 3 * self.width));
+        for row in self.pixels.chunks(self.width) {
+            raw.push(0);
+            for &pixel in row {
+                raw.extend_from_slice(&color::to_bytes(pixel, tone_map));
+            }
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bits per channel, truecolor, deflate, adaptive filtering, no interlacing
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+        png_chunk(out, b"IHDR", &header)?;
+        png_chunk(out, b"IDAT", &zlib_stored(&raw))?;
+        png_chunk(out, b"IEND", &[])
+    }
+
+    // Portable float map (PFM): the linear colors as little endian 32 bit floats.
+    // PFM stores the rows bottom to top; the negative scale in the header marks the
+    // byte order.
+    pub fn to_pfm(&self, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        for row in self.pixels.chunks(self.width).rev() {
+            for pixel in row {
+                for component in [pixel.x(), pixel.y(), pixel.z()] {
+                    out.write_all(&(component as f32).to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Length, type, data and the CRC of type and data
+fn png_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data));
+    out.write_all(&crc.to_be_bytes())
+}
+
+// `data` in a zlib stream of uncompressed deflate blocks (at most 65535 bytes each)
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        // deflate needs at least one block
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        stream.push(last as u8);
+        stream.extend_from_slice(&len.to_le_bytes());
+        stream.extend_from_slice(&(!len).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+pub(crate) fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+pub(crate) fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % MOD;
+        b = (b + a) % MOD;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The header fields and the floats of a PFM file
+    fn read_pfm(data: &[u8]) -> (Vec<String>, Vec<f32>) {
+        let mut fields = Vec::new();
+        let mut start = 0;
+        while fields.len() < 4 {
+            let end = start
+                + data[start..]
+                    .iter()
+                    .position(|b| b.is_ascii_whitespace())
+                    .unwrap();
+            fields.push(String::from_utf8(data[start..end].to_vec()).unwrap());
+            start = end + 1;
+        }
+        let floats = data[start..]
+            .chunks(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        (fields, floats)
+    }
+
+    #[test]
+    fn pfm_reads_back_as_the_same_floats() {
+        let mut image = Framebuffer::new(2, 2);
+        image.set(0, 0, Color::new(0.1, 2.5, 1e-3));
+        image.set(1, 0, Color::new(100.0, 0.0, 0.75));
+        image.set(0, 1, Color::new(-1.0, 1.0 / 3.0, 7.0));
+        image.set(1, 1, Color::new(0.0, 0.5, 65504.0));
+        let mut data = Vec::new();
+        image.to_pfm(&mut data).unwrap();
+
+        let (header, floats) = read_pfm(&data);
+        assert_eq!(header, ["PF", "2", "2", "-1.0"]);
+        // Bottom row first
+        let expected: Vec<f32> = [(0, 1), (1, 1), (0, 0), (1, 0)]
+            .iter()
+            .flat_map(|&(x, y)| {
+                let c = image.get(x, y);
+                [c.x() as f32, c.y() as f32, c.z() as f32]
+            })
+            .collect();
+        assert_eq!(floats, expected);
+    }
+
+    #[test]
+    fn two_by_two_ppm_has_the_pixels_top_row_first() {
+        let mut image = Framebuffer::new(2, 2);
+        image.set(0, 0, Color::new(1.0, 0.0, 0.0));
+        image.set(1, 0, Color::new(0.0, 1.0, 0.0));
+        image.set(0, 1, Color::new(0.0, 0.0, 1.0));
+        image.set(1, 1, Color::new(0.25, 0.25, 0.25));
+
+        let mut binary = Vec::new();
+        image.to_ppm(&mut binary, ToneMap::None).unwrap();
+        let mut expected = b"P6\n2 2\n255\n".to_vec();
+        expected.extend_from_slice(&[255, 0, 0, 0, 255, 0, 0, 0, 255, 128, 128, 128]);
+        assert_eq!(binary, expected);
+
+        let mut text = Vec::new();
+        image.to_ppm_text(&mut text, ToneMap::None).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "P3\n2 2\n255\n255 0 0\n0 255 0\n0 0 255\n128 128 128\n"
+        );
+    }
+}
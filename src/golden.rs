@@ -0,0 +1,76 @@
+// Comparing renders against reference ("golden") images, to catch changes to how the
+// built-in scenes come out. Renders with the same seed and options are identical, but
+// rounding in functions like sin and cos may differ slightly between platforms, hence the
+// tolerance.
+
+use crate::texture;
+
+// Ok if the PPM files `rendered` and `reference` have the same size and no color channel of
+// any pixel differs by more than `tolerance` (out of 255), otherwise what is different
+pub fn compare_ppm(rendered: &[u8], reference: &[u8], tolerance: u8) -> Result<(), String> {
+    let (width, height, pixels) =
+        texture::parse_ppm(rendered).map_err(|e| format!("render: {}", e))?;
+    let (ref_width, ref_height, ref_pixels) =
+        texture::parse_ppm(reference).map_err(|e| format!("reference: {}", e))?;
+    if (width, height) != (ref_width, ref_height) {
+        return Err(format!(
+            "size {}x{} differs from the reference's {}x{}",
+            width, height, ref_width, ref_height
+        ));
+    }
+
+    // Back from [0, 1] to the bytes in the files
+    let byte = |value: f64| (value * 255.0).round() as i32;
+    let mut drifted = 0;
+    let mut largest = 0;
+    for (pixel, ref_pixel) in pixels.iter().zip(&ref_pixels) {
+        let difference = (0..3)
+            .map(|i| (byte(pixel[i]) - byte(ref_pixel[i])).abs())
+            .max()
+            .unwrap_or(0);
+        if difference > tolerance as i32 {
+            drifted += 1;
+        }
+        largest = largest.max(difference);
+    }
+
+    if drifted > 0 {
+        return Err(format!(
+            "{} of {} pixels differ by more than {} (up to {})",
+            drifted,
+            pixels.len(),
+            tolerance,
+            largest
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 2x1 PPM with the given pixels
+    fn ppm(pixels: [[u8; 3]; 2]) -> Vec<u8> {
+        let mut data = b"P6\n2 1\n255\n".to_vec();
+        data.extend(pixels.iter().flatten());
+        data
+    }
+
+    #[test]
+    fn drift_within_the_tolerance_passes_and_beyond_it_fails() {
+        let reference = ppm([[10, 20, 30], [200, 100, 0]]);
+        assert!(compare_ppm(&reference, &reference, 0).is_ok());
+        let close = ppm([[12, 20, 29], [200, 100, 0]]);
+        assert!(compare_ppm(&close, &reference, 2).is_ok());
+        assert_eq!(
+            compare_ppm(&close, &reference, 1),
+            Err("1 of 2 pixels differ by more than 1 (up to 2)".to_string())
+        );
+
+        let wider = b"P6\n3 1\n255\n\0\0\0\0\0\0\0\0\0".to_vec();
+        assert!(compare_ppm(&wider, &reference, 255)
+            .unwrap_err()
+            .starts_with("size 3x1"));
+    }
+}
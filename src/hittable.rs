@@ -1,14 +1,26 @@
+use crate::aabb::Aabb;
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::vec3::{self, Point3, Vec3};
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Default, Clone)]
 pub struct HitRecord {
     pub p: Point3,
+    // Always faces against the ray that hit, whichever side of the surface that came from
+    // (see set_face_normal), so shading never has to flip it
     pub normal: Vec3,
-    pub mat: Option<Rc<dyn Material>>,
+    pub mat: Option<Arc<dyn Material>>,
     pub t: f64,
+    // Surface coordinates of the hit point, used for texture lookups
+    pub u: f64,
+    pub v: f64,
+    // Unit directions along the surface in which u and v grow, for normal mapping. Zero
+    // for shapes that don't provide them.
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+    // Whether the ray came from the side the shape's outward normal points to, e.g. false
+    // for a ray inside a sphere. Dielectric uses it to tell entering the glass from leaving.
     pub front_face: bool,
 }
 
@@ -17,7 +29,11 @@ impl HitRecord {
         Default::default()
     }
 
+    // Store the normal facing against the ray, and on which side of the surface the ray
+    // was. Also clears the tangent basis, shapes that have one set it afterwards.
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) {
+        self.tangent = Vec3::default();
+        self.bitangent = Vec3::default();
         self.front_face = vec3::dot(r.direction(), outward_normal) < 0.0;
         self.normal = if self.front_face {
             outward_normal
@@ -25,8 +41,71 @@ impl HitRecord {
             -outward_normal
         }
     }
+
+    // Whether the ray hit the outside of the surface (see `front_face`)
+    pub fn is_front_face(&self) -> bool {
+        self.front_face
+    }
 }
 
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
+    // Fills in `rec` for the closest hit with t in [t_min, t_max]. When there is no such
+    // hit `rec` must be left untouched, lists and BVH nodes pass the same record to all
+    // of their children.
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool;
+
+    // Box enclosing the whole object, None for unbounded objects (or empty lists)
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    // Point the object is gathered around, e.g. for sorting objects into a BVH. The center
+    // of the bounding box unless the object knows better, None for unbounded objects.
+    fn centroid(&self) -> Option<Point3> {
+        self.bounding_box().map(|b| 0.5 * (b.min() + b.max()))
+    }
+
+    // Probability density (per solid angle) of `random` picking `direction` from `origin`.
+    // Only needed for objects that are sampled directly as lights.
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    // Random direction from `origin` towards a point on the object
+    fn random(&self, _origin: Point3) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    // Whether the object provides pdf_value and random, so it can be sampled directly as
+    // a light. Emitters that can't are only found by rays bouncing into them.
+    fn can_sample_light(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::shapes::Sphere;
+
+    #[test]
+    fn hit_from_inside_is_not_the_front_face() {
+        let sphere = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        let mut rec = HitRecord::new();
+
+        let inside = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(sphere.hit(&inside, 0.001, f64::INFINITY, &mut rec));
+        assert!(!rec.is_front_face());
+        // The normal still faces the ray, back towards the center
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+
+        let outside = Ray::new(Point3::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0), 0.0);
+        assert!(sphere.hit(&outside, 0.001, f64::INFINITY, &mut rec));
+        assert!(rec.is_front_face());
+        assert!((rec.normal - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+    }
 }
@@ -1,9 +1,16 @@
+use crate::aabb::{self, Aabb};
+use crate::common;
 use crate::hittable::{HitRecord, Hittable};
 use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
 
 #[derive(Default)]
 pub struct HittableList {
     objects: Vec<Box<dyn Hittable>>,
+    // Box around all of the objects, kept up to date as they are added and removed so
+    // rays that miss it can skip the objects altogether. None if any object is unbounded
+    // (or there are none).
+    bbox: Option<Aabb>,
 }
 
 impl HittableList {
@@ -12,13 +19,90 @@ impl HittableList {
     }
 
     pub fn add(&mut self, object: Box<dyn Hittable>) {
+        self.bbox = match (self.objects.is_empty(), self.bbox, object.bounding_box()) {
+            (true, _, object_box) => object_box,
+            (false, Some(list_box), Some(object_box)) => {
+                Some(aabb::surrounding_box(list_box, object_box))
+            }
+            _ => None,
+        };
         self.objects.push(object);
     }
+
+    // Take the object at `index` out of the list, None if there is no such object.
+    // The objects after it move up one place.
+    pub fn remove(&mut self, index: usize) -> Option<Box<dyn Hittable>> {
+        if index < self.objects.len() {
+            let object = self.objects.remove(index);
+            self.bbox = Self::enclosing_box(&self.objects);
+            Some(object)
+        } else {
+            None
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.objects.clear();
+        self.bbox = None;
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Hittable> {
+        self.objects.iter().map(|object| object.as_ref())
+    }
+
+    fn enclosing_box(objects: &[Box<dyn Hittable>]) -> Option<Aabb> {
+        let mut output_box: Option<Aabb> = None;
+
+        for object in objects {
+            // a single unbounded object makes the whole list unbounded
+            let temp_box = object.bounding_box()?;
+            output_box = Some(match output_box {
+                Some(b) => aabb::surrounding_box(b, temp_box),
+                None => temp_box,
+            });
+        }
+
+        output_box
+    }
+}
+
+impl FromIterator<Box<dyn Hittable>> for HittableList {
+    fn from_iter<I: IntoIterator<Item = Box<dyn Hittable>>>(iter: I) -> Self {
+        let objects: Vec<_> = iter.into_iter().collect();
+        HittableList {
+            bbox: Self::enclosing_box(&objects),
+            objects,
+        }
+    }
+}
+
+// Hands out the objects, leaving nothing behind
+impl IntoIterator for HittableList {
+    type Item = Box<dyn Hittable>;
+    type IntoIter = std::vec::IntoIter<Box<dyn Hittable>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.objects.into_iter()
+    }
 }
 
 impl Hittable for HittableList {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        let mut temp_rec = HitRecord::new();
+        // a ray that misses the box around everything can't hit anything in it
+        if let Some(bbox) = &self.bbox {
+            if !bbox.hit(r, t_min, t_max) {
+                return false;
+            }
+        }
+
         // did it hit any object ?
         let mut hit_anything = false;
         // will store the nearest object that the ray hits
@@ -26,18 +110,242 @@ impl Hittable for HittableList {
         let mut closest_so_far = t_max;
 
         for object in &self.objects {
-            // check hit for each object in the list
-            if object.hit(r, t_min, closest_so_far, &mut temp_rec) {
+            // check hit for each object in the list; an object only writes to rec
+            // when it finds something closer than closest_so_far, so rec always ends
+            // up holding the nearest hit without going through a temporary record
+            if object.hit(r, t_min, closest_so_far, rec) {
                 // found a hit
                 hit_anything = true;
                 // update the closest distance
                 // now this becomes our new maximum distance
-                closest_so_far = temp_rec.t;
-                // save the hit info
-                *rec = temp_rec.clone();
+                closest_so_far = rec.t;
             }
         }
 
         hit_anything
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+
+    // Sampling picks one of the objects at random, so the density is the average
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+
+        let weight = 1.0 / self.objects.len() as f64;
+        self.objects
+            .iter()
+            .map(|object| weight * object.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+
+        let index = common::random_int_range(0, self.objects.len() as i32 - 1) as usize;
+        self.objects[index].random(origin)
+    }
+
+    fn can_sample_light(&self) -> bool {
+        !self.objects.is_empty() && self.objects.iter().all(|object| object.can_sample_light())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::shapes::Sphere;
+    use crate::vec3;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn world_can_be_hit_from_several_threads() {
+        let mut world = HittableList::new();
+        world.add(Box::new(Sphere::new(
+            Point3::new(0.0, 0.0, -2.0),
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        let world = Arc::new(world);
+
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let world = Arc::clone(&world);
+                thread::spawn(move || {
+                    let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+                    let mut rec = HitRecord::new();
+                    world.hit(&r, 0.001, f64::INFINITY, &mut rec) && (rec.t - 1.5).abs() < 1e-9
+                })
+            })
+            .collect();
+        for t in threads {
+            assert!(t.join().unwrap());
+        }
+    }
+
+    // A sphere that counts the records it writes, each of which clones its material once
+    struct Counted(Sphere, Arc<AtomicUsize>);
+
+    impl Hittable for Counted {
+        fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+            let hit = self.0.hit(r, t_min, t_max, rec);
+            if hit {
+                self.1.fetch_add(1, Ordering::Relaxed);
+            }
+            hit
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            self.0.bounding_box()
+        }
+    }
+
+    #[test]
+    fn list_writes_the_record_once_per_closer_hit() {
+        // Ten spheres in a row along -Z, the nearest first
+        let writes = Arc::new(AtomicUsize::new(0));
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let spheres: Vec<Box<dyn Hittable>> = (1..=10)
+            .map(|i| {
+                let center = Point3::new(0.0, 0.0, -3.0 * i as f64);
+                let sphere = Sphere::new(center, 1.0, material.clone());
+                Box::new(Counted(sphere, writes.clone())) as Box<dyn Hittable>
+            })
+            .collect();
+        let list: HittableList = spheres.into_iter().collect();
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let mut rec = HitRecord::new();
+        assert!(list.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.t - 2.0).abs() < 1e-9);
+        // The spheres behind the first are looked for only in front of it, so they don't
+        // touch the record
+        assert_eq!(writes.load(Ordering::Relaxed), 1);
+        // The list's objects, this test's own and the one in the record
+        assert_eq!(Arc::strong_count(&material), 12);
+
+        // A miss leaves the record as it was
+        let away = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        assert!(!list.hit(&away, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.t - 2.0).abs() < 1e-9);
+        assert_eq!(writes.load(Ordering::Relaxed), 1);
+    }
+
+    fn small_sphere(x: f64) -> Box<dyn Hittable> {
+        Box::new(Sphere::new(
+            Point3::new(x, 0.0, 0.0),
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        ))
+    }
+
+    #[test]
+    fn add_remove_and_clear_keep_the_count_and_box() {
+        let mut list = HittableList::new();
+        assert!(list.is_empty());
+        assert!(list.remove(0).is_none());
+
+        for x in [0.0, 2.0, 4.0] {
+            list.add(small_sphere(x));
+        }
+        assert_eq!(list.len(), 3);
+        assert!((list.bounding_box().unwrap().max().x() - 4.5).abs() < 1e-12);
+
+        // Taking out the last one shrinks the box, the others move up
+        let removed = list.remove(2).unwrap();
+        assert!((removed.bounding_box().unwrap().min().x() - 3.5).abs() < 1e-12);
+        assert_eq!(list.len(), 2);
+        assert!((list.bounding_box().unwrap().max().x() - 2.5).abs() < 1e-12);
+        assert!(list.remove(2).is_none());
+        assert_eq!(list.len(), 2);
+
+        list.clear();
+        assert!(list.is_empty());
+        assert!(list.bounding_box().is_none());
+    }
+
+    #[test]
+    fn spheres_collect_into_a_list_and_back_out() {
+        let list: HittableList = [0.0, 2.0, 4.0].map(small_sphere).into_iter().collect();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.iter().count(), 3);
+        assert!((list.bounding_box().unwrap().min().x() + 0.5).abs() < 1e-12);
+
+        let objects: Vec<_> = list.into_iter().collect();
+        assert_eq!(objects.len(), 3);
+    }
+
+    // Counts every hit test, whether it hits or not
+    struct Probed(Sphere, Arc<AtomicUsize>);
+
+    impl Hittable for Probed {
+        fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+            self.1.fetch_add(1, Ordering::Relaxed);
+            self.0.hit(r, t_min, t_max, rec)
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            self.0.bounding_box()
+        }
+    }
+
+    #[test]
+    fn rays_missing_a_cluster_test_none_of_it_and_hits_are_kept() {
+        common::seed_rng(9);
+        let tests = Arc::new(AtomicUsize::new(0));
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        // Fifty small spheres within a unit of the origin
+        let spheres: Vec<Sphere> = (0..50)
+            .map(|_| {
+                let center = vec3::random_in_unit_sphere();
+                Sphere::new(center, 0.1, material.clone())
+            })
+            .collect();
+        let cluster: HittableList = spheres
+            .iter()
+            .map(|s| {
+                let copy = Sphere::new(s.center(), s.radius(), material.clone());
+                Box::new(Probed(copy, tests.clone())) as Box<dyn Hittable>
+            })
+            .collect();
+
+        // From a camera 10 away, rays into the sky around the cluster don't look inside
+        let camera = Point3::new(0.0, 0.0, 10.0);
+        for _ in 0..100 {
+            let mut direction = vec3::random_unit_vector();
+            if direction.z() < 0.0 {
+                direction = -direction;
+            }
+            let r = Ray::new(camera, direction, 0.0);
+            assert!(!cluster.hit(&r, 0.001, f64::INFINITY, &mut HitRecord::new()));
+        }
+        assert_eq!(tests.load(Ordering::Relaxed), 0);
+
+        // Rays at the cluster find the same nearest hit as testing every sphere
+        for _ in 0..500 {
+            let target = 1.2 * vec3::random_in_unit_sphere();
+            let r = Ray::new(camera, target - camera, 0.0);
+            let mut rec = HitRecord::new();
+            let hit = cluster.hit(&r, 0.001, f64::INFINITY, &mut rec);
+            let nearest = spheres
+                .iter()
+                .filter_map(|s| {
+                    let mut rec = HitRecord::new();
+                    s.hit(&r, 0.001, f64::INFINITY, &mut rec).then_some(rec.t)
+                })
+                .fold(None, |best: Option<f64>, t| {
+                    Some(best.map_or(t, |b| b.min(t)))
+                });
+            assert_eq!(hit.then_some(rec.t), nearest);
+        }
+    }
 }
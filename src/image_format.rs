@@ -0,0 +1,31 @@
+// File format the rendered image is written in
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ImageFormat {
+    // 8 bit PPM: averaged, tone mapped and gamma corrected, ready to look at
+    Ppm,
+    // 8 bit PNG, the same colors as PPM
+    Png,
+    // Portable float map: the averaged linear colors as 32 bit floats, before tone mapping
+    // and gamma, for tone mapping or compositing in another program
+    Pfm,
+}
+
+impl ImageFormat {
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        match name {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            "pfm" => Some(ImageFormat::Pfm),
+            _ => None,
+        }
+    }
+
+    // File name extension, the same as the name
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+            ImageFormat::Pfm => "pfm",
+        }
+    }
+}
@@ -0,0 +1,40 @@
+pub mod aabb;
+pub mod background;
+pub mod bvh;
+pub mod camera;
+#[cfg(feature = "native")]
+pub mod checkpoint;
+pub mod color;
+pub mod common;
+pub mod config;
+pub mod denoise;
+pub mod downsample;
+pub mod framebuffer;
+#[cfg(feature = "native")]
+pub mod golden;
+pub mod hittable;
+pub mod hittable_list;
+pub mod image_format;
+pub mod mat3;
+pub mod material;
+pub mod mesh;
+pub mod onb;
+pub mod pdf;
+pub mod perlin;
+mod png;
+#[cfg(feature = "native")]
+pub mod progress;
+pub mod ray;
+pub mod render_mode;
+pub mod sampling;
+pub mod scene;
+#[cfg(feature = "native")]
+pub mod scene_cache;
+pub mod scene_loader;
+pub mod shapes;
+pub mod texture;
+pub mod tile;
+pub mod tonemap;
+pub mod tracer;
+pub mod variance;
+pub mod vec3;
@@ -1,158 +1,214 @@
-mod camera;
-mod color;
-mod common;
-mod hittable;
-mod hittable_list;
-mod material;
-mod ray;
-mod shapes;
-mod vec3;
-
-use std::io;
-use std::rc::Rc;
-
-use camera::Camera;
-use color::Color;
-use hittable::{HitRecord, Hittable};
-use hittable_list::HittableList;
-use material::{Dielectric, Lambertian, Metal};
-use ray::Ray;
-use shapes::{Cube, Sphere, Square};
-use vec3::Point3;
-
-fn ray_color(r: &Ray, world: &dyn Hittable, depth: i32) -> Color {
-    // If we've exceeded the ray bounce limit, no more light is gathered
-    if depth <= 0 {
-        return Color::new(0.0, 0.0, 0.0);
-    }
-
-    let mut rec = HitRecord::new();
-    if world.hit(r, 0.001, common::INFINITY, &mut rec) {
-        let mut attenuation = Color::default();
-        let mut scattered = Ray::default();
-        if rec
-            .mat
-            .as_ref()
-            .unwrap()
-            .scatter(r, &rec, &mut attenuation, &mut scattered)
-        {
-            return attenuation * ray_color(&scattered, world, depth - 1);
-        }
-        return Color::new(0.0, 0.0, 0.0);
-    }
-
-    let unit_direction = vec3::unit_vector(r.direction());
-    let t = 0.5 * (unit_direction.y() + 1.0);
-    (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
-}
-
-fn random_scene() -> HittableList {
-    let mut world = HittableList::new();
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
 
-    // Ground
-    let ground_material = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
-    world.add(Box::new(Square::horizontal(
-        Point3::new(0.0, 0.0, 0.0),
-        1000.0,
-        ground_material.clone(),
-    )));
+use ray_tracing::background::SolidBackground;
+use ray_tracing::bvh::BvhNode;
+use ray_tracing::camera::Camera;
+use ray_tracing::checkpoint::Checkpoint;
+use ray_tracing::color::{self, Color};
+use ray_tracing::common;
+use ray_tracing::config::{self, Config};
+use ray_tracing::denoise;
+use ray_tracing::downsample::{self, DownsampleFilter};
+use ray_tracing::framebuffer::Framebuffer;
+use ray_tracing::golden;
+use ray_tracing::hittable::{HitRecord, Hittable};
+use ray_tracing::hittable_list::HittableList;
+use ray_tracing::image_format::ImageFormat;
+use ray_tracing::progress::Progress;
+use ray_tracing::ray::Ray;
+use ray_tracing::render_mode::RenderMode;
+use ray_tracing::sampling::SampleStrategy;
+use ray_tracing::scene::Scene;
+use ray_tracing::scene_cache;
+use ray_tracing::scene_loader;
+use ray_tracing::shapes::prelude::*;
+use ray_tracing::texture::CheckerTexture;
+use ray_tracing::tile::{self, Tile};
+use ray_tracing::tonemap::ToneMap;
+use ray_tracing::tracer::Tracer;
+use ray_tracing::variance;
+use ray_tracing::vec3::{self, Point3};
 
-    // Sphere
-    let sphere_material = Rc::new(Metal::new(Color::new(0.2, 0.7, 0.7), 0.1));
-    world.add(Box::new(Sphere::new(
-        Point3::new(0.0, 1.0, 1.0),
-        1.0,
-        sphere_material,
-    )));
+// Width and height of the pieces the image is rendered in
+const TILE_SIZE: usize = 32;
 
-    // Cube
-    let cube_material = Rc::new(Metal::new(Color::new(0.2, 0.7, 0.7), 0.1));
-    let cube = Cube::new(
-        Point3::new(-4.5, 0.0, 0.0),
-        Point3::new(-2.5, 2.0, 2.0),
-        cube_material,
-    );
-    world.add(Box::new(cube));
+// Samples per pixel taken between two checkpoints
+const CHECKPOINT_INTERVAL: i32 = 16;
 
-    // Cylinder
-    let cylinder_material = Rc::new(Lambertian::new(Color::new(0.8, 1.0, 0.2)));
-    let cylinder = crate::shapes::cylinder::Cylinder::new(
-        Point3::new(3.5, 0.0, 1.0),
-        vec3::Vec3::new(0.0, 1.0, 0.0),
-        0.8,
-        2.0,
-        cylinder_material,
-    );
-    world.add(Box::new(cylinder));
-
-    world
-}
+// Camera rays per pixel for the denoiser's normal and albedo guides
+const GUIDE_SAMPLES: i32 = 4;
 
+#[derive(Debug, Copy, Clone, PartialEq)]
 enum SceneType {
     Sphere,
     PlaneCube,
     AllObjects,
     AllObjectsAltCamera,
+    Light,
+    CornellBox,
+}
+
+impl SceneType {
+    // Every built-in scene, in the order --render-all renders them
+    const ALL: [SceneType; 6] = [
+        SceneType::Sphere,
+        SceneType::PlaneCube,
+        SceneType::AllObjects,
+        SceneType::AllObjectsAltCamera,
+        SceneType::Light,
+        SceneType::CornellBox,
+    ];
+
+    // Name used to pick the scene with --scene
+    fn from_name(name: &str) -> Option<SceneType> {
+        SceneType::ALL
+            .into_iter()
+            .find(|scene_type| scene_type.name() == name)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SceneType::Sphere => "sphere",
+            SceneType::PlaneCube => "plane-cube",
+            SceneType::AllObjects => "all-objects",
+            SceneType::AllObjectsAltCamera => "all-objects-alt-camera",
+            SceneType::Light => "light",
+            SceneType::CornellBox => "cornell-box",
+        }
+    }
+
+    fn build(self) -> Scene {
+        match self {
+            SceneType::Sphere => scene_sphere(),
+            SceneType::PlaneCube => scene_plane_cube(),
+            SceneType::AllObjects => scene_all_objects(),
+            SceneType::AllObjectsAltCamera => scene_all_objects_alt_camera(),
+            SceneType::Light => scene_light(),
+            SceneType::CornellBox => scene_cornell_box(),
+        }
+    }
+}
+
+// The scenes a batch render goes through and the file each is written to: all built-in
+// scenes if `names` is empty, otherwise the named ones in the order given
+fn batch_plan(
+    names: &[String],
+    out_dir: &Path,
+    format: ImageFormat,
+) -> Result<Vec<(SceneType, PathBuf)>, String> {
+    let scene_types = if names.is_empty() {
+        SceneType::ALL.to_vec()
+    } else {
+        names
+            .iter()
+            .map(|name| {
+                SceneType::from_name(name).ok_or_else(|| format!("unknown scene '{}'", name))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    Ok(scene_types
+        .into_iter()
+        .map(|scene_type| {
+            let file_name = format!("{}.{}", scene_type.name(), format.extension());
+            (scene_type, out_dir.join(file_name))
+        })
+        .collect())
 }
 
-fn scene_sphere() -> HittableList {
+// Camera shared by the built-in scenes, only the viewpoint differs
+fn camera(lookfrom: Point3, lookat: Point3) -> Camera {
+    Camera::builder()
+        .look_from(lookfrom)
+        .look_at(lookat)
+        .vfov(43.0)
+        .aspect_ratio(3.0 / 2.0)
+        .aperture(0.05)
+        .focus_dist(10.0)
+        .shutter(0.0, 1.0)
+        .build()
+}
+
+fn scene_sphere() -> Scene {
     let mut world = HittableList::new();
-    let ground_material = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_texture = Arc::new(CheckerTexture::from_colors(
+        3.0,
+        Color::new(0.2, 0.3, 0.1),
+        Color::new(0.9, 0.9, 0.9),
+    ));
+    let ground_material = Arc::new(Lambertian::from_texture(ground_texture));
     world.add(Box::new(Square::horizontal(
         Point3::new(0.0, 0.0, 0.0),
         1000.0,
         ground_material,
     )));
-    let sphere_material = Rc::new(Metal::new(Color::new(0.8, 0.2, 0.2), 0.1));
+    let sphere_material = Arc::new(Metal::new(Color::new(0.8, 0.2, 0.2), 0.1));
     world.add(Box::new(Sphere::new(
         Point3::new(0.0, 1.0, 0.0),
         1.0,
         sphere_material,
     )));
-    world
+
+    let cam = camera(Point3::new(0.0, 2.0, 5.0), Point3::new(0.0, 1.0, 0.0));
+    Scene::new(world, cam)
 }
 
-fn scene_plane_cube() -> HittableList {
+fn scene_plane_cube() -> Scene {
     let mut world = HittableList::new();
-    let ground_material = Rc::new(Lambertian::new(Color::new(0.4, 0.15, 0.05)));
+    let ground_material = Arc::new(Lambertian::new(Color::new(0.4, 0.15, 0.05)));
     world.add(Box::new(Square::horizontal(
         Point3::new(0.0, 0.0, 0.0),
         1000.0,
         ground_material,
     )));
-    let cube_material = Rc::new(Metal::new(Color::new(0.1, 0.2, 0.2), 0.2)); // dimmer
+    let cube_material = Arc::new(Metal::new(Color::new(0.1, 0.2, 0.2), 0.2)); // dimmer
     let cube = Cube::new(
         Point3::new(-1.0, 0.0, -1.0),
         Point3::new(1.0, 2.0, 1.0),
         cube_material,
     );
     world.add(Box::new(cube));
-    world
+
+    let cam = camera(Point3::new(0.0, 3.0, 7.0), Point3::new(0.0, 1.0, 0.0));
+    Scene::new(world, cam)
 }
 
-fn scene_all_objects() -> HittableList {
+fn scene_all_objects() -> Scene {
     let mut world = HittableList::new();
-    let ground_material = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
     world.add(Box::new(Square::horizontal(
         Point3::new(0.0, 0.0, 0.0),
         1000.0,
         ground_material.clone(),
     )));
-    let sphere_material = Rc::new(Metal::new(Color::new(0.2, 0.7, 0.7), 0.1));
+    let sphere_material = Arc::new(Metal::new(Color::new(0.2, 0.7, 0.7), 0.1));
     world.add(Box::new(Sphere::new(
         Point3::new(0.0, 1.0, 1.0),
         1.0,
         sphere_material,
     )));
-    let cube_material = Rc::new(Metal::new(Color::new(0.2, 0.7, 0.7), 0.1));
+    let glass_material = Arc::new(Dielectric::new(1.5));
+    world.add(Box::new(Sphere::new(
+        Point3::new(-1.5, 0.6, 3.0),
+        0.6,
+        glass_material,
+    )));
+    let cube_material = Arc::new(Metal::new(Color::new(0.2, 0.7, 0.7), 0.1));
     let cube = Cube::new(
         Point3::new(-4.5, 0.0, 0.0),
         Point3::new(-2.5, 2.0, 2.0),
         cube_material,
     );
     world.add(Box::new(cube));
-    let cylinder_material = Rc::new(Lambertian::new(Color::new(0.8, 1.0, 0.2)));
-    let cylinder = crate::shapes::cylinder::Cylinder::new(
+    let cylinder_material = Arc::new(Lambertian::new(Color::new(0.8, 1.0, 0.2)));
+    let cylinder = Cylinder::new(
         Point3::new(3.5, 0.0, 1.0),
         vec3::Vec3::new(0.0, 1.0, 0.0),
         0.8,
@@ -160,80 +216,993 @@ fn scene_all_objects() -> HittableList {
         cylinder_material,
     );
     world.add(Box::new(cylinder));
-    world
+
+    let cam = camera(Point3::new(0.0, 3.0, 10.0), Point3::new(0.0, 1.0, 1.0));
+    Scene::new(world, cam)
 }
 
-fn scene_all_objects_alt_camera() -> (HittableList, Point3, Point3) {
-    let world = scene_all_objects();
-    let lookfrom = Point3::new(0.0, 5.0, 10.0); // Camera from the side
+fn scene_all_objects_alt_camera() -> Scene {
+    let mut scene = scene_all_objects();
+    // Camera from the side
+    scene.camera = camera(Point3::new(0.0, 5.0, 10.0), Point3::new(0.0, 1.0, 1.0));
+    scene
+}
 
-    let lookat = Point3::new(0.0, 1.0, 1.0);
-    (world, lookfrom, lookat)
+// A small glowing panel above a diffuse floor, lit only by the panel itself
+fn scene_light() -> Scene {
+    let mut world = HittableList::new();
+    let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Box::new(Square::horizontal(
+        Point3::new(0.0, 0.0, 0.0),
+        1000.0,
+        ground_material,
+    )));
+    let sphere_material = Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.3)));
+    world.add(Box::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        sphere_material,
+    )));
+    let light_material = Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0)));
+    let light_panel =
+        || Square::horizontal(Point3::new(0.0, 3.5, 0.0), 2.0, light_material.clone());
+    world.add(Box::new(light_panel()));
+
+    let cam = camera(Point3::new(0.0, 3.0, 8.0), Point3::new(0.0, 1.0, 0.0));
+    let mut scene = Scene::new(world, cam);
+    scene.lights.add(Box::new(light_panel()));
+    // Render against black so the emission shows
+    scene.background = Box::new(SolidBackground(Color::new(0.0, 0.0, 0.0)));
+    scene
+}
+
+// The classic test of light transport: a white room with a red wall on the left and a
+// green one on the right, lit only by a square lamp in the ceiling, with two white boxes
+// turned slightly towards each other. The room is 2 units wide, high and deep, open at
+// the front where the camera looks in.
+fn scene_cornell_box() -> Scene {
+    let mut world = HittableList::new();
+    let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    let light_material = Arc::new(DiffuseLight::new(Color::new(12.0, 12.0, 12.0)));
+
+    let (x, y, z) = (
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+    );
+    // Each wall faces into the room
+    let wall = |center: Point3, u_axis: Vec3, v_axis: Vec3, material: Arc<Lambertian>| {
+        Box::new(Rectangle::new(center, u_axis, v_axis, 1.0, 1.0, material))
+    };
+    world.add(wall(Point3::new(-1.0, 1.0, 0.0), y, z, red));
+    world.add(wall(Point3::new(1.0, 1.0, 0.0), z, y, green));
+    world.add(wall(Point3::new(0.0, 0.0, 0.0), z, x, white.clone()));
+    world.add(wall(Point3::new(0.0, 2.0, 0.0), x, z, white.clone()));
+    world.add(wall(Point3::new(0.0, 1.0, -1.0), x, y, white.clone()));
+
+    // Just below the ceiling, so the two don't fight over the same hits
+    let lamp = || {
+        Rectangle::new(
+            Point3::new(0.0, 1.999, 0.0),
+            x,
+            z,
+            0.25,
+            0.25,
+            light_material.clone(),
+        )
+    };
+    world.add(Box::new(lamp()));
+
+    let tall_box = RectangularBox::new(
+        Point3::new(-0.3, 0.0, -0.3),
+        Point3::new(0.3, 1.2, 0.3),
+        white.clone(),
+    );
+    world.add(Box::new(Translate::new(
+        Box::new(RotateY::new(Box::new(tall_box), 15.0)),
+        Vec3::new(-0.35, 0.0, -0.35),
+    )));
+    let short_box = RectangularBox::new(
+        Point3::new(-0.3, 0.0, -0.3),
+        Point3::new(0.3, 0.6, 0.3),
+        white,
+    );
+    world.add(Box::new(Translate::new(
+        Box::new(RotateY::new(Box::new(short_box), -18.0)),
+        Vec3::new(0.35, 0.0, 0.3),
+    )));
+
+    // Square, without depth of field, far enough back to see the whole opening
+    let cam = Camera::builder()
+        .look_from(Point3::new(0.0, 1.0, 3.8))
+        .look_at(Point3::new(0.0, 1.0, 0.0))
+        .vfov(40.0)
+        .aspect_ratio(1.0)
+        .build();
+    let mut scene = Scene::new(world, cam);
+    scene.aspect_ratio = 1.0;
+    scene.lights.add(Box::new(lamp()));
+    scene.background = Box::new(SolidBackground(Color::new(0.0, 0.0, 0.0)));
+    scene
 }
 
 fn main() {
-    // Image
+    let config = Config::from_args(env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("error: {}\n\n{}", e, config::USAGE);
+        process::exit(2);
+    });
+
+    // Everything random (scene layout, BVH split axes, pixel samples) follows from this seed
+    let seed = config.seed.unwrap_or_else(rand::random);
+    common::seed_rng(seed);
+
+    let cores = available_cores();
+    if config.threads > cores {
+        eprintln!(
+            "warning: rendering with {} threads, but there are only {} cores",
+            config.threads, cores
+        );
+    }
+
+    if let Some(names) = &config.batch {
+        render_batch(names, &config, seed);
+        return;
+    }
+
+    // Select the scene to render: a scene file, or one of the built-in scenes
+    let mut scene = match &config.scene_file {
+        Some(path) => scene_loader::load_scene(path).unwrap_or_else(|e| {
+            eprintln!("error: could not load scene '{}': {}", path, e);
+            process::exit(1);
+        }),
+        None => {
+            let scene_type = SceneType::from_name(&config.scene).unwrap_or_else(|| {
+                eprintln!(
+                    "error: unknown scene '{}'\n\n{}",
+                    config.scene,
+                    config::USAGE
+                );
+                process::exit(2);
+            });
+            scene_type.build()
+        }
+    };
+    override_settings(&mut scene, &config);
+
+    render(scene, &config, seed);
+}
+
+fn available_cores() -> usize {
+    thread::available_parallelism().map_or(1, |cores| cores.get())
+}
+
+// Threads asked for with --threads, where 0 means one per core
+fn thread_count(config: &Config) -> usize {
+    match config.threads {
+        0 => available_cores(),
+        n => n,
+    }
+}
+
+// Settings given on the command line win over the scene's own
+fn override_settings(scene: &mut Scene, config: &Config) {
+    if let Some(width) = config.width {
+        scene.image_width = width;
+    }
+    if let Some(samples_per_pixel) = config.samples_per_pixel {
+        scene.samples_per_pixel = samples_per_pixel;
+    }
+    if let Some(ray_epsilon) = config.ray_epsilon {
+        scene.ray_epsilon = ray_epsilon;
+    }
+    if let Some(far_clip) = config.far_clip {
+        scene.far_clip = far_clip;
+    }
+}
+
+// Render several built-in scenes in one run, each into its own file in the output directory.
+// Every scene starts from the same seed, so each file is the image a run with just that
+// --scene would give. With a reference directory the files are then checked against the
+// ones in there (or replace them, with BLESS=1), exiting with an error if any differ.
+fn render_batch(names: &[String], config: &Config, seed: u64) {
+    let bless = env::var("BLESS").is_ok_and(|value| value == "1");
+    let plan = batch_plan(names, Path::new(&config.out_dir), config.format).unwrap_or_else(|e| {
+        eprintln!("error: {}\n\n{}", e, config::USAGE);
+        process::exit(2);
+    });
+    fs::create_dir_all(&config.out_dir).unwrap_or_else(|e| {
+        eprintln!("error: could not create '{}': {}", config.out_dir, e);
+        process::exit(1);
+    });
+
+    let mut failed = Vec::new();
+    for (scene_type, path) in plan {
+        eprintln!("Rendering {} to {}", scene_type.name(), path.display());
+        common::seed_rng(seed);
+        let mut scene = scene_type.build();
+        override_settings(&mut scene, config);
+        let scene_config = Config {
+            output: Some(path.to_string_lossy().into_owned()),
+            ..config.clone()
+        };
+        render(scene, &scene_config, seed);
 
-    const ASPECT_RATIO: f64 = 3.0 / 2.0;
-    const IMAGE_WIDTH: i32 = 800;
-    const IMAGE_HEIGHT: i32 = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as i32;
-    const SAMPLES_PER_PIXEL: i32 = 500;
-    const MAX_DEPTH: i32 = 50;
+        if let Some(reference_dir) = &config.reference_dir {
+            let reference = Path::new(reference_dir).join(path.file_name().unwrap());
+            let result = if bless {
+                fs::create_dir_all(reference_dir)
+                    .and_then(|_| fs::copy(&path, &reference))
+                    .map(|_| "blessed".to_string())
+                    .map_err(|e| format!("could not write '{}': {}", reference.display(), e))
+            } else {
+                match (fs::read(&path), fs::read(&reference)) {
+                    (Ok(rendered), Ok(expected)) => {
+                        golden::compare_ppm(&rendered, &expected, config.tolerance)
+                            .map(|_| "matches the reference".to_string())
+                    }
+                    (Err(e), _) => Err(format!("could not read '{}': {}", path.display(), e)),
+                    (_, Err(e)) => Err(format!(
+                        "could not read '{}': {} (BLESS=1 creates it)",
+                        reference.display(),
+                        e
+                    )),
+                }
+            };
+            match result {
+                Ok(status) => eprintln!("{}: {}", scene_type.name(), status),
+                Err(e) => {
+                    eprintln!("{}: {}", scene_type.name(), e);
+                    failed.push(scene_type.name());
+                }
+            }
+        }
+    }
 
-    // Select the scene to render:
-    let scene_type = SceneType::AllObjectsAltCamera;
+    if !failed.is_empty() {
+        eprintln!("error: does not match the reference: {}", failed.join(", "));
+        process::exit(1);
+    }
+}
+
+fn render(scene: Scene, config: &Config, seed: u64) {
+    // Put the scene objects in a bounding volume hierarchy instead of testing each one per ray
+    const USE_BVH: bool = true;
 
-    // World and camera setup
-    let (world, lookfrom, lookat) = match scene_type {
-        SceneType::Sphere => {
-            let w = scene_sphere();
-            (w, Point3::new(0.0, 2.0, 5.0), Point3::new(0.0, 1.0, 0.0))
+    // With supersampling the image is rendered --ssaa times wider and higher, and only
+    // scaled down to the final size at the end
+    let ssaa = config.ssaa;
+    let (final_width, final_height) = (scene.image_width, scene.image_height());
+    // --width is checked against --ssaa up front, but a scene file can give its own width
+    let (Some(image_width), Some(image_height)) = (
+        final_width.checked_mul(ssaa as i32),
+        final_height.checked_mul(ssaa as i32),
+    ) else {
+        eprintln!(
+            "error: '--ssaa {}' makes the {}x{} image too large",
+            ssaa, final_width, final_height
+        );
+        process::exit(2);
+    };
+    let samples_per_pixel = scene.samples_per_pixel;
+    let cam = &scene.camera;
+
+    // Where the render is kept with --cache-dir, decided before the scene is taken apart
+    let cache_path = config.cache_dir.as_deref().map(|dir| {
+        let scene_file = config.scene_file.as_deref().unwrap_or_default();
+        let hash = scene_cache::scene_file_hash(scene_file, &cache_settings(&scene, config))
+            .unwrap_or_else(|e| {
+                eprintln!("error: could not read scene file '{}': {}", scene_file, e);
+                process::exit(1);
+            });
+        scene_cache::cache_path(dir, hash)
+    });
+
+    let world: Box<dyn Hittable> = if USE_BVH {
+        // Unbounded objects like planes can't go in the hierarchy, they're tested next to it
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = scene
+            .world
+            .into_iter()
+            .partition(|object| object.bounding_box().is_some());
+        let mut world: HittableList = unbounded.into_iter().collect();
+        if !bounded.is_empty() {
+            world.add(Box::new(BvhNode::new(bounded)));
         }
-        SceneType::PlaneCube => {
-            let w = scene_plane_cube();
-            (w, Point3::new(0.0, 3.0, 7.0), Point3::new(0.0, 1.0, 0.0))
+        Box::new(world)
+    } else {
+        Box::new(scene.world)
+    };
+    let tracer = Tracer {
+        world: world.as_ref(),
+        lights: &scene.lights,
+        background: scene.background.as_ref(),
+        max_depth: scene.max_depth,
+        ray_epsilon: scene.ray_epsilon,
+        far_clip: scene.far_clip,
+        mode: config.mode,
+        occlusion_distance: config.occlusion_distance,
+    };
+    // Auxiliary passes are written as they are
+    let tone_map = match config.mode {
+        RenderMode::Beauty => config.tone_map,
+        _ => ToneMap::None,
+    };
+
+    if let Some((x, y)) = config.debug_pixel {
+        if x >= final_width as usize || y >= final_height as usize {
+            eprintln!(
+                "error: --debug-pixel {},{} is outside the {}x{} image",
+                x, y, final_width, final_height
+            );
+            process::exit(2);
         }
-        SceneType::AllObjects => {
-            let w = scene_all_objects();
-            (w, Point3::new(0.0, 3.0, 10.0), Point3::new(0.0, 1.0, 1.0))
+        eprintln!("pixel {},{}, sample 0:", x, y);
+        // With supersampling, the top left of the pixels rendered for it
+        debug_pixel(
+            &tracer,
+            cam,
+            config.sampling,
+            (image_width, image_height),
+            (x * ssaa, y * ssaa),
+            seed,
+            samples_per_pixel,
+        );
+    }
+
+    // Render, continuing from a checkpoint if there is one. With checkpoints the samples
+    // are taken in passes over the whole image, saving the sums after each pass. A render
+    // found in the cache (see `cache_path` above) is taken as it is.
+
+    let cached = cache_path
+        .as_ref()
+        .filter(|path| Path::new(path).exists())
+        .and_then(|path| match Checkpoint::load(path) {
+            Ok(checkpoint) => {
+                eprintln!("using the cached render '{}'", path);
+                Some(checkpoint)
+            }
+            Err(e) => {
+                eprintln!(
+                    "warning: could not load the cached render '{}', rendering again: {}",
+                    path, e
+                );
+                None
+            }
+        });
+
+    let tiles = tile::tiles(image_width as usize, image_height as usize, TILE_SIZE);
+    let mut accumulated = match (cached, &config.resume) {
+        (Some(checkpoint), _) => checkpoint,
+        (None, Some(path)) => {
+            let checkpoint = Checkpoint::load(path).unwrap_or_else(|e| {
+                eprintln!("error: could not load checkpoint '{}': {}", path, e);
+                process::exit(1);
+            });
+            let (width, height) = (checkpoint.sums.width, checkpoint.sums.height);
+            if (width, height) != (image_width as usize, image_height as usize) {
+                eprintln!(
+                    "error: checkpoint '{}' is {}x{}, the image is {}x{}",
+                    path, width, height, image_width, image_height
+                );
+                process::exit(1);
+            }
+            checkpoint
         }
-        SceneType::AllObjectsAltCamera => {
-            let (w, lookfrom, lookat) = scene_all_objects_alt_camera();
-            (w, lookfrom, lookat)
+        (None, None) => Checkpoint::new(image_width as usize, image_height as usize),
+    };
+    let checkpoint_path = config.checkpoint.as_ref().or(config.resume.as_ref());
+    let pass_samples = match checkpoint_path {
+        Some(_) => CHECKPOINT_INTERVAL,
+        None => samples_per_pixel,
+    };
+    let samples_left = (samples_per_pixel - accumulated.samples).max(0);
+    let passes = (samples_left + pass_samples - 1) / pass_samples;
+
+    // Each thread takes the next tile nobody has started on until there are none left. Every
+    // sample draws from its own seeded sequence, so the image doesn't depend on which thread
+    // renders what.
+    let threads = thread_count(config).min(tiles.len()).max(1);
+
+    let progress = Progress::start(tiles.len() as u64 * passes as u64);
+    while accumulated.samples < samples_per_pixel {
+        let first_sample = accumulated.samples;
+        let end_sample = samples_per_pixel.min(first_sample + pass_samples);
+        let render_tile = |tile: &Tile| {
+            let mut colors = Vec::with_capacity(tile.width * tile.height);
+            let mut squares = Vec::with_capacity(tile.width * tile.height);
+            for (x, y) in tile.pixels() {
+                // The camera counts rows from the bottom
+                let (i, j) = (x as i32, image_height - 1 - y as i32);
+                let pixel_seed = common::derive_seed(seed, (j * image_width + i) as u64);
+                let mut pixel_color = accumulated.sums.get(x, y);
+                let mut pixel_square = accumulated.squares.get(x, y);
+                let offsets = config.sampling.offsets(
+                    pixel_seed,
+                    first_sample..end_sample,
+                    samples_per_pixel,
+                );
+                for (s, offset) in (first_sample..end_sample).zip(offsets) {
+                    // Samples the camera has no ray for count as black
+                    let Some(r) = sample_ray(
+                        cam,
+                        config.sampling,
+                        (image_width, image_height),
+                        (i, j),
+                        pixel_seed,
+                        s,
+                        offset,
+                    ) else {
+                        continue;
+                    };
+                    let sample = tracer.ray_color(&r);
+
+                    // A NaN or infinite sample would turn the whole pixel black, so it is
+                    // dropped; debug builds stop right there to help find where it came from
+                    debug_assert!(
+                        sample.is_finite(),
+                        "non-finite sample {} at pixel ({}, {})",
+                        sample,
+                        i,
+                        j
+                    );
+                    if sample.is_finite() {
+                        pixel_color += sample;
+                        pixel_square += sample * sample;
+                    }
+                }
+                colors.push(pixel_color);
+                squares.push(pixel_square);
+            }
+            (colors, squares)
+        };
+
+        let next_tile = AtomicUsize::new(0);
+        let rendered: Vec<_> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut rendered = Vec::new();
+                        while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed))
+                        {
+                            let (colors, squares) = render_tile(tile);
+                            progress.inc(1);
+                            rendered.push((tile, colors, squares));
+                        }
+                        rendered
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        });
+        for (tile, colors, squares) in rendered {
+            accumulated.sums.set_tile(tile, &colors);
+            accumulated.squares.set_tile(tile, &squares);
+        }
+        accumulated.samples = end_sample;
+
+        if let Some(path) = checkpoint_path {
+            accumulated.save(path).unwrap_or_else(|e| {
+                eprintln!("error: could not save checkpoint '{}': {}", path, e);
+                process::exit(1);
+            });
+        }
+    }
+    progress.finish();
+
+    if let Some(path) = cache_path.filter(|_| passes > 0) {
+        let saved = match Path::new(&path).parent() {
+            Some(dir) => fs::create_dir_all(dir).and_then(|_| accumulated.save(&path)),
+            None => accumulated.save(&path),
+        };
+        if let Err(e) = saved {
+            eprintln!(
+                "warning: could not save the render to the cache '{}': {}",
+                path, e
+            );
         }
+    }
+
+    let samples_taken = accumulated.samples;
+    let image = accumulated
+        .sums
+        .map(|sum| color::average(sum, samples_taken));
+    let image = if config.denoise && config.mode == RenderMode::Beauty {
+        let (normals, albedo) = render_guides(
+            world.as_ref(),
+            cam,
+            image_width,
+            image_height,
+            scene.ray_epsilon,
+            scene.far_clip,
+            seed,
+        );
+        denoise::denoise(&image, &normals, &albedo)
+    } else {
+        image
+    };
+    let image = if ssaa > 1 {
+        downsample::downsample(&image, ssaa, config.ssaa_filter)
+    } else {
+        image
     };
 
-    let vup = Point3::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.05;
-
-    let cam = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        43.0,
-        ASPECT_RATIO,
-        aperture,
-        dist_to_focus,
+    // Auxiliary passes come out squared for the gamma step, which PFM skips
+    let squared = config.mode != RenderMode::Beauty;
+    write_image(
+        &image,
+        config.output.as_deref(),
+        config.format,
+        tone_map,
+        squared,
     );
 
-    // Render
+    if let Some(path) = &config.variance_output {
+        let variance = variance::variance(&accumulated.sums, &accumulated.squares, samples_taken);
+        // A pixel averaging a block of ssaa x ssaa rendered pixels varies that many times
+        // less than they do (with the bicubic filter, roughly)
+        let variance = if ssaa > 1 {
+            downsample::downsample(&variance, ssaa, DownsampleFilter::Box)
+                .map(|v| v / (ssaa * ssaa) as f64)
+        } else {
+            variance
+        };
+        // PFM gets the variance itself, the other formats a heatmap of it
+        match config.format {
+            ImageFormat::Pfm => {
+                write_image(&variance, Some(path), config.format, ToneMap::None, false)
+            }
+            _ => write_image(
+                &variance::heatmap(&variance),
+                Some(path),
+                config.format,
+                ToneMap::None,
+                true,
+            ),
+        }
+    }
+}
+
+// The render settings that go into the cache key next to the scene file: everything that
+// changes the sums of the samples. The seed only counts when it was chosen, any render of
+// a scene is as good as any other otherwise.
+fn cache_settings(scene: &Scene, config: &Config) -> String {
+    format!(
+        "{}x{} samples {} depth {} epsilon {} far {} ssaa {} {:?} {:?} occlusion {} seed {:?}",
+        scene.image_width,
+        scene.image_height(),
+        scene.samples_per_pixel,
+        scene.max_depth,
+        scene.ray_epsilon,
+        scene.far_clip,
+        config.ssaa,
+        config.sampling,
+        config.mode,
+        config.occlusion_distance,
+        config.seed
+    )
+}
+
+// Camera ray for sample `s` of the pixel (i, j) (rows counted from the bottom), placed at
+// `offset` in the pixel. Each sample gets its own random sequence, seeded here, so the
+// image does not depend on which passes the samples were taken in.
+fn sample_ray(
+    cam: &Camera,
+    sampling: SampleStrategy,
+    (image_width, image_height): (i32, i32),
+    (i, j): (i32, i32),
+    pixel_seed: u64,
+    s: i32,
+    (du, dv): (f64, f64),
+) -> Option<Ray> {
+    common::seed_rng(common::derive_seed(pixel_seed, s as u64));
+    let u = (i as f64 + du) / (image_width - 1) as f64;
+    let v = (j as f64 + dv) / (image_height - 1) as f64;
+    match sampling.lens_and_time(pixel_seed, s) {
+        Some((lens, shutter)) => cam.get_ray_at(u, v, lens, shutter),
+        None => cam.get_ray(u, v),
+    }
+}
 
-    print!("P3\n{} {}\n255\n", IMAGE_WIDTH, IMAGE_HEIGHT);
+// --debug-pixel: follow the first sample of the rendered pixel (x, y), counted from the
+// top left, exactly as the render does, and print each step of its path to stderr
+fn debug_pixel(
+    tracer: &Tracer,
+    cam: &Camera,
+    sampling: SampleStrategy,
+    (image_width, image_height): (i32, i32),
+    (x, y): (usize, usize),
+    seed: u64,
+    samples_per_pixel: i32,
+) {
+    let (i, j) = (x as i32, image_height - 1 - y as i32);
+    let pixel_seed = common::derive_seed(seed, (j * image_width + i) as u64);
+    let offset = sampling.offsets(pixel_seed, 0..1, samples_per_pixel)[0];
+    let Some(r) = sample_ray(
+        cam,
+        sampling,
+        (image_width, image_height),
+        (i, j),
+        pixel_seed,
+        0,
+        offset,
+    ) else {
+        eprintln!("  the camera sees nothing there");
+        return;
+    };
 
-    for j in (0..IMAGE_HEIGHT).rev() {
-        eprint!("\rScanlines remaining: {} ", j);
-        for i in 0..IMAGE_WIDTH {
-            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-            for _ in 0..SAMPLES_PER_PIXEL {
-                let u = (i as f64 + common::random_double()) / (IMAGE_WIDTH - 1) as f64;
-                let v = (j as f64 + common::random_double()) / (IMAGE_HEIGHT - 1) as f64;
-                let r = cam.get_ray(u, v);
-                pixel_color += ray_color(&r, &world, MAX_DEPTH);
+    let mut log = Vec::new();
+    let color = tracer.ray_color_logged(&r, &mut log);
+    for (n, step) in log.iter().enumerate() {
+        eprintln!(
+            "  {}: from {} towards {}",
+            n,
+            step.ray.origin(),
+            step.ray.direction()
+        );
+        match step.hit {
+            Some((p, material)) => {
+                eprintln!("     hit {} at {}, light {}", material, p, step.light)
             }
-            color::write_color(&mut io::stdout(), pixel_color, SAMPLES_PER_PIXEL);
+            None => eprintln!("     missed, background {}", step.light),
+        }
+        if let Some(attenuation) = step.attenuation {
+            eprintln!("     attenuation {}", attenuation);
         }
     }
+    eprintln!("  color {}", color);
+}
+
+// Output: binary PPM when writing to a file, plain text PPM on stdout otherwise. `squared`
+// images hold the squares of the values PFM should store.
+fn write_image(
+    image: &Framebuffer,
+    path: Option<&str>,
+    format: ImageFormat,
+    tone_map: ToneMap,
+    squared: bool,
+) {
+    let binary = path.is_some();
+    let mut out: Box<dyn Write> = match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path).unwrap_or_else(|e| {
+            eprintln!("error: could not create '{}': {}", path, e);
+            process::exit(1);
+        }))),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
 
-    eprint!("\nDone.\n");
+    match format {
+        ImageFormat::Ppm if binary => image.to_ppm(&mut out, tone_map),
+        ImageFormat::Ppm => image.to_ppm_text(&mut out, tone_map),
+        ImageFormat::Png => image.to_png(&mut out, tone_map),
+        ImageFormat::Pfm if squared => image
+            .map(|c| Color::new(c.x().sqrt(), c.y().sqrt(), c.z().sqrt()))
+            .to_pfm(&mut out),
+        ImageFormat::Pfm => image.to_pfm(&mut out),
+    }
+    .expect("writing image");
+    out.flush().expect("writing image");
+}
+
+// Normal and albedo of the first surface each pixel sees, for the denoiser. A few rays per
+// pixel are enough to smooth the edges, these don't have any noise to average out.
+fn render_guides(
+    world: &dyn Hittable,
+    cam: &Camera,
+    image_width: i32,
+    image_height: i32,
+    ray_epsilon: f64,
+    far_clip: f64,
+    seed: u64,
+) -> (Framebuffer, Framebuffer) {
+    let mut normals = Framebuffer::new(image_width as usize, image_height as usize);
+    let mut albedo = Framebuffer::new(image_width as usize, image_height as usize);
+    for y in 0..image_height {
+        let j = image_height - 1 - y;
+        for i in 0..image_width {
+            common::seed_rng(common::derive_seed(!seed, (j * image_width + i) as u64));
+            let mut normal_sum = Color::new(0.0, 0.0, 0.0);
+            let mut albedo_sum = Color::new(0.0, 0.0, 0.0);
+            for s in 0..GUIDE_SAMPLES {
+                let (du, dv) = SampleStrategy::Stratified.offset(s, GUIDE_SAMPLES);
+                let u = (i as f64 + du) / (image_width - 1) as f64;
+                let v = (j as f64 + dv) / (image_height - 1) as f64;
+                let Some(r) = cam.get_ray(u, v) else {
+                    continue;
+                };
+                let mut rec = HitRecord::new();
+                let t_min = Scene::t_min(ray_epsilon, r.origin());
+                if world.hit(&r, t_min, Scene::t_max(far_clip, &r), &mut rec) {
+                    normal_sum += RenderMode::Normal.first_hit_value(&r, &rec);
+                    albedo_sum += RenderMode::Albedo.first_hit_value(&r, &rec);
+                }
+            }
+            let (x, y) = (i as usize, y as usize);
+            normals.set(x, y, color::average(normal_sum, GUIDE_SAMPLES));
+            albedo.set(x, y, color::average(albedo_sum, GUIDE_SAMPLES));
+        }
+    }
+    (normals, albedo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Render the built-in scene `name` with the given extra options into a temporary file
+    // and return the file's contents
+    fn render_scene(name: &str, options: &[&str], seed: u64) -> Vec<u8> {
+        let path = env::temp_dir().join(format!(
+            "ray-tracing-test-{}-{}-{}.img",
+            name,
+            process::id(),
+            thread_id()
+        ));
+        let args = ["--output", path.to_str().unwrap()]
+            .into_iter()
+            .chain(options.iter().copied());
+        let config = Config::from_args(args.map(String::from)).unwrap();
+
+        common::seed_rng(seed);
+        let mut scene = SceneType::from_name(name).unwrap().build();
+        override_settings(&mut scene, &config);
+        render(scene, &config, seed);
+
+        let image = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        image
+    }
+
+    // Tests run on several threads at once, this keeps their files apart
+    fn thread_id() -> String {
+        format!("{:?}", thread::current().id()).replace(|c: char| !c.is_alphanumeric(), "")
+    }
+
+    #[test]
+    fn same_seed_renders_the_same_pixels() {
+        let options = ["--width", "4", "--samples", "4", "--threads", "2"];
+        let first = render_scene("cornell-box", &options, 7);
+        let second = render_scene("cornell-box", &options, 7);
+        assert!(first.starts_with(b"P6\n4 4\n"));
+        assert_eq!(first, second);
+        assert_ne!(first, render_scene("cornell-box", &options, 8));
+    }
+
+    // The pixels of a PPM or PFM file, after its three header lines
+    fn pixel_data(data: &[u8]) -> &[u8] {
+        let mut header_end = 0;
+        for _ in 0..3 {
+            header_end += data[header_end..].iter().position(|&b| b == b'\n').unwrap() + 1;
+        }
+        &data[header_end..]
+    }
+
+    // The colors of a PFM file as written by write_image
+    fn pfm_floats(data: &[u8]) -> Vec<f32> {
+        pixel_data(data)
+            .chunks(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn resumed_render_matches_an_uninterrupted_one() {
+        let checkpoint = env::temp_dir().join(format!(
+            "ray-tracing-test-resume-{}-{}.checkpoint",
+            process::id(),
+            thread_id()
+        ));
+        let checkpoint = checkpoint.to_str().unwrap();
+
+        // Stratified sampling lays its grid out for the number of samples asked for, so
+        // only a render resumed with the same --samples takes the same samples with it
+        for sampling in ["random", "halton"] {
+            let size = ["--width", "4", "--format", "pfm", "--sampling", sampling];
+            let whole = render_scene(
+                "cornell-box",
+                &[&size[..], &["--samples", "200"]].concat(),
+                3,
+            );
+            let first_half = ["--samples", "100", "--checkpoint", checkpoint];
+            render_scene("cornell-box", &[&size[..], &first_half].concat(), 3);
+            let second_half = ["--samples", "200", "--resume", checkpoint];
+            let resumed = render_scene("cornell-box", &[&size[..], &second_half].concat(), 3);
+            assert_eq!(Checkpoint::load(checkpoint).unwrap().samples, 200);
+            fs::remove_file(checkpoint).unwrap();
+
+            let (whole, resumed) = (pfm_floats(&whole), pfm_floats(&resumed));
+            assert_eq!(whole.len(), 4 * 4 * 3);
+            assert!(whole.iter().any(|&c| c > 0.0));
+            // The same samples, only added up in passes
+            for (a, b) in whole.iter().zip(&resumed) {
+                assert!(
+                    (a - b).abs() <= 1e-5 * a.abs().max(1.0),
+                    "{}: {} vs {}",
+                    sampling,
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    // The plan as "scene -> file" lines, to compare in one go
+    fn plan_names(names: &[&str], format: ImageFormat) -> Result<Vec<String>, String> {
+        let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        let plan = batch_plan(&names, Path::new("out"), format)?;
+        Ok(plan
+            .iter()
+            .map(|(scene_type, path)| format!("{} -> {}", scene_type.name(), path.display()))
+            .collect())
+    }
+
+    #[test]
+    fn batch_plan_names_a_file_per_scene() {
+        let sep = std::path::MAIN_SEPARATOR;
+        assert_eq!(
+            plan_names(&["light", "sphere"], ImageFormat::Png).unwrap(),
+            [
+                format!("light -> out{}light.png", sep),
+                format!("sphere -> out{}sphere.png", sep),
+            ]
+        );
+
+        // Nothing named is every built-in scene, in order
+        let all = plan_names(&[], ImageFormat::Ppm).unwrap();
+        assert_eq!(all.len(), SceneType::ALL.len());
+        assert_eq!(all[0], format!("sphere -> out{}sphere.ppm", sep));
+        assert_eq!(all[5], format!("cornell-box -> out{}cornell-box.ppm", sep));
+
+        assert_eq!(
+            plan_names(&["sphere", "teapot"], ImageFormat::Ppm),
+            Err("unknown scene 'teapot'".to_string())
+        );
+    }
+
+    #[test]
+    fn built_in_scenes_match_their_references() {
+        // The same settings the references were made with:
+        // --render-all --seed 1 --width 80 --samples 8 --reference-dir references
+        let failed: Vec<String> = SceneType::ALL
+            .iter()
+            .filter_map(|scene_type| {
+                let name = scene_type.name();
+                let rendered = render_scene(name, &["--width", "80", "--samples", "8"], 1);
+                let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("references")
+                    .join(format!("{}.ppm", name));
+                let expected = fs::read(&path).unwrap();
+                golden::compare_ppm(&rendered, &expected, Config::default().tolerance)
+                    .err()
+                    .map(|e| format!("{}: {}", name, e))
+            })
+            .collect();
+        assert!(failed.is_empty(), "{}", failed.join("\n"));
+    }
+
+    #[test]
+    fn every_built_in_scene_builds_and_the_sphere_renders() {
+        for scene_type in SceneType::ALL {
+            assert!(!scene_type.build().world.is_empty(), "{:?}", scene_type);
+        }
+
+        // 24 x 16: the red metal sphere fills the middle, the sky the top corners
+        let image = render_scene("sphere", &["--width", "24", "--samples", "4"], 1);
+        assert!(image.starts_with(b"P6\n24 16\n255\n"));
+        let pixel = |x: usize, y: usize| {
+            let i = 3 * (y * 24 + x);
+            <[u8; 3]>::try_from(&pixel_data(&image)[i..i + 3]).unwrap()
+        };
+        // Reflecting the sky tints it, but it stays clearly red
+        let [r, g, b] = pixel(12, 8).map(i32::from);
+        assert!(r > g + 40 && r > b + 40, "middle is {:?}", [r, g, b]);
+        let [r, _, b] = pixel(0, 0);
+        assert!(b > r, "corner is {:?}", pixel(0, 0));
+    }
+
+    #[test]
+    fn zero_threads_means_one_per_core() {
+        let config = |threads: &str| Config::from_args(["--threads", threads].map(String::from));
+        assert_eq!(thread_count(&config("4").unwrap()), 4);
+        assert_eq!(thread_count(&config("1").unwrap()), 1);
+        assert_eq!(thread_count(&config("0").unwrap()), available_cores());
+        assert_eq!(thread_count(&Config::default()), available_cores());
+    }
+
+    #[test]
+    fn halton_lens_and_time_samples_are_less_noisy() {
+        // A light sweeping across the middle of the view while the shutter is open, seen
+        // out of focus through a wide lens
+        let cam = Camera::builder()
+            .look_from(Point3::new(0.0, 0.0, 5.0))
+            .look_at(Point3::new(0.0, 0.0, 0.0))
+            .vfov(30.0)
+            .aspect_ratio(1.0)
+            .aperture(0.5)
+            .focus_dist(3.0)
+            .shutter(0.0, 1.0)
+            .build();
+        let light = MovingSphere::new(
+            Point3::new(-1.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            0.0,
+            1.0,
+            0.4,
+            Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0))),
+        );
+
+        // Variance over many seeds of the share of `n` samples of the middle pixel of a 9 x 9
+        // image that see the light
+        let variance = |sampling: SampleStrategy, n: i32| {
+            let estimates: Vec<f64> = (0..400)
+                .map(|pixel_seed| {
+                    let offsets = sampling.offsets(pixel_seed, 0..n, n);
+                    let seen = (0..n)
+                        .filter(|&s| {
+                            let r = sample_ray(
+                                &cam,
+                                sampling,
+                                (9, 9),
+                                (4, 4),
+                                pixel_seed,
+                                s,
+                                offsets[s as usize],
+                            )
+                            .unwrap();
+                            light.hit(&r, 0.001, f64::INFINITY, &mut HitRecord::new())
+                        })
+                        .count();
+                    seen as f64 / n as f64
+                })
+                .collect();
+            let mean = estimates.iter().sum::<f64>() / estimates.len() as f64;
+            estimates
+                .iter()
+                .map(|e| (e - mean) * (e - mean))
+                .sum::<f64>()
+                / estimates.len() as f64
+        };
+
+        // Halton pulls ahead as the samples fill the lens and shutter interval together
+        let random = variance(SampleStrategy::Random, 64);
+        let halton = variance(SampleStrategy::Halton, 64);
+        assert!(random > 0.001, "{}", random);
+        assert!(halton * 2.0 < random, "{} vs {}", halton, random);
+        assert!(variance(SampleStrategy::Halton, 16) < variance(SampleStrategy::Random, 16));
+    }
+
+    #[test]
+    fn cornell_box_has_five_walls_two_boxes_and_one_lamp() {
+        let scene = SceneType::CornellBox.build();
+        assert_eq!(scene.world.len(), 8);
+        assert_eq!(scene.lights.len(), 1);
+
+        // Look at each object from inside the room, and see which ones give off light
+        let eye = Point3::new(0.0, 1.0, 0.9);
+        let glowing = scene
+            .world
+            .iter()
+            .filter(|object| {
+                let bbox = object.bounding_box().unwrap();
+                let target = 0.5 * (bbox.min() + bbox.max());
+                let r = Ray::new(eye, target - eye, 0.0);
+                let mut rec = HitRecord::new();
+                assert!(object.hit(&r, 0.001, f64::INFINITY, &mut rec));
+                let light = rec
+                    .mat
+                    .as_ref()
+                    .unwrap()
+                    .emitted(rec.u, rec.v, &rec.p, r.direction());
+                light.length() > 0.0
+            })
+            .count();
+        assert_eq!(glowing, 1);
+    }
 }
@@ -0,0 +1,160 @@
+use std::ops::Mul;
+
+use crate::vec3::{self, Vec3};
+
+// 3x3 matrix for linear transforms (rotations, scaling) of points, directions and normals
+#[derive(Copy, Clone)]
+pub struct Mat3 {
+    rows: [Vec3; 3],
+}
+
+impl Mat3 {
+    pub fn from_rows(r0: Vec3, r1: Vec3, r2: Vec3) -> Mat3 {
+        Mat3 { rows: [r0, r1, r2] }
+    }
+
+    // The columns are where the matrix sends the X, Y and Z axes
+    pub fn from_columns(c0: Vec3, c1: Vec3, c2: Vec3) -> Mat3 {
+        Mat3::from_rows(c0, c1, c2).transpose()
+    }
+
+    pub fn identity() -> Mat3 {
+        Mat3::from_rows(
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        )
+    }
+
+    // Rotation by `angle` radians around the unit vector `axis`, counterclockwise when
+    // looking at the origin from the tip of the axis (Rodrigues' formula)
+    pub fn from_axis_angle(axis: Vec3, angle: f64) -> Mat3 {
+        let k = vec3::unit_vector(axis);
+        let (sin, cos) = angle.sin_cos();
+        let t = 1.0 - cos;
+
+        // cos * I + sin * [k]x + (1 - cos) * k k^T
+        Mat3::from_rows(
+            Vec3::new(
+                cos + t * k.x() * k.x(),
+                t * k.x() * k.y() - sin * k.z(),
+                t * k.x() * k.z() + sin * k.y(),
+            ),
+            Vec3::new(
+                t * k.y() * k.x() + sin * k.z(),
+                cos + t * k.y() * k.y(),
+                t * k.y() * k.z() - sin * k.x(),
+            ),
+            Vec3::new(
+                t * k.z() * k.x() - sin * k.y(),
+                t * k.z() * k.y() + sin * k.x(),
+                cos + t * k.z() * k.z(),
+            ),
+        )
+    }
+
+    // Rotation by `angle` radians around +Y, taking +X towards -Z
+    pub fn rotation_y(angle: f64) -> Mat3 {
+        let (sin, cos) = angle.sin_cos();
+        Mat3::from_rows(
+            Vec3::new(cos, 0.0, sin),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(-sin, 0.0, cos),
+        )
+    }
+
+    pub fn mul_vec(&self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            vec3::dot(self.rows[0], v),
+            vec3::dot(self.rows[1], v),
+            vec3::dot(self.rows[2], v),
+        )
+    }
+
+    pub fn transpose(&self) -> Mat3 {
+        let [r0, r1, r2] = self.rows;
+        Mat3::from_rows(
+            Vec3::new(r0.x(), r1.x(), r2.x()),
+            Vec3::new(r0.y(), r1.y(), r2.y()),
+            Vec3::new(r0.z(), r1.z(), r2.z()),
+        )
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let [r0, r1, r2] = self.rows;
+        vec3::dot(r0, vec3::cross(r1, r2))
+    }
+
+    // None for singular matrices (e.g. a scale by zero along some axis).
+    // For rotations transpose() gives the same result much cheaper.
+    pub fn inverse(&self) -> Option<Mat3> {
+        let det = self.determinant();
+        if det.abs() < 1e-12 {
+            return None;
+        }
+
+        // The columns of the inverse are the cross products of pairs of rows
+        let [r0, r1, r2] = self.rows;
+        Some(Mat3::from_columns(
+            vec3::cross(r1, r2) / det,
+            vec3::cross(r2, r0) / det,
+            vec3::cross(r0, r1) / det,
+        ))
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        self.mul_vec(v)
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, m: Mat3) -> Mat3 {
+        let columns = m.transpose().rows;
+        Mat3::from_columns(
+            self.mul_vec(columns[0]),
+            self.mul_vec(columns[1]),
+            self.mul_vec(columns[2]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn same(a: Mat3, b: Mat3) -> bool {
+        (0..3).all(|i| (a.rows[i] - b.rows[i]).length() < 1e-12)
+    }
+
+    #[test]
+    fn quarter_turn_about_y_takes_x_to_minus_z() {
+        let turned = Mat3::rotation_y(std::f64::consts::FRAC_PI_2) * Vec3::new(1.0, 0.0, 0.0);
+        assert!((turned - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        let m = Mat3::from_rows(
+            Vec3::new(2.0, 1.0, 0.0),
+            Vec3::new(0.0, 3.0, -1.0),
+            Vec3::new(1.0, 0.0, 4.0),
+        );
+        let inverse = m.inverse().unwrap();
+        assert!(same(inverse * m, Mat3::identity()));
+        assert!(same(m * inverse, Mat3::identity()));
+
+        // A squashed matrix has none
+        let flat = Mat3::from_rows(
+            Vec3::new(1.0, 2.0, 3.0),
+            Vec3::new(2.0, 4.0, 6.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        assert!(flat.inverse().is_none());
+    }
+}
@@ -1,9 +1,14 @@
-use crate::color::Color;
+use crate::color::{self, Color};
 use crate::hittable::HitRecord;
+use crate::onb::OrthoNormalBasis;
+use crate::pdf::{CosinePdf, Pdf};
 use crate::ray::Ray;
+use crate::texture::{SolidColor, Texture};
+use crate::vec3::{Point3, Vec3};
 use crate::{common, vec3};
+use std::sync::Arc;
 
-pub trait Material {
+pub trait Material: Send + Sync {
     fn scatter(
         &self,
         r_in: &Ray,
@@ -11,54 +16,95 @@ pub trait Material {
         attenuation: &mut Color,
         scattered: &mut Ray,
     ) -> bool;
+
+    // Probability density of scatter() sending the ray in the direction of `scattered`.
+    // Stays 0 for materials that don't scatter diffusely (mirrors, glass).
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+
+    // Light given off by the surface itself at the (u, v) surface coordinates / point p,
+    // seen by a ray travelling in `direction`. Black for anything that isn't a light source.
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point3, _direction: Vec3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    // Short name of the kind of material, for debugging output (e.g. --debug-pixel)
+    fn name(&self) -> &'static str {
+        "material"
+    }
 }
 
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(a: Color) -> Lambertian {
+        Lambertian::from_texture(Arc::new(SolidColor::new(a)))
+    }
+
+    pub fn from_texture(a: Arc<dyn Texture>) -> Lambertian {
         Lambertian { albedo: a }
     }
 }
 
 impl Material for Lambertian {
+    fn name(&self) -> &'static str {
+        "lambertian"
+    }
+
     fn scatter(
         &self,
-        _r_in: &Ray,
+        r_in: &Ray,
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
     ) -> bool {
-        let mut scatter_direction = rec.normal + vec3::random_unit_vector();
-
-        // Catch degenerate scatter direction
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
-        }
+        // Cosine weighted around the normal, the distribution of an ideal diffuse surface
+        let scatter_direction = CosinePdf::new(rec.normal).generate();
 
-        *attenuation = self.albedo;
-        *scattered = Ray::new(rec.p, scatter_direction);
+        *attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
+        *scattered = Ray::new(rec.p, scatter_direction, r_in.time());
         true
     }
+
+    // The density of the cosine weighted directions scatter picks
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        CosinePdf::new(rec.normal).value(scattered.direction())
+    }
 }
 
+// Mirror whose reflections are blurred by its fuzz (roughness): 0 is a perfect mirror, 1 is
+// about as rough as a metal gets
 pub struct Metal {
-    albedo: Color,
-    fuzz: f64,
+    albedo: Arc<dyn Texture>,
+    // Read from the red channel, so gray textures give the fuzz directly
+    fuzz: Arc<dyn Texture>,
 }
 
 impl Metal {
+    // How many times the fuzz is drawn before a ray reflected below the surface is absorbed
+    const FUZZ_ATTEMPTS: usize = 4;
+
     pub fn new(a: Color, f: f64) -> Metal {
-        Metal {
-            albedo: a,
-            fuzz: if f < 1.0 { f } else { 1.0 },
-        }
+        Metal::textured(
+            Arc::new(SolidColor::new(a)),
+            Arc::new(SolidColor::new(Color::new(f, f, f))),
+        )
+    }
+
+    // Albedo and fuzz that vary over the surface, e.g. a scratched or worn metal
+    pub fn textured(albedo: Arc<dyn Texture>, fuzz: Arc<dyn Texture>) -> Metal {
+        Metal { albedo, fuzz }
     }
 }
 
 impl Material for Metal {
+    fn name(&self) -> &'static str {
+        "metal"
+    }
+
     fn scatter(
         &self,
         r_in: &Ray,
@@ -68,20 +114,37 @@ impl Material for Metal {
     ) -> bool {
         let reflected = vec3::reflect(vec3::unit_vector(r_in.direction()), rec.normal);
 
-        *attenuation = self.albedo;
-        *scattered = Ray::new(rec.p, reflected + self.fuzz * vec3::random_in_unit_sphere());
+        let fuzz = common::clamp(self.fuzz.value(rec.u, rec.v, &rec.p).x(), 0.0, 1.0);
+        let mut scatter_direction = reflected + fuzz * vec3::random_in_unit_sphere();
+
+        // Fuzz that pushes the reflection below the surface would absorb the ray and make
+        // rough metals too dark, so it is drawn again a few times before giving up
+        for _ in 1..Self::FUZZ_ATTEMPTS {
+            if fuzz == 0.0 || vec3::dot(scatter_direction, rec.normal) > 0.0 {
+                break;
+            }
+            scatter_direction = reflected + fuzz * vec3::random_in_unit_sphere();
+        }
+
+        // With a lot of fuzz the random offset can cancel out the reflection
+        if scatter_direction.near_zero() {
+            scatter_direction = reflected;
+        }
+
+        *attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
+        *scattered = Ray::new(rec.p, scatter_direction, r_in.time());
         vec3::dot(scattered.direction(), rec.normal) > 0.0
     }
 }
 
 pub struct Dielectric {
-    ir: f64, // Index of refraction
+    refraction_index: f64,
 }
 
 impl Dielectric {
     pub fn new(index_of_refraction: f64) -> Dielectric {
         Dielectric {
-            ir: index_of_refraction,
+            refraction_index: index_of_refraction,
         }
     }
 
@@ -94,6 +157,10 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
+    fn name(&self) -> &'static str {
+        "dielectric"
+    }
+
     fn scatter(
         &self,
         r_in: &Ray,
@@ -101,16 +168,18 @@ impl Material for Dielectric {
         attenuation: &mut Color,
         scattered: &mut Ray,
     ) -> bool {
-        let refraction_ratio = if rec.front_face {
-            1.0 / self.ir
+        // Going from air into the material or from the material back out into air
+        let refraction_ratio = if rec.is_front_face() {
+            1.0 / self.refraction_index
         } else {
-            self.ir
+            self.refraction_index
         };
 
         let unit_direction = vec3::unit_vector(r_in.direction());
         let cos_theta = f64::min(vec3::dot(-unit_direction, rec.normal), 1.0);
         let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
 
+        // Total internal reflection: Snell's law has no solution, so the ray must reflect
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
         let direction = if cannot_refract
             || Self::reflectance(cos_theta, refraction_ratio) > common::random_double()
@@ -121,7 +190,740 @@ impl Material for Dielectric {
         };
 
         *attenuation = Color::new(1.0, 1.0, 1.0);
-        *scattered = Ray::new(rec.p, direction);
+        *scattered = Ray::new(rec.p, direction, r_in.time());
+        true
+    }
+}
+
+// Diffuse base under a clear reflective coat, like plastic or varnished wood. How much light
+// the coat reflects follows the Fresnel term of a glass-like coating: little when looking
+// straight at the surface, nearly all at grazing angles. Each ray either reflects off the
+// coat (blurred by `roughness`, tinted by `specular`) or scatters diffusely off the base.
+// Like metal and glass it leaves scattering_pdf at 0, so lights are found by bouncing only.
+pub struct Glossy {
+    diffuse: Color,
+    specular: Color,
+    roughness: f64,
+}
+
+impl Glossy {
+    // Refractive index of the coating
+    const COAT_INDEX: f64 = 1.5;
+
+    pub fn new(diffuse: Color, specular: Color, roughness: f64) -> Glossy {
+        Glossy {
+            diffuse,
+            specular,
+            roughness: common::clamp(roughness, 0.0, 1.0),
+        }
+    }
+}
+
+impl Material for Glossy {
+    fn name(&self) -> &'static str {
+        "glossy"
+    }
+
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        let unit_direction = vec3::unit_vector(r_in.direction());
+        let cos_theta = f64::min(vec3::dot(-unit_direction, rec.normal), 1.0);
+
+        // Picking the coat with the probability it reflects weights the two parts correctly
+        if Dielectric::reflectance(cos_theta, Glossy::COAT_INDEX) > common::random_double() {
+            let reflected = vec3::reflect(unit_direction, rec.normal);
+            let mut direction = reflected + self.roughness * vec3::random_in_unit_sphere();
+            if direction.near_zero() {
+                direction = reflected;
+            }
+            *attenuation = self.specular;
+            *scattered = Ray::new(rec.p, direction, r_in.time());
+            return vec3::dot(direction, rec.normal) > 0.0;
+        }
+
+        let mut direction = rec.normal + vec3::random_unit_vector();
+        if direction.near_zero() {
+            direction = rec.normal;
+        }
+        *attenuation = self.diffuse;
+        *scattered = Ray::new(rec.p, direction, r_in.time());
+        true
+    }
+}
+
+// Classic Phong shading for stylized renders: a diffuse color plus a highlight of the
+// `specular` color around the mirror direction, the tighter the higher `shininess` (the
+// exponent of the cosine to the mirror direction). This is the normalized variant, whose
+// highlight gets brighter as it gets smaller instead of just fading. Each ray takes one of
+// the two parts, in proportion to how bright they are, and samples that part's own
+// distribution. Like Glossy it leaves scattering_pdf at 0.
+pub struct Phong {
+    diffuse: Color,
+    specular: Color,
+    shininess: f64,
+}
+
+impl Phong {
+    pub fn new(diffuse: Color, specular: Color, shininess: f64) -> Phong {
+        Phong {
+            diffuse,
+            specular,
+            shininess: shininess.max(0.0),
+        }
+    }
+}
+
+impl Material for Phong {
+    fn name(&self) -> &'static str {
+        "phong"
+    }
+
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        let diffuse_weight = color::luminance(self.diffuse).max(0.0);
+        let specular_weight = color::luminance(self.specular).max(0.0);
+        let total = diffuse_weight + specular_weight;
+        if total <= 0.0 {
+            return false;
+        }
+
+        // Dividing by the probability of picking a part makes up for it taking all the rays
+        if common::random_double() * total < specular_weight {
+            // cos^n around the mirror direction: with that as the density, the highlight's
+            // (n + 2) / (2 pi) * cos^n times the cosine to the normal leaves just a factor
+            let reflected = vec3::reflect(vec3::unit_vector(r_in.direction()), rec.normal);
+            let n = self.shininess;
+            let cos_alpha = common::random_double().powf(1.0 / (n + 1.0));
+            let sin_alpha = (1.0 - cos_alpha * cos_alpha).max(0.0).sqrt();
+            let phi = 2.0 * common::PI * common::random_double();
+            let direction = OrthoNormalBasis::build_from_w(reflected).local(Vec3::new(
+                phi.cos() * sin_alpha,
+                phi.sin() * sin_alpha,
+                cos_alpha,
+            ));
+
+            let cos_theta = vec3::dot(direction, rec.normal);
+            if cos_theta <= 0.0 {
+                return false;
+            }
+            *attenuation =
+                (n + 2.0) / (n + 1.0) * cos_theta * total / specular_weight * self.specular;
+            *scattered = Ray::new(rec.p, direction, r_in.time());
+            return true;
+        }
+
+        let uvw = OrthoNormalBasis::build_from_w(rec.normal);
+        *attenuation = total / diffuse_weight * self.diffuse;
+        *scattered = Ray::new(
+            rec.p,
+            uvw.local(vec3::random_cosine_direction()),
+            r_in.time(),
+        );
+        true
+    }
+}
+
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn name(&self) -> &'static str {
+        "diffuse_light"
+    }
+
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _attenuation: &mut Color,
+        _scattered: &mut Ray,
+    ) -> bool {
+        // Lights only emit, they don't bounce incoming rays
+        false
+    }
+
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point3, _direction: Vec3) -> Color {
+        self.emit
+    }
+}
+
+// Light that only shines into a cone around `direction`, brightest along the axis and
+// fading out smoothly towards the edge of the cone, `angle` degrees from the axis
+pub struct SpotLight {
+    emit: Color,
+    direction: Vec3,
+    cos_angle: f64,
+}
+
+impl SpotLight {
+    pub fn new(emit: Color, direction: Vec3, angle: f64) -> SpotLight {
+        SpotLight {
+            emit,
+            direction: vec3::unit_vector(direction),
+            cos_angle: common::degrees_to_radians(angle).cos(),
+        }
+    }
+}
+
+impl Material for SpotLight {
+    fn name(&self) -> &'static str {
+        "spot_light"
+    }
+
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _attenuation: &mut Color,
+        _scattered: &mut Ray,
+    ) -> bool {
+        false
+    }
+
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point3, direction: Vec3) -> Color {
+        // The light leaves the surface against the direction of the ray that sees it
+        let cos = vec3::dot(-vec3::unit_vector(direction), self.direction);
+        if cos <= self.cos_angle {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        // Smoothstep from 0 at the edge of the cone to 1 on the axis
+        let t = (cos - self.cos_angle) / (1.0 - self.cos_angle);
+        t * t * (3.0 - 2.0 * t) * self.emit
+    }
+}
+
+// Phase function for volumes: scatters in a uniformly random direction
+pub struct Isotropic {
+    albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(c: Color) -> Isotropic {
+        Isotropic::from_texture(Arc::new(SolidColor::new(c)))
+    }
+
+    pub fn from_texture(a: Arc<dyn Texture>) -> Isotropic {
+        Isotropic { albedo: a }
+    }
+}
+
+impl Material for Isotropic {
+    fn name(&self) -> &'static str {
+        "isotropic"
+    }
+
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        *scattered = Ray::new(rec.p, vec3::random_unit_vector(), r_in.time());
+        *attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
         true
     }
 }
+
+// Wraps another material and tilts the surface normal by a normal map, which makes flat
+// surfaces look bumpy without extra geometry. The map's colors are tangent space
+// directions mapped from [-1, 1] to [0, 1]: red along the tangent (growing u), green along
+// the bitangent (growing v) and blue along the normal, so (0.5, 0.5, 1) leaves the normal
+// alone. Shapes without a tangent basis are shaded with their own normal.
+pub struct NormalMapped {
+    base: Arc<dyn Material>,
+    normal_map: Arc<dyn Texture>,
+}
+
+impl NormalMapped {
+    pub fn new(base: Arc<dyn Material>, normal_map: Arc<dyn Texture>) -> NormalMapped {
+        NormalMapped { base, normal_map }
+    }
+
+    // The hit with the normal from the normal map
+    fn mapped(&self, rec: &HitRecord) -> HitRecord {
+        let mut mapped = rec.clone();
+        if rec.tangent.near_zero() || rec.bitangent.near_zero() {
+            return mapped;
+        }
+
+        let c = self.normal_map.value(rec.u, rec.v, &rec.p);
+        let normal = rec.tangent * (2.0 * c.x() - 1.0)
+            + rec.bitangent * (2.0 * c.y() - 1.0)
+            + rec.normal * (2.0 * c.z() - 1.0);
+
+        // A map pointing into the surface would shade it from behind
+        if vec3::dot(normal, rec.normal) > 0.0 {
+            mapped.normal = vec3::unit_vector(normal);
+        }
+        mapped
+    }
+}
+
+impl Material for NormalMapped {
+    fn name(&self) -> &'static str {
+        "normal_mapped"
+    }
+
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        self.base
+            .scatter(r_in, &self.mapped(rec), attenuation, scattered)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.base.scattering_pdf(r_in, &self.mapped(rec), scattered)
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point3, direction: Vec3) -> Color {
+        self.base.emitted(u, v, p, direction)
+    }
+}
+
+// Wraps another material and tilts its shading normal by the slope of a height map, so a
+// plain grayscale image (brighter is higher, the average of the channels) gives relief
+// detail. `scale` sets how steep the bumps look, negative values turn bumps into dents.
+// The slope is measured between neighboring (u, v) coordinates, so textures that only
+// depend on the hit point (like NoiseTexture) stay flat. Shapes without a tangent basis
+// are shaded with their own normal.
+pub struct BumpMapped {
+    base: Arc<dyn Material>,
+    height_map: Arc<dyn Texture>,
+    scale: f64,
+}
+
+impl BumpMapped {
+    // Step in u and v for measuring the slope of the height map
+    const DELTA: f64 = 1e-3;
+
+    pub fn new(base: Arc<dyn Material>, height_map: Arc<dyn Texture>, scale: f64) -> BumpMapped {
+        BumpMapped {
+            base,
+            height_map,
+            scale,
+        }
+    }
+
+    fn height(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        let c = self.height_map.value(u, v, p);
+        (c.x() + c.y() + c.z()) / 3.0
+    }
+
+    // The hit with the normal leaning away from uphill
+    fn mapped(&self, rec: &HitRecord) -> HitRecord {
+        let mut mapped = rec.clone();
+        if rec.tangent.near_zero() || rec.bitangent.near_zero() {
+            return mapped;
+        }
+
+        let d = Self::DELTA;
+        let (u, v, p) = (rec.u, rec.v, &rec.p);
+        let slope_u = (self.height(u + d, v, p) - self.height(u - d, v, p)) / (2.0 * d);
+        let slope_v = (self.height(u, v + d, p) - self.height(u, v - d, p)) / (2.0 * d);
+        let normal = rec.normal - self.scale * (slope_u * rec.tangent + slope_v * rec.bitangent);
+        mapped.normal = vec3::unit_vector(normal);
+        mapped
+    }
+}
+
+impl Material for BumpMapped {
+    fn name(&self) -> &'static str {
+        "bump_mapped"
+    }
+
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        self.base
+            .scatter(r_in, &self.mapped(rec), attenuation, scattered)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.base.scattering_pdf(r_in, &self.mapped(rec), scattered)
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point3, direction: Vec3) -> Color {
+        self.base.emitted(u, v, p, direction)
+    }
+}
+
+// Plain description of one of the materials above, for materials that are read from or
+// written to files. With the serde feature it (de)serializes as an object tagged by "type",
+// e.g. {"type": "metal", "albedo": [0.8, 0.6, 0.2], "fuzz": 0.1}.
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum MaterialParams {
+    Lambertian {
+        albedo: Color,
+    },
+    Metal {
+        albedo: Color,
+        #[cfg_attr(feature = "serde", serde(default))]
+        fuzz: f64,
+    },
+    Dielectric {
+        refraction_index: f64,
+    },
+    DiffuseLight {
+        emit: Color,
+    },
+}
+
+impl MaterialParams {
+    pub fn build(&self) -> Arc<dyn Material> {
+        match *self {
+            MaterialParams::Lambertian { albedo } => Arc::new(Lambertian::new(albedo)),
+            MaterialParams::Metal { albedo, fuzz } => Arc::new(Metal::new(albedo, fuzz)),
+            MaterialParams::Dielectric { refraction_index } => {
+                Arc::new(Dielectric::new(refraction_index))
+            }
+            MaterialParams::DiffuseLight { emit } => Arc::new(DiffuseLight::new(emit)),
+        }
+    }
+
+    // Whether objects made of this material should also be sampled as lights
+    pub fn is_emissive(&self) -> bool {
+        matches!(self, MaterialParams::DiffuseLight { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::shapes::{Sphere, Square};
+    use crate::texture::CheckerTexture;
+
+    // The hit of `r` on a unit sphere at the origin made of `material`
+    fn hit_unit_sphere(material: Arc<dyn Material>, r: &Ray) -> HitRecord {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, material);
+        let mut rec = HitRecord::new();
+        assert!(sphere.hit(r, 0.001, f64::INFINITY, &mut rec));
+        rec
+    }
+
+    // Scatters `r` off `rec` `n` times, returning the directions of the rays that weren't
+    // absorbed
+    fn scatter_many(material: &dyn Material, r: &Ray, rec: &HitRecord, n: usize) -> Vec<Vec3> {
+        (0..n)
+            .filter_map(|_| {
+                let mut attenuation = Color::default();
+                let mut scattered = Ray::default();
+                material
+                    .scatter(r, rec, &mut attenuation, &mut scattered)
+                    .then(|| scattered.direction())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn glass_bends_rays_by_snells_law() {
+        common::seed_rng(1);
+        let glass = Arc::new(Dielectric::new(1.5));
+
+        // Half way out from the center the ray meets the surface at 30 degrees
+        let r = Ray::new(Point3::new(-5.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let rec = hit_unit_sphere(glass.clone(), &r);
+        let directions = scatter_many(glass.as_ref(), &r, &rec, 1000);
+        assert_eq!(directions.len(), 1000);
+
+        let mut refracted = 0;
+        for direction in directions {
+            let direction = vec3::unit_vector(direction);
+            let cos = vec3::dot(direction, -rec.normal);
+            if cos > 0.0 {
+                // Into the glass at sin 0.5 / 1.5, bent towards the center
+                refracted += 1;
+                let sin = f64::sqrt(1.0 - cos * cos);
+                assert!((sin - 0.5 / 1.5).abs() < 1e-9);
+                assert!(direction.y() < 0.0);
+            } else {
+                let mirror = vec3::reflect(Vec3::new(1.0, 0.0, 0.0), rec.normal);
+                assert!((direction - mirror).length() < 1e-9);
+            }
+        }
+        // Schlick's approximation reflects about 4 percent at this angle
+        assert!(refracted > 900, "{} of 1000 refracted", refracted);
+
+        // Straight through the center nothing bends
+        let r = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let rec = hit_unit_sphere(glass.clone(), &r);
+        for direction in scatter_many(glass.as_ref(), &r, &rec, 100) {
+            let direction = vec3::unit_vector(direction);
+            assert!(direction.x().abs() > 1.0 - 1e-9);
+        }
+    }
+
+    // A hit on the floor (normal +Y) at `p`, for a ray coming down at 45 degrees
+    fn floor_hit(p: Point3) -> (Ray, HitRecord) {
+        let r = Ray::new(
+            p + Vec3::new(-1.0, 1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.0),
+            0.0,
+        );
+        let mut rec = HitRecord::new();
+        rec.p = p;
+        rec.t = 1.0;
+        rec.set_face_normal(&r, Vec3::new(0.0, 1.0, 0.0));
+        (r, rec)
+    }
+
+    // Average angle (in radians) between the scattered rays and the mirror direction
+    fn spread(material: &dyn Material, r: &Ray, rec: &HitRecord) -> f64 {
+        let mirror = vec3::unit_vector(vec3::reflect(r.direction(), rec.normal));
+        let directions = scatter_many(material, r, rec, 1000);
+        directions
+            .iter()
+            .map(|&d| f64::min(vec3::dot(vec3::unit_vector(d), mirror), 1.0).acos())
+            .sum::<f64>()
+            / directions.len() as f64
+    }
+
+    #[test]
+    fn metal_is_as_rough_as_its_texture_says_where_it_is_hit() {
+        common::seed_rng(1);
+        // Cells 1 wide, polished and rough by turns along X
+        let roughness = CheckerTexture::from_colors(
+            common::PI,
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(0.8, 0.8, 0.8),
+        );
+        let metal = Metal::textured(
+            Arc::new(SolidColor::new(Color::new(0.9, 0.9, 0.9))),
+            Arc::new(roughness),
+        );
+
+        let (r, rec) = floor_hit(Point3::new(0.5, 0.5, 0.5));
+        assert!(spread(&metal, &r, &rec) < 1e-9);
+        let (r, rec) = floor_hit(Point3::new(1.5, 0.5, 0.5));
+        assert!(spread(&metal, &r, &rec) > 0.2);
+    }
+
+    #[test]
+    fn glossy_coat_reflects_more_at_grazing_angles() {
+        common::seed_rng(1);
+        // Red coat over a blue base, so the attenuation tells which part a ray took
+        let glossy = Glossy::new(Color::new(0.0, 0.0, 1.0), Color::new(1.0, 0.0, 0.0), 0.0);
+        let reflected_share = |degrees: f64| {
+            let angle = degrees.to_radians();
+            let direction = Vec3::new(angle.sin(), -angle.cos(), 0.0);
+            let r = Ray::new(Point3::new(0.0, 0.0, 0.0) - direction, direction, 0.0);
+            let mut rec = HitRecord::new();
+            rec.t = 1.0;
+            rec.set_face_normal(&r, Vec3::new(0.0, 1.0, 0.0));
+            let reflected = (0..4000)
+                .filter(|_| {
+                    let mut attenuation = Color::default();
+                    let mut scattered = Ray::default();
+                    glossy.scatter(&r, &rec, &mut attenuation, &mut scattered)
+                        && attenuation.x() > 0.0
+                })
+                .count();
+            reflected as f64 / 4000.0
+        };
+
+        let shares: Vec<f64> = [0.0, 45.0, 75.0, 89.0].map(reflected_share).to_vec();
+        // About 4 percent head on, like glass, and most of the light at a grazing angle
+        assert!((shares[0] - 0.04).abs() < 0.015, "{:?}", shares);
+        assert!(
+            shares.windows(2).all(|pair| pair[0] < pair[1]),
+            "{:?}",
+            shares
+        );
+        assert!(shares[3] > 0.7, "{:?}", shares);
+    }
+
+    #[test]
+    fn flat_normal_map_leaves_the_normal_alone() {
+        let gray: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let floor = Square::horizontal(Point3::new(0.0, 0.0, 0.0), 2.0, gray.clone());
+        let r = Ray::new(Point3::new(0.3, 1.0, 0.2), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let mut rec = HitRecord::new();
+        assert!(floor.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert!(!rec.tangent.near_zero());
+
+        let flat = NormalMapped::new(
+            gray.clone(),
+            Arc::new(SolidColor::new(Color::new(0.5, 0.5, 1.0))),
+        );
+        assert!((flat.mapped(&rec).normal - rec.normal).length() < 1e-12);
+
+        // Leaning towards the tangent does tilt it that way
+        let tilted = NormalMapped::new(gray, Arc::new(SolidColor::new(Color::new(1.0, 0.5, 1.0))));
+        let normal = tilted.mapped(&rec).normal;
+        let expected = vec3::unit_vector(rec.normal + rec.tangent);
+        assert!((normal - expected).length() < 1e-12);
+    }
+
+    #[test]
+    fn spot_light_only_shines_into_its_cone() {
+        // Pointing down, 30 degrees to the edge of the cone
+        let spot = SpotLight::new(Color::new(4.0, 4.0, 4.0), Vec3::new(0.0, -1.0, 0.0), 30.0);
+        let p = Point3::new(0.0, 0.0, 0.0);
+        // A ray seeing the light travels up towards it, from `degrees` off the axis
+        let seen_from = |degrees: f64| {
+            let angle = degrees.to_radians();
+            spot.emitted(0.0, 0.0, &p, Vec3::new(angle.sin(), angle.cos(), 0.0))
+        };
+
+        assert!((seen_from(0.0) - Color::new(4.0, 4.0, 4.0)).length() < 1e-12);
+        for degrees in [10.0, 20.0, 29.0] {
+            assert!(seen_from(degrees).x() > 0.0, "dark at {} degrees", degrees);
+        }
+        for degrees in [31.0, 60.0, 90.0, 180.0] {
+            assert_eq!(seen_from(degrees).x(), 0.0, "lit at {} degrees", degrees);
+        }
+    }
+
+    // Height rising `slope` per unit of u
+    struct RampU(f64);
+
+    impl Texture for RampU {
+        fn value(&self, u: f64, _v: f64, _p: &Point3) -> Color {
+            Color::new(1.0, 1.0, 1.0) * (self.0 * u)
+        }
+    }
+
+    #[test]
+    fn bump_map_tilts_the_normal_down_the_slope() {
+        let gray: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let floor = Square::horizontal(Point3::new(0.0, 0.0, 0.0), 2.0, gray.clone());
+        let r = Ray::new(Point3::new(0.3, 1.0, 0.2), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let mut rec = HitRecord::new();
+        assert!(floor.hit(&r, 0.001, f64::INFINITY, &mut rec));
+
+        let level = BumpMapped::new(
+            gray.clone(),
+            Arc::new(SolidColor::new(Color::new(0.3, 0.3, 0.3))),
+            1.0,
+        );
+        assert!((level.mapped(&rec).normal - rec.normal).length() < 1e-12);
+
+        // A slope of 0.5 scaled by 2 leans the normal 45 degrees away from the tangent,
+        // and a negative scale leans it the other way
+        let ramp = BumpMapped::new(gray.clone(), Arc::new(RampU(0.5)), 2.0);
+        let expected = vec3::unit_vector(rec.normal - rec.tangent);
+        assert!((ramp.mapped(&rec).normal - expected).length() < 1e-9);
+        let dents = BumpMapped::new(gray, Arc::new(RampU(0.5)), -2.0);
+        let expected = vec3::unit_vector(rec.normal + rec.tangent);
+        assert!((dents.mapped(&rec).normal - expected).length() < 1e-9);
+    }
+
+    #[test]
+    fn lambertian_scatters_about_the_normal_by_the_cosine() {
+        common::seed_rng(6);
+        let gray = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        // Hit on the side of the sphere, normal +X
+        let r = Ray::new(Point3::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0), 0.0);
+        let rec = hit_unit_sphere(Arc::new(Lambertian::new(Color::default())), &r);
+        let directions = scatter_many(&gray, &r, &rec, 50_000);
+        assert_eq!(directions.len(), 50_000);
+
+        let cosines: Vec<f64> = directions
+            .iter()
+            .map(|&d| vec3::dot(vec3::unit_vector(d), rec.normal))
+            .collect();
+        assert!(cosines.iter().all(|&c| c >= -1e-9));
+        let mean = cosines.iter().sum::<f64>() / cosines.len() as f64;
+        assert!((mean - 2.0 / 3.0).abs() < 0.01, "{}", mean);
+    }
+
+    #[test]
+    fn rough_metal_reflects_nearly_all_its_albedo() {
+        common::seed_rng(10);
+        let albedo = 0.9;
+        let metal = Metal::new(Color::new(albedo, albedo, albedo), 1.0);
+        // Coming in at 70 degrees from the normal, where the fuzz often points down
+        let angle = 70f64.to_radians();
+        let direction = Vec3::new(angle.sin(), -angle.cos(), 0.0);
+        let (_, mut rec) = floor_hit(Point3::new(0.5, 0.0, 0.5));
+        let r = Ray::new(rec.p - direction, direction, 0.0);
+        rec.set_face_normal(&r, Vec3::new(0.0, 1.0, 0.0));
+
+        let n = 20_000;
+        let reflectance = albedo * scatter_many(&metal, &r, &rec, n).len() as f64 / n as f64;
+
+        // Drawing the fuzz only once, as before, loses the rays that end up pointing down
+        let reflected = vec3::reflect(vec3::unit_vector(direction), rec.normal);
+        let kept = (0..n)
+            .filter(|_| vec3::dot(reflected + vec3::random_in_unit_sphere(), rec.normal) > 0.0)
+            .count();
+        let single_draw = albedo * kept as f64 / n as f64;
+
+        assert!(albedo - reflectance < 0.05, "{}", reflectance);
+        assert!(
+            albedo - reflectance < (albedo - single_draw) / 4.0,
+            "{} vs {}",
+            reflectance,
+            single_draw
+        );
+    }
+
+    #[test]
+    fn lambertian_pdf_peaks_along_the_normal_and_is_zero_below() {
+        let gray = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let (r, rec) = floor_hit(Point3::new(0.0, 0.0, 0.0));
+        let pdf = |direction: Vec3| gray.scattering_pdf(&r, &rec, &Ray::new(rec.p, direction, 0.0));
+
+        let straight_up = pdf(Vec3::new(0.0, 1.0, 0.0));
+        assert!((straight_up - 1.0 / common::PI).abs() < 1e-12);
+        for degrees in [10.0, 45.0, 80.0] {
+            let angle = f64::to_radians(degrees);
+            let tilted = pdf(Vec3::new(angle.sin(), angle.cos(), 0.0));
+            assert!(tilted < straight_up);
+            assert!((tilted - angle.cos() / common::PI).abs() < 1e-12);
+        }
+        assert_eq!(pdf(Vec3::new(1.0, 0.0, 0.0)), 0.0);
+        assert_eq!(pdf(Vec3::new(0.3, -1.0, 0.0)), 0.0);
+
+        // Materials that don't scatter diffusely keep the default of 0
+        let metal = Metal::new(Color::new(0.8, 0.8, 0.8), 0.0);
+        let up = Ray::new(rec.p, Vec3::new(0.0, 1.0, 0.0), 0.0);
+        assert_eq!(metal.scattering_pdf(&r, &rec, &up), 0.0);
+    }
+
+    #[test]
+    fn shinier_phong_highlights_are_tighter() {
+        common::seed_rng(15);
+        let (r, rec) = floor_hit(Point3::new(0.0, 0.0, 0.0));
+        let highlight = |shininess: f64| {
+            let phong = Phong::new(Color::default(), Color::new(1.0, 1.0, 1.0), shininess);
+            spread(&phong, &r, &rec)
+        };
+        let (dull, shiny, mirror_like) = (highlight(10.0), highlight(100.0), highlight(1000.0));
+        assert!(dull > 2.0 * shiny, "{} vs {}", dull, shiny);
+        assert!(shiny > 2.0 * mirror_like, "{} vs {}", shiny, mirror_like);
+        assert!(mirror_like < 0.05);
+    }
+}
@@ -0,0 +1,186 @@
+use std::fs;
+use std::io;
+use std::sync::Arc;
+
+use crate::hittable_list::HittableList;
+use crate::material::Material;
+use crate::shapes::Triangle;
+use crate::vec3::Point3;
+
+// Load the geometry of a Wavefront OBJ file as a list of triangles sharing one material.
+// Only `v` and `f` lines are used; normals, texture coordinates, groups etc. are skipped.
+// Faces with more than three vertices are split into a fan of triangles.
+pub fn load_obj(path: &str, material: Arc<dyn Material>) -> io::Result<HittableList> {
+    let contents = fs::read_to_string(path)?;
+    parse_obj(&contents, material)
+}
+
+fn parse_obj(contents: &str, material: Arc<dyn Material>) -> io::Result<HittableList> {
+    let mut vertices: Vec<Point3> = Vec::new();
+    let mut world = HittableList::new();
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("v") => {
+                let coords = tokens
+                    .take(3)
+                    .map(|t| t.parse::<f64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| invalid_data(line_number, &e.to_string()))?;
+                if coords.len() != 3 {
+                    return Err(invalid_data(line_number, "vertex needs three coordinates"));
+                }
+                vertices.push(Point3::new(coords[0], coords[1], coords[2]));
+            }
+            Some("f") => {
+                let face = tokens
+                    .map(|t| vertex_index(t, vertices.len(), line_number))
+                    .collect::<io::Result<Vec<_>>>()?;
+                if face.len() < 3 {
+                    return Err(invalid_data(
+                        line_number,
+                        "face needs at least three vertices",
+                    ));
+                }
+                for i in 1..face.len() - 1 {
+                    world.add(Box::new(Triangle::new(
+                        vertices[face[0]],
+                        vertices[face[i]],
+                        vertices[face[i + 1]],
+                        material.clone(),
+                    )));
+                }
+            }
+            // comments, empty lines and everything we don't render
+            _ => {}
+        }
+    }
+
+    Ok(world)
+}
+
+// A ground of triangles over the rectangle from `min` to `max` (x and z), split into
+// `subdivisions` x `subdivisions` cells. Each vertex is raised to `height_fn(x, z)`, so e.g.
+// Perlin noise gives rolling hills; the triangles face up (+y).
+pub fn height_grid(
+    min: (f64, f64),
+    max: (f64, f64),
+    subdivisions: usize,
+    height_fn: impl Fn(f64, f64) -> f64,
+    material: Arc<dyn Material>,
+) -> HittableList {
+    let n = subdivisions.max(1);
+    let vertex = |i: usize, k: usize| {
+        let x = min.0 + (max.0 - min.0) * i as f64 / n as f64;
+        let z = min.1 + (max.1 - min.1) * k as f64 / n as f64;
+        Point3::new(x, height_fn(x, z), z)
+    };
+    let vertices: Vec<Vec<Point3>> = (0..=n)
+        .map(|k| (0..=n).map(|i| vertex(i, k)).collect())
+        .collect();
+
+    let mut world = HittableList::new();
+    for k in 0..n {
+        for i in 0..n {
+            let (p00, p10) = (vertices[k][i], vertices[k][i + 1]);
+            let (p01, p11) = (vertices[k + 1][i], vertices[k + 1][i + 1]);
+            world.add(Box::new(Triangle::new(p00, p01, p10, material.clone())));
+            world.add(Box::new(Triangle::new(p10, p01, p11, material.clone())));
+        }
+    }
+    world
+}
+
+// Resolve a face token like "3", "3/1" or "3/1/2" (1-based, negative counts from the end)
+// into an index into the vertices read so far
+fn vertex_index(token: &str, vertex_count: usize, line_number: usize) -> io::Result<usize> {
+    let index_str = token.split('/').next().unwrap_or("");
+    let index: i64 = index_str
+        .parse()
+        .map_err(|_| invalid_data(line_number, &format!("invalid vertex index '{}'", token)))?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        vertex_count as i64 + index
+    };
+
+    if index == 0 || resolved < 0 || resolved >= vertex_count as i64 {
+        return Err(invalid_data(
+            line_number,
+            &format!("vertex index {} out of range", index),
+        ));
+    }
+
+    Ok(resolved as usize)
+}
+
+fn invalid_data(line_number: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line_number + 1, message),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::hittable::{HitRecord, Hittable};
+    use crate::material::Lambertian;
+    use crate::ray::Ray;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn tetrahedron_loads_as_four_triangles() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/tetrahedron.obj"
+        );
+        let mesh = load_obj(path, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))).unwrap();
+        assert_eq!(mesh.len(), 4);
+    }
+
+    fn gray() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn flat_height_grid_is_a_level_plane() {
+        let grid = height_grid((-2.0, -2.0), (2.0, 2.0), 4, |_, _| 0.5, gray());
+        assert_eq!(grid.len(), 4 * 4 * 2);
+        let bbox = grid.bounding_box().unwrap();
+        assert!(bbox.max().y() - bbox.min().y() < 1e-3);
+
+        // Dropped anywhere on it, a ray lands at the same height, on the up facing side
+        for (x, z) in [(-1.9, -1.9), (-0.3, 1.2), (0.7, 0.7), (1.5, -0.6)] {
+            let r = Ray::new(Point3::new(x, 5.0, z), Vec3::new(0.0, -1.0, 0.0), 0.0);
+            let mut rec = HitRecord::new();
+            assert!(grid.hit(&r, 0.001, f64::INFINITY, &mut rec));
+            assert!((rec.p.y() - 0.5).abs() < 1e-9);
+            assert!(rec.is_front_face());
+            assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn varying_height_raises_the_vertices() {
+        // From 0 at the (0, 0) corner to 4 at the (2, 2) one
+        let grid = height_grid((0.0, 0.0), (2.0, 2.0), 4, |x, z| x * z, gray());
+        let bbox = grid.bounding_box().unwrap();
+        assert!(bbox.min().y().abs() < 1e-3);
+        assert!((bbox.max().y() - 4.0).abs() < 1e-3);
+
+        // Just past a vertex c on the diagonal the surface rises from c * c towards its
+        // neighbors at c * (c + 0.5), which puts it at c * c + 0.2 * c
+        for &(x, height) in &[(0.6, 0.35), (1.1, 1.2), (1.6, 2.55)] {
+            let r = Ray::new(Point3::new(x, 10.0, x), Vec3::new(0.0, -1.0, 0.0), 0.0);
+            let mut rec = HitRecord::new();
+            assert!(grid.hit(&r, 0.001, f64::INFINITY, &mut rec));
+            assert!((rec.p.y() - height).abs() < 1e-9, "{} at {}", rec.p.y(), x);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use crate::vec3::{self, Vec3};
+
+// Orthonormal basis built around a direction, used to turn directions sampled
+// around +Z into directions around that vector
+pub struct OrthoNormalBasis {
+    axis: [Vec3; 3],
+}
+
+impl OrthoNormalBasis {
+    // Basis whose w axis points along n
+    pub fn build_from_w(n: Vec3) -> OrthoNormalBasis {
+        let w = vec3::unit_vector(n);
+        // Any vector that isn't (nearly) parallel to w will do
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = vec3::unit_vector(vec3::cross(w, a));
+        let u = vec3::cross(w, v);
+
+        OrthoNormalBasis { axis: [u, v, w] }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.axis[0]
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.axis[1]
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.axis[2]
+    }
+
+    // Convert coordinates in this basis to world coordinates
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x() * self.u() + a.y() * self.v() + a.z() * self.w()
+    }
+}
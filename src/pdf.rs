@@ -0,0 +1,206 @@
+use crate::background::Background;
+use crate::common;
+use crate::hittable::Hittable;
+use crate::onb::OrthoNormalBasis;
+use crate::vec3::{self, Point3, Vec3};
+
+// Probability densities over directions: ways of picking where to send a ray, together with
+// how likely each direction is to be picked, which the light found that way is divided by
+
+pub trait Pdf {
+    // Probability density (per solid angle) of `generate` picking `direction`
+    fn value(&self, direction: Vec3) -> f64;
+
+    // Random direction, picked with the density `value` gives
+    fn generate(&self) -> Vec3;
+}
+
+// Cosine weighted around a normal, the way an ideal diffuse surface scatters
+pub struct CosinePdf {
+    uvw: OrthoNormalBasis,
+}
+
+impl CosinePdf {
+    pub fn new(normal: Vec3) -> CosinePdf {
+        CosinePdf {
+            uvw: OrthoNormalBasis::build_from_w(normal),
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: Vec3) -> f64 {
+        let cosine = vec3::dot(vec3::unit_vector(direction), self.uvw.w());
+        if cosine < 0.0 {
+            0.0
+        } else {
+            cosine / common::PI
+        }
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.uvw.local(vec3::random_cosine_direction())
+    }
+}
+
+// Towards a point on an object (usually the list of lights) seen from `origin`
+pub struct HittablePdf<'a> {
+    object: &'a dyn Hittable,
+    origin: Point3,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(object: &'a dyn Hittable, origin: Point3) -> HittablePdf<'a> {
+        HittablePdf { object, origin }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        self.object.pdf_value(self.origin, direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.object.random(self.origin)
+    }
+}
+
+// Towards the bright parts of a background that can be sampled (see
+// Background::can_sample_light)
+pub struct BackgroundPdf<'a> {
+    background: &'a dyn Background,
+}
+
+impl<'a> BackgroundPdf<'a> {
+    pub fn new(background: &'a dyn Background) -> BackgroundPdf<'a> {
+        BackgroundPdf { background }
+    }
+}
+
+impl Pdf for BackgroundPdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        self.background.pdf_value(direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        // A background that can't be sampled has a density of 0 everywhere, so any
+        // direction will do
+        self.background
+            .sample_light()
+            .map_or(Vec3::new(1.0, 0.0, 0.0), |(direction, _, _)| direction)
+    }
+}
+
+// Either of two densities, each picked half the time, so the directions either of them is
+// good at are covered. The density of a direction is the average of the two.
+pub struct MixturePdf<'a> {
+    pdfs: [&'a dyn Pdf; 2],
+}
+
+impl<'a> MixturePdf<'a> {
+    pub fn new(first: &'a dyn Pdf, second: &'a dyn Pdf) -> MixturePdf<'a> {
+        MixturePdf {
+            pdfs: [first, second],
+        }
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        0.5 * (self.pdfs[0].value(direction) + self.pdfs[1].value(direction))
+    }
+
+    fn generate(&self) -> Vec3 {
+        if common::random_double() < 0.5 {
+            self.pdfs[0].generate()
+        } else {
+            self.pdfs[1].generate()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::color::Color;
+    use crate::material::DiffuseLight;
+    use crate::shapes::Sphere;
+
+    const SAMPLES: usize = 200_000;
+
+    // Integral of the density over all directions, from uniformly spread directions
+    // (density 1 / (4 PI) each)
+    fn integral(pdf: &dyn Pdf) -> f64 {
+        let sum: f64 = (0..SAMPLES)
+            .map(|_| pdf.value(vec3::random_unit_vector()))
+            .sum();
+        sum * 4.0 * common::PI / SAMPLES as f64
+    }
+
+    // Solid angle the density covers, from its own directions: the average of 1 / density
+    fn covered_solid_angle(pdf: &dyn Pdf) -> f64 {
+        let sum: f64 = (0..SAMPLES).map(|_| 1.0 / pdf.value(pdf.generate())).sum();
+        sum / SAMPLES as f64
+    }
+
+    // A light of radius 1, 2 away from the origin: it covers the directions within 30
+    // degrees of +Y
+    fn sphere_light() -> Sphere {
+        Sphere::new(
+            Point3::new(0.0, 2.0, 0.0),
+            1.0,
+            Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+        )
+    }
+
+    #[test]
+    fn each_density_integrates_to_one() {
+        common::seed_rng(17);
+        let cosine = CosinePdf::new(Vec3::new(1.0, 1.0, 0.0));
+        let light = sphere_light();
+        let towards_light = HittablePdf::new(&light, Point3::new(0.0, 0.0, 0.0));
+        let mixture = MixturePdf::new(&cosine, &towards_light);
+
+        for (name, pdf) in [
+            ("cosine", &cosine as &dyn Pdf),
+            ("light", &towards_light),
+            ("mixture", &mixture),
+        ] {
+            let total = integral(pdf);
+            assert!(
+                (total - 1.0).abs() < 0.02,
+                "{} integrates to {}",
+                name,
+                total
+            );
+        }
+    }
+
+    #[test]
+    fn generated_directions_follow_the_density() {
+        common::seed_rng(17);
+        // The hemisphere, and the cap of the sphere within 30 degrees
+        let cosine = covered_solid_angle(&CosinePdf::new(Vec3::new(0.0, 0.0, 1.0)));
+        assert!((cosine - 2.0 * common::PI).abs() < 0.05, "{}", cosine);
+        let light = sphere_light();
+        let cap = 2.0 * common::PI * (1.0 - f64::sqrt(3.0) / 2.0);
+        let seen = covered_solid_angle(&HittablePdf::new(&light, Point3::new(0.0, 0.0, 0.0)));
+        assert!((seen - cap).abs() < 1e-6, "{} vs {}", seen, cap);
+    }
+
+    #[test]
+    fn mixture_density_is_the_average_of_its_parts() {
+        common::seed_rng(17);
+        let cosine = CosinePdf::new(Vec3::new(0.0, 1.0, 0.0));
+        let light = sphere_light();
+        let towards_light = HittablePdf::new(&light, Point3::new(0.0, 0.0, 0.0));
+        let mixture = MixturePdf::new(&cosine, &towards_light);
+        for _ in 0..1000 {
+            let direction = mixture.generate();
+            let average = 0.5 * (cosine.value(direction) + towards_light.value(direction));
+            assert!((mixture.value(direction) - average).abs() <= 1e-12 * average);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::vec3::{self, Point3, Vec3};
+
+const POINT_COUNT: usize = 256;
+
+// Seed used by Perlin::new so renders using noise textures are reproducible
+const DEFAULT_SEED: u64 = 0x5eed;
+
+pub struct Perlin {
+    ranvec: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new() -> Perlin {
+        Perlin::with_seed(DEFAULT_SEED)
+    }
+
+    pub fn with_seed(seed: u64) -> Perlin {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let ranvec = (0..POINT_COUNT)
+            .map(|_| {
+                vec3::unit_vector(Vec3::new(
+                    rng.random_range(-1.0..1.0),
+                    rng.random_range(-1.0..1.0),
+                    rng.random_range(-1.0..1.0),
+                ))
+            })
+            .collect();
+
+        let perm_x = Self::generate_perm(&mut rng);
+        let perm_y = Self::generate_perm(&mut rng);
+        let perm_z = Self::generate_perm(&mut rng);
+
+        Perlin {
+            ranvec,
+            perm_x,
+            perm_y,
+            perm_z,
+        }
+    }
+
+    // Gradient noise in [-1, 1]
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let u = p.x() - p.x().floor();
+        let v = p.y() - p.y().floor();
+        let w = p.z() - p.z().floor();
+
+        let i = p.x().floor() as i64;
+        let j = p.y().floor() as i64;
+        let k = p.z().floor() as i64;
+
+        let mut c = [[[Vec3::default(); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    *corner = self.ranvec[self.perm_x[((i + di as i64) & 255) as usize]
+                        ^ self.perm_y[((j + dj as i64) & 255) as usize]
+                        ^ self.perm_z[((k + dk as i64) & 255) as usize]];
+                }
+            }
+        }
+
+        Self::perlin_interp(&c, u, v, w)
+    }
+
+    // Sum of several octaves of noise, each at double the frequency and half the weight
+    pub fn turb(&self, p: &Point3, depth: i32) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(&temp_p);
+            weight *= 0.5;
+            temp_p *= 2.0;
+        }
+
+        accum.abs()
+    }
+
+    fn generate_perm(rng: &mut StdRng) -> Vec<usize> {
+        let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+        for i in (1..POINT_COUNT).rev() {
+            let target = rng.random_range(0..=i);
+            p.swap(i, target);
+        }
+        p
+    }
+
+    fn perlin_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+        // Hermite smoothing to hide the grid artifacts of linear interpolation
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+        let mut accum = 0.0;
+
+        for (i, plane) in c.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, corner) in row.iter().enumerate() {
+                    let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                    let weight_v = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * vec3::dot(*corner, weight_v);
+                }
+            }
+        }
+
+        accum
+    }
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Perlin::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 20 x 20 x 20 grid of points a little under a cell apart, so they land all over
+    // the cells
+    fn grid() -> impl Iterator<Item = Point3> {
+        (0..8000).map(|n| {
+            let at = |i: i32| (i % 20) as f64 * 0.37 - 3.0;
+            Point3::new(at(n), at(n / 20), at(n / 400))
+        })
+    }
+
+    #[test]
+    fn noise_stays_within_minus_one_to_one() {
+        let perlin = Perlin::new();
+        for p in grid() {
+            let n = perlin.noise(&p);
+            assert!((-1.0..=1.0).contains(&n), "noise at {} is {}", p, n);
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_noise() {
+        let (a, b) = (Perlin::with_seed(7), Perlin::with_seed(7));
+        assert!(grid().all(|p| a.noise(&p) == b.noise(&p)));
+
+        let c = Perlin::with_seed(8);
+        assert!(grid().any(|p| a.noise(&p) != c.noise(&p)));
+    }
+}
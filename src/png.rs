@@ -0,0 +1,475 @@
+use crate::color::Color;
+use crate::framebuffer::{adler32, crc32};
+
+pub(crate) const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+// Decode a PNG into (width, height, pixels) with colors scaled to [0, 1]. Handles 8 bit
+// gray, RGB, palette, gray + alpha and RGBA images without interlacing, which covers what
+// image editors and Framebuffer::to_png write; alpha is dropped.
+pub(crate) fn decode(data: &[u8]) -> Result<(usize, usize, Vec<Color>), String> {
+    if !data.starts_with(SIGNATURE) {
+        return Err("not a PNG file".to_string());
+    }
+
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut compressed = Vec::new();
+    let mut pos = SIGNATURE.len();
+    loop {
+        let length = data
+            .get(pos..pos + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| "truncated chunk".to_string())?;
+        let kind_start = pos + 4;
+        let end = kind_start
+            .checked_add(4)
+            .and_then(|start| start.checked_add(length))
+            .filter(|&end| end + 4 <= data.len())
+            .ok_or_else(|| "truncated chunk".to_string())?;
+        let kind = &data[kind_start..kind_start + 4];
+        let body = &data[kind_start + 4..end];
+        let crc = u32::from_be_bytes([data[end], data[end + 1], data[end + 2], data[end + 3]]);
+        if crc32(data[kind_start..end].iter()) != crc {
+            return Err(format!(
+                "bad checksum in {} chunk",
+                String::from_utf8_lossy(kind)
+            ));
+        }
+        pos = end + 4;
+
+        match kind {
+            b"IHDR" if body.len() == 13 => header = Some(Header::parse(body)?),
+            b"PLTE" => palette = body,
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+
+    let header = header.ok_or_else(|| "missing IHDR chunk".to_string())?;
+    let (width, height, channels) = (header.width, header.height, header.channels);
+
+    // A made up header can ask for far more pixels than the data could hold, so the
+    // decompressed size is bounded by it instead of trusting the stream
+    let stride = width
+        .checked_mul(channels)
+        .ok_or_else(|| format!("image size {}x{} is too large", width, height))?;
+    let raw_size = stride
+        .checked_add(1)
+        .and_then(|row| row.checked_mul(height))
+        .ok_or_else(|| format!("image size {}x{} is too large", width, height))?;
+    let raw = zlib_inflate(&compressed, raw_size)?;
+    if raw.len() != raw_size {
+        return Err("truncated pixel data".to_string());
+    }
+
+    let samples = unfilter(&raw, stride, channels)?;
+    let scale = 1.0 / 255.0;
+    let sample = |b: u8| b as f64 * scale;
+    let pixels = samples
+        .chunks(channels)
+        .map(|p| match header.color_type {
+            0 | 4 => Ok(Color::new(sample(p[0]), sample(p[0]), sample(p[0]))),
+            2 | 6 => Ok(Color::new(sample(p[0]), sample(p[1]), sample(p[2]))),
+            _ => {
+                let i = 3 * p[0] as usize;
+                palette
+                    .get(i..i + 3)
+                    .map(|c| Color::new(sample(c[0]), sample(c[1]), sample(c[2])))
+                    .ok_or_else(|| format!("palette index {} out of range", p[0]))
+            }
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok((width, height, pixels))
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    color_type: u8,
+    channels: usize,
+}
+
+impl Header {
+    fn parse(body: &[u8]) -> Result<Header, String> {
+        let width = u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize;
+        let height = u32::from_be_bytes([body[4], body[5], body[6], body[7]]) as usize;
+        let (bit_depth, color_type, interlace) = (body[8], body[9], body[12]);
+        if width == 0 || height == 0 {
+            return Err("empty image".to_string());
+        }
+        if bit_depth != 8 {
+            return Err(format!("unsupported bit depth {}", bit_depth));
+        }
+        if interlace != 0 {
+            return Err("interlaced images are not supported".to_string());
+        }
+        let channels = match color_type {
+            0 | 3 => 1,
+            4 => 2,
+            2 => 3,
+            6 => 4,
+            _ => return Err(format!("unsupported color type {}", color_type)),
+        };
+        Ok(Header {
+            width,
+            height,
+            color_type,
+            channels,
+        })
+    }
+}
+
+// Undo the per row filters; every row of `raw` starts with its filter type
+fn unfilter(raw: &[u8], stride: usize, channels: usize) -> Result<Vec<u8>, String> {
+    let mut samples: Vec<u8> = Vec::with_capacity(raw.len() - raw.len() / (stride + 1));
+    for row in raw.chunks(stride + 1) {
+        let start = samples.len();
+        let (filter, line) = (row[0], &row[1..]);
+        for (i, &x) in line.iter().enumerate() {
+            // Left, up and up-left neighbours, 0 outside the image
+            let a = if i >= channels {
+                samples[start + i - channels]
+            } else {
+                0
+            };
+            let b = if start > 0 {
+                samples[start + i - stride]
+            } else {
+                0
+            };
+            let c = if start > 0 && i >= channels {
+                samples[start + i - stride - channels]
+            } else {
+                0
+            };
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(format!("unknown filter type {}", filter)),
+            };
+            samples.push(x.wrapping_add(predicted));
+        }
+    }
+    Ok(samples)
+}
+
+// Whichever of left, up and up-left is closest to left + up - up-left
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// Decompress a zlib stream, failing if it would produce more than `limit` bytes
+fn zlib_inflate(stream: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    if stream.len() < 6 {
+        return Err("truncated zlib stream".to_string());
+    }
+    let (cmf, flags) = (stream[0], stream[1]);
+    if cmf & 0x0f != 8 || !(cmf as u16 * 256 + flags as u16).is_multiple_of(31) || flags & 0x20 != 0
+    {
+        return Err("invalid zlib header".to_string());
+    }
+    let out = inflate(&stream[2..], limit)?;
+    let tail = &stream[stream.len() - 4..];
+    if adler32(&out) != u32::from_be_bytes([tail[0], tail[1], tail[2], tail[3]]) {
+        return Err("bad zlib checksum".to_string());
+    }
+    Ok(out)
+}
+
+// Deflate (RFC 1951): stored, fixed Huffman and dynamic Huffman blocks
+fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    const LENGTH_BASE: [u16; 29] = [
+        3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+        131, 163, 195, 227, 258,
+    ];
+    const LENGTH_EXTRA: [u8; 29] = [
+        0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+    ];
+    const DISTANCE_BASE: [u16; 30] = [
+        1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+        2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+    ];
+    const DISTANCE_EXTRA: [u8; 30] = [
+        0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12,
+        13, 13,
+    ];
+
+    let mut bits = BitReader { data, pos: 0 };
+    let mut out = Vec::new();
+    let too_large = || "pixel data is larger than the image".to_string();
+    loop {
+        let last = bits.read(1)? == 1;
+        match bits.read(2)? {
+            0 => {
+                let start = bits.align();
+                let header = data
+                    .get(start..start + 4)
+                    .ok_or_else(|| "truncated stored block".to_string())?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                if len != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err("corrupt stored block length".to_string());
+                }
+                let block = data
+                    .get(start + 4..start + 4 + len as usize)
+                    .ok_or_else(|| "truncated stored block".to_string())?;
+                if out.len() + block.len() > limit {
+                    return Err(too_large());
+                }
+                out.extend_from_slice(block);
+                bits.pos = 8 * (start + 4 + len as usize);
+            }
+            kind @ (1 | 2) => {
+                let (literals, distances) = if kind == 1 {
+                    fixed_codes()
+                } else {
+                    dynamic_codes(&mut bits)?
+                };
+                loop {
+                    let symbol = literals.decode(&mut bits)? as usize;
+                    if symbol < 256 {
+                        if out.len() == limit {
+                            return Err(too_large());
+                        }
+                        out.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == 256 {
+                        break;
+                    }
+                    let index = symbol - 257;
+                    if index >= LENGTH_BASE.len() {
+                        return Err("invalid length code".to_string());
+                    }
+                    let length = LENGTH_BASE[index] as usize
+                        + bits.read(LENGTH_EXTRA[index] as u32)? as usize;
+                    let index = distances.decode(&mut bits)? as usize;
+                    if index >= DISTANCE_BASE.len() {
+                        return Err("invalid distance code".to_string());
+                    }
+                    let distance = DISTANCE_BASE[index] as usize
+                        + bits.read(DISTANCE_EXTRA[index] as u32)? as usize;
+                    if distance > out.len() {
+                        return Err("distance reaches before the start".to_string());
+                    }
+                    if out.len() + length > limit {
+                        return Err(too_large());
+                    }
+                    // Byte by byte: the copy may overlap the bytes it produces
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+            }
+            _ => return Err("invalid block type".to_string()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(bits: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    // The code length code lengths come in this order
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+
+    let literal_count = bits.read(5)? as usize + 257;
+    let distance_count = bits.read(5)? as usize + 1;
+    let length_count = bits.read(4)? as usize + 4;
+    let mut length_lengths = [0u8; 19];
+    for &i in &ORDER[..length_count] {
+        length_lengths[i] = bits.read(3)? as u8;
+    }
+    let length_code = Huffman::new(&length_lengths);
+
+    // Literal and distance code lengths form one run-length coded sequence
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let (value, repeat) = match length_code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *i
+                    .checked_sub(1)
+                    .map(|p| &lengths[p])
+                    .ok_or_else(|| "repeat with no previous length".to_string())?;
+                (previous, 3 + bits.read(2)? as usize)
+            }
+            17 => (0, 3 + bits.read(3)? as usize),
+            _ => (0, 11 + bits.read(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err("too many code lengths".to_string());
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+// Reads the stream least significant bit first, as deflate packs it
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self
+                .data
+                .get(self.pos / 8)
+                .ok_or_else(|| "truncated deflate stream".to_string())?;
+            value |= ((byte >> (self.pos % 8)) as u32 & 1) << i;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    // Skip to the next byte boundary and return its index
+    fn align(&mut self) -> usize {
+        self.pos = self.pos.div_ceil(8) * 8;
+        self.pos / 8
+    }
+}
+
+// Canonical Huffman code given by the code length of every symbol
+struct Huffman {
+    // Number of codes of each length
+    counts: [u16; 16],
+    // Symbols ordered by code length, then by value
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols = Vec::with_capacity(lengths.len());
+        for length in 1..16 {
+            for (symbol, &l) in lengths.iter().enumerate() {
+                if l as usize == length {
+                    symbols.push(symbol as u16);
+                }
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    // Codes are packed most significant bit first, so the code is built one bit at a
+    // time and compared against the range of codes of each length
+    fn decode(&self, bits: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0usize, 0usize, 0usize);
+        for &count in &self.counts[1..] {
+            code |= bits.read(1)? as usize;
+            let count = count as usize;
+            if code < first + count {
+                return Ok(self.symbols[index + code - first]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+
+    #[test]
+    fn inflates_a_dynamic_huffman_stream() {
+        // zlib.compress(bytes((i * i % 251) % 16 + 97 for i in range(1000)), 9) from
+        // Python: one dynamic Huffman block with back references
+        let stream = include_bytes!("../tests/fixtures/dynamic.zlib");
+        let expected: Vec<u8> = (0..1000u32)
+            .map(|i| (i * i % 251 % 16 + 97) as u8)
+            .collect();
+        assert_eq!(zlib_inflate(stream, 1000).unwrap(), expected);
+        // The limit stops a stream from growing past the image
+        assert!(zlib_inflate(stream, 100).is_err());
+    }
+
+    #[test]
+    fn paeth_picks_the_nearest_neighbour() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 20), 10);
+    }
+
+    #[test]
+    fn corrupt_files_are_errors() {
+        let png = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/2x2.png"
+        ))
+        .unwrap();
+        assert!(decode(&png).is_ok());
+        for cut in [4, 20, png.len() - 1] {
+            assert!(decode(&png[..cut]).is_err());
+        }
+        let mut flipped = png.clone();
+        flipped[40] ^= 1;
+        assert!(decode(&flipped).is_err());
+    }
+
+    #[test]
+    fn reads_back_the_renderers_own_png() {
+        use crate::framebuffer::Framebuffer;
+        use crate::tonemap::ToneMap;
+
+        // Large enough for more than one stored deflate block
+        let mut image = Framebuffer::new(300, 80);
+        for y in 0..80 {
+            for x in 0..300 {
+                image.set(x, y, Color::new(x as f64 / 300.0, y as f64 / 80.0, 0.5));
+            }
+        }
+        let mut data = Vec::new();
+        image.to_png(&mut data, ToneMap::None).unwrap();
+
+        let (width, height, pixels) = decode(&data).unwrap();
+        assert_eq!((width, height), (300, 80));
+        for (i, pixel) in pixels.iter().enumerate() {
+            let expected = color::to_bytes(image.get(i % 300, i / 300), ToneMap::None);
+            let bytes = [pixel.x(), pixel.y(), pixel.z()].map(|c| (c * 255.0).round() as u8);
+            assert_eq!(bytes, expected);
+        }
+    }
+}
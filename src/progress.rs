@@ -0,0 +1,110 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// Minimum time between two progress lines on stderr
+const UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+// Render progress on stderr with elapsed time and an estimate of the time left.
+// The counters are atomic so several render threads can share one Progress.
+pub struct Progress {
+    total: u64,
+    done: AtomicU64,
+    started: Instant,
+    last_update_ms: AtomicU64,
+}
+
+impl Progress {
+    pub fn start(total: u64) -> Progress {
+        Progress {
+            total,
+            done: AtomicU64::new(0),
+            started: Instant::now(),
+            last_update_ms: AtomicU64::new(0),
+        }
+    }
+
+    // Record n more finished units of work (scanlines, tiles, ...)
+    pub fn inc(&self, n: u64) {
+        let done = self.done.fetch_add(n, Ordering::Relaxed) + n;
+
+        // Only the thread that wins the race for this interval prints
+        let now_ms = self.started.elapsed().as_millis() as u64;
+        let last_ms = self.last_update_ms.load(Ordering::Relaxed);
+        if now_ms < last_ms + UPDATE_INTERVAL.as_millis() as u64 {
+            return;
+        }
+        if self
+            .last_update_ms
+            .compare_exchange(last_ms, now_ms, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            self.print(done);
+        }
+    }
+
+    pub fn finish(&self) {
+        self.print(self.done.load(Ordering::Relaxed));
+        eprintln!("\nDone.");
+    }
+
+    fn print(&self, done: u64) {
+        let elapsed = self.started.elapsed();
+        let fraction = if self.total == 0 {
+            1.0
+        } else {
+            done as f64 / self.total as f64
+        };
+        let eta = match estimate_remaining(elapsed, fraction) {
+            Some(remaining) => format_duration(remaining),
+            None => "--:--".to_string(),
+        };
+
+        eprint!(
+            "\r[{:5.1}%] elapsed {}, remaining {}   ",
+            100.0 * fraction,
+            format_duration(elapsed),
+            eta
+        );
+    }
+}
+
+// Time left assuming the rest of the work goes at the same pace as what's done so far
+pub fn estimate_remaining(elapsed: Duration, fraction_done: f64) -> Option<Duration> {
+    if fraction_done <= 0.0 {
+        return None;
+    }
+    let fraction_done = fraction_done.min(1.0);
+    Some(elapsed.mul_f64((1.0 - fraction_done) / fraction_done))
+}
+
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remaining_time_follows_the_pace_so_far() {
+        // A quarter done in 30 seconds leaves three times as long
+        let remaining = estimate_remaining(Duration::from_secs(30), 0.25).unwrap();
+        assert_eq!(remaining, Duration::from_secs(90));
+        assert_eq!(
+            estimate_remaining(Duration::from_secs(30), 1.0),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(estimate_remaining(Duration::from_secs(30), 0.0), None);
+    }
+
+    #[test]
+    fn durations_show_hours_only_when_needed() {
+        assert_eq!(format_duration(Duration::from_secs(75)), "01:15");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1:02:05");
+    }
+}
@@ -1,19 +1,47 @@
 use crate::vec3::{Point3, Vec3};
 
-#[derive(Default)]
+// What a ray is for, so objects can choose to be seen by some rays only (see Visibility)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RayKind {
+    // Sent from the camera into the scene
+    Camera,
+    // Scattered off a surface, e.g. seeing an object in a mirror
+    Bounce,
+    // Checking whether anything is in the way, towards a light or for ambient occlusion
+    Shadow,
+}
+
+#[derive(Clone, Copy)]
 pub struct Ray {
     orig: Point3,
     dir: Vec3,
+    tm: f64, // Moment the ray exists at, used for motion blur
+    // 1 / direction per component, so box tests multiply instead of divide. A component
+    // of 0 gives an infinity, which the slab test handles.
+    inv_dir: Vec3,
+    kind: RayKind,
 }
 
 impl Ray {
-    pub fn new(origin: Point3, direction: Vec3) -> Self {
+    pub fn new(origin: Point3, direction: Vec3, time: f64) -> Self {
         Self {
             orig: origin,
             dir: direction,
+            tm: time,
+            inv_dir: Vec3::new(
+                1.0 / direction.x(),
+                1.0 / direction.y(),
+                1.0 / direction.z(),
+            ),
+            kind: RayKind::Bounce,
         }
     }
 
+    // The same ray marked as being of another kind, new rays are bounces
+    pub fn with_kind(self, kind: RayKind) -> Ray {
+        Ray { kind, ..self }
+    }
+
     pub fn origin(&self) -> Point3 {
         self.orig
     }
@@ -22,7 +50,25 @@ impl Ray {
         self.dir
     }
 
+    pub fn inv_direction(&self) -> Vec3 {
+        self.inv_dir
+    }
+
+    pub fn time(&self) -> f64 {
+        self.tm
+    }
+
+    pub fn kind(&self) -> RayKind {
+        self.kind
+    }
+
     pub fn at(&self, t: f64) -> Vec3 {
         self.orig + t * self.dir
     }
 }
+
+impl Default for Ray {
+    fn default() -> Self {
+        Ray::new(Point3::default(), Vec3::default(), 0.0)
+    }
+}
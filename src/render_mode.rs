@@ -0,0 +1,189 @@
+use crate::color::Color;
+use crate::hittable::{HitRecord, Hittable};
+use crate::onb::OrthoNormalBasis;
+use crate::ray::{Ray, RayKind};
+use crate::scene::Scene;
+use crate::vec3;
+
+// Distance at which the depth pass has dropped to half brightness
+const DEPTH_FALLOFF: f64 = 10.0;
+
+// Rays the occlusion pass casts from every hit
+const OCCLUSION_RAYS: usize = 16;
+
+// What the renderer puts in each pixel. Everything but Beauty is an auxiliary pass that only
+// looks at the first surface a camera ray hits, which helps telling geometry problems
+// (wrong normals, gaps) apart from shading problems.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RenderMode {
+    // The full path traced image
+    Beauty,
+    // White close to the camera fading to black with distance, black where nothing is hit
+    Depth,
+    // Outward surface normal mapped from [-1, 1] to [0, 1] per channel
+    Normal,
+    // The color the surface's material tints light with (its light color for lights)
+    Albedo,
+    // Ambient occlusion: how open the surface is to its surroundings, white in the open
+    // fading to black in creases and corners. Unlike the other passes it looks around the
+    // hit, see occlusion().
+    Occlusion,
+}
+
+impl RenderMode {
+    pub fn from_name(name: &str) -> Option<RenderMode> {
+        match name {
+            "beauty" => Some(RenderMode::Beauty),
+            "depth" => Some(RenderMode::Depth),
+            "normal" => Some(RenderMode::Normal),
+            "albedo" => Some(RenderMode::Albedo),
+            "occlusion" => Some(RenderMode::Occlusion),
+            _ => None,
+        }
+    }
+
+    // Color of the auxiliary pass for a camera ray that hit `rec`. The value is squared so
+    // that the gamma correction when writing the image gives back exactly the value itself
+    // (a normal of (0, 0, 1) is written as (128, 128, 255)).
+    pub fn first_hit_color(self, r: &Ray, rec: &HitRecord) -> Color {
+        let value = self.first_hit_value(r, rec);
+        value * value
+    }
+
+    // The auxiliary pass's value itself, e.g. the mapped normal
+    pub fn first_hit_value(self, r: &Ray, rec: &HitRecord) -> Color {
+        match self {
+            RenderMode::Beauty => panic!("the beauty pass is path traced, not a first hit pass"),
+            RenderMode::Occlusion => panic!("the occlusion pass needs the scene, see occlusion()"),
+            RenderMode::Depth => {
+                let distance = rec.t * r.direction().length();
+                let brightness = 1.0 / (1.0 + distance / DEPTH_FALLOFF);
+                Color::new(brightness, brightness, brightness)
+            }
+            RenderMode::Normal => {
+                let outward_normal = if rec.is_front_face() {
+                    rec.normal
+                } else {
+                    -rec.normal
+                };
+                0.5 * (outward_normal + Color::new(1.0, 1.0, 1.0))
+            }
+            RenderMode::Albedo => {
+                let mat = rec.mat.as_ref().unwrap();
+                let mut attenuation = Color::default();
+                let mut scattered = Ray::default();
+                if mat.scatter(r, rec, &mut attenuation, &mut scattered) {
+                    attenuation
+                } else {
+                    mat.emitted(rec.u, rec.v, &rec.p, r.direction())
+                }
+            }
+        }
+    }
+}
+
+// Share of the hemisphere above the hit `rec` (of the camera ray `r`) that no geometry in
+// `world` covers within `distance`, from 0 in a closed off crack to 1 on an open plane.
+// Directions are weighted by their cosine like diffuse light, so what lies straight above
+// the surface counts more than what lies to its sides.
+pub fn occlusion(
+    world: &dyn Hittable,
+    r: &Ray,
+    rec: &HitRecord,
+    ray_epsilon: f64,
+    distance: f64,
+) -> f64 {
+    let uvw = OrthoNormalBasis::build_from_w(rec.normal);
+    let t_min = Scene::t_min(ray_epsilon, rec.p);
+    let open = (0..OCCLUSION_RAYS)
+        .filter(|_| {
+            let probe = Ray::new(rec.p, uvw.local(vec3::random_cosine_direction()), r.time())
+                .with_kind(RayKind::Shadow);
+            !world.hit(&probe, t_min, distance, &mut HitRecord::new())
+        })
+        .count();
+    open as f64 / OCCLUSION_RAYS as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+    use crate::common;
+    use crate::hittable_list::HittableList;
+    use crate::material::{Lambertian, Material};
+    use crate::shapes::{Plane, Sphere};
+    use crate::vec3::{Point3, Vec3};
+    use std::sync::Arc;
+
+    fn sphere_hit(r: &Ray) -> HitRecord {
+        let sphere = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        let mut rec = HitRecord::new();
+        assert!(sphere.hit(r, 0.001, f64::INFINITY, &mut rec));
+        rec
+    }
+
+    #[test]
+    fn normal_pass_of_the_front_of_a_sphere_is_light_blue() {
+        let r = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = sphere_hit(&r);
+        let value = RenderMode::Normal.first_hit_value(&r, &rec);
+        assert!((value - Color::new(0.5, 0.5, 1.0)).length() < 1e-9);
+        let written = color::to_srgb_bytes(RenderMode::Normal.first_hit_color(&r, &rec));
+        assert_eq!(written, [128, 128, 255]);
+
+        // From inside it is still the outward normal: the far side faces +Z too
+        let inside = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        let rec = sphere_hit(&inside);
+        let value = RenderMode::Normal.first_hit_value(&inside, &rec);
+        assert!((value - Color::new(0.5, 0.5, 1.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn depth_pass_is_half_bright_at_the_falloff_distance() {
+        let r = Ray::new(
+            Point3::new(0.0, 0.0, 1.0 + DEPTH_FALLOFF),
+            Vec3::new(0.0, 0.0, -2.0),
+            0.0,
+        );
+        let rec = sphere_hit(&r);
+        let value = RenderMode::Depth.first_hit_value(&r, &rec);
+        assert!((value - Color::new(0.5, 0.5, 0.5)).length() < 1e-9);
+    }
+
+    #[test]
+    fn open_floor_is_unoccluded_and_a_corner_is_not() {
+        common::seed_rng(12);
+        let gray: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        // A floor with two walls meeting it at the origin, the room on the +X +Z side
+        let mut world = HittableList::new();
+        for normal in [
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ] {
+            world.add(Box::new(Plane::new(
+                Point3::new(0.0, 0.0, 0.0),
+                normal,
+                gray.clone(),
+            )));
+        }
+        let floor_at = |x: f64, z: f64| {
+            let r = Ray::new(Point3::new(x, 3.0, z), Vec3::new(0.0, -1.0, 0.0), 0.0);
+            let mut rec = HitRecord::new();
+            assert!(world.hit(&r, 0.001, f64::INFINITY, &mut rec));
+            occlusion(&world, &r, &rec, 0.001, 1.0)
+        };
+
+        assert_eq!(floor_at(10.0, 10.0), 1.0);
+        let corner = floor_at(0.05, 0.05);
+        assert!(corner < 0.7, "{}", corner);
+        // Halfway along one wall, only that wall is close
+        let wall = floor_at(0.05, 10.0);
+        assert!(corner < wall && wall < 1.0, "{} {}", corner, wall);
+    }
+}
@@ -0,0 +1,255 @@
+use std::ops::Range;
+
+use crate::common;
+
+// Where in a pixel the camera rays for its samples go
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SampleStrategy {
+    // Every sample anywhere in the pixel
+    Random,
+    // The pixel is split into an n x n grid (n = floor(sqrt(samples))) with one random
+    // sample in each cell, so samples can't clump together. Samples that don't fill
+    // another complete grid are placed at random.
+    Stratified,
+    // Points of the Halton sequence, which fill the pixel evenly at any sample count, and
+    // also spread out the lens position and shutter time (see lens_and_time) together
+    // with the position in the pixel. Random sampling picks these independently, which
+    // leaves blotchy noise where depth of field and motion blur meet. Each pixel shifts
+    // the whole sequence by its own random amount, so neighboring pixels don't repeat
+    // the same pattern.
+    Halton,
+}
+
+// Bases of the Halton sequence for the sample dimensions: the position in the pixel
+// (2 and 3), the point on the lens (5 and 7) and the moment in the shutter interval (11).
+// Different primes keep the dimensions from lining up with each other.
+const HALTON_BASES: [u64; 5] = [2, 3, 5, 7, 11];
+
+// Where the random shifts of a pixel's Halton sequence are hashed from, apart from the
+// per sample offsets of the other strategies
+const HALTON_SHIFT_STREAM: u64 = 0x4841_4c54_4f4e;
+
+impl SampleStrategy {
+    pub fn from_name(name: &str) -> Option<SampleStrategy> {
+        match name {
+            "random" => Some(SampleStrategy::Random),
+            "stratified" => Some(SampleStrategy::Stratified),
+            "halton" => Some(SampleStrategy::Halton),
+            _ => None,
+        }
+    }
+
+    // Offset in [0, 1) x [0, 1) from the pixel corner for sample `index` of `count`.
+    // Halton gets a new random shift on every call, so only offsets() keeps its samples
+    // spread out.
+    pub fn offset(self, index: i32, count: i32) -> (f64, f64) {
+        self.place(
+            index,
+            count,
+            (common::random_double(), common::random_double()),
+        )
+    }
+
+    // The offsets of samples `samples` of `count` in one pixel, all made up front. Instead
+    // of two draws from the generator per sample, each offset is hashed from the pixel's
+    // seed and the sample index, which gives a sample the same offset no matter which pass
+    // takes it. It costs about the same as drawing (see offsets_timing below): a few
+    // nanoseconds either way, next to hundreds for tracing the sample.
+    pub fn offsets(self, pixel_seed: u64, samples: Range<i32>, count: i32) -> Vec<(f64, f64)> {
+        if self == SampleStrategy::Halton {
+            let shift = (halton_shift(pixel_seed, 0), halton_shift(pixel_seed, 1));
+            return samples
+                .map(|index| self.place(index, count, shift))
+                .collect();
+        }
+
+        samples
+            .map(|index| {
+                let bits = common::derive_seed(!pixel_seed, index as u64);
+                let random = (
+                    common::double_from_bits(bits),
+                    common::double_from_bits(common::derive_seed(bits, 1)),
+                );
+                self.place(index, count, random)
+            })
+            .collect()
+    }
+
+    // Where the random point `random` in [0, 1) x [0, 1) goes for sample `index` of `count`
+    fn place(self, index: i32, count: i32, random: (f64, f64)) -> (f64, f64) {
+        match self {
+            SampleStrategy::Random => random,
+            SampleStrategy::Stratified => {
+                let n = (count as f64).sqrt() as i32;
+                if index >= n * n {
+                    return random;
+                }
+                let (du, dv) = random;
+                let cell_u = (index % n) as f64;
+                let cell_v = (index / n) as f64;
+                ((cell_u + du) / n as f64, (cell_v + dv) / n as f64)
+            }
+            SampleStrategy::Halton => (
+                halton(index as u64, 0, random.0),
+                halton(index as u64, 1, random.1),
+            ),
+        }
+    }
+
+    // The numbers in [0, 1) that pick the point on the lens and the moment the shutter is
+    // open at for sample `index` of the pixel, to go with its offset: see
+    // Camera::get_ray_at. None where the camera picks them at random itself.
+    pub fn lens_and_time(self, pixel_seed: u64, index: i32) -> Option<((f64, f64), f64)> {
+        match self {
+            SampleStrategy::Halton => {
+                let dimension = |d| halton(index as u64, d, halton_shift(pixel_seed, d));
+                Some(((dimension(2), dimension(3)), dimension(4)))
+            }
+            _ => None,
+        }
+    }
+}
+
+// Number `index` of the Halton sequence in the given dimension, shifted by `shift` and
+// wrapped around to stay in [0, 1): the digits of the index in that dimension's base,
+// mirrored around the decimal point (the radical inverse)
+fn halton(index: u64, dimension: usize, shift: f64) -> f64 {
+    let base = HALTON_BASES[dimension];
+    let mut index = index;
+    let mut value = 0.0;
+    let mut digit_weight = 1.0;
+    while index > 0 {
+        digit_weight /= base as f64;
+        value += (index % base) as f64 * digit_weight;
+        index /= base;
+    }
+    (value + shift).fract()
+}
+
+// Random amount the pixel shifts the given dimension of its Halton sequence by
+fn halton_shift(pixel_seed: u64, dimension: usize) -> f64 {
+    let stream = common::derive_seed(pixel_seed, HALTON_SHIFT_STREAM);
+    common::double_from_bits(common::derive_seed(stream, dimension as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Variance over many pixels of the 16 sample estimate of a pixel whose brightness
+    // runs from 0 on one side to 1 on the other (and along the diagonal), true mean 0.5
+    fn gradient_variance(strategy: SampleStrategy) -> f64 {
+        let pixels = 2000;
+        let errors: Vec<f64> = (0..pixels)
+            .map(|pixel| {
+                let offsets = strategy.offsets(pixel, 0..16, 16);
+                let estimate = offsets.iter().map(|(u, v)| 0.5 * (u + v)).sum::<f64>() / 16.0;
+                estimate - 0.5
+            })
+            .collect();
+        errors.iter().map(|e| e * e).sum::<f64>() / pixels as f64
+    }
+
+    #[test]
+    fn stratified_samples_are_less_noisy_on_a_gradient() {
+        let random = gradient_variance(SampleStrategy::Random);
+        let stratified = gradient_variance(SampleStrategy::Stratified);
+        let halton = gradient_variance(SampleStrategy::Halton);
+        // With a sample in each cell of a 4 x 4 grid the variance drops by about 16 times
+        assert!(stratified * 4.0 < random, "{} vs {}", stratified, random);
+        assert!(halton * 4.0 < random, "{} vs {}", halton, random);
+    }
+
+    #[test]
+    fn stratified_samples_fill_one_cell_each() {
+        let offsets = SampleStrategy::Stratified.offsets(1, 0..16, 16);
+        let mut cells: Vec<_> = offsets
+            .iter()
+            .map(|(u, v)| ((u * 4.0) as i32, (v * 4.0) as i32))
+            .collect();
+        cells.sort();
+        cells.dedup();
+        assert_eq!(cells.len(), 16);
+    }
+
+    // Mean and variance of the u and v offsets together
+    fn stats(offsets: &[(f64, f64)]) -> (f64, f64) {
+        let values: Vec<f64> = offsets.iter().flat_map(|&(u, v)| [u, v]).collect();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance =
+            values.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / values.len() as f64;
+        (mean, variance)
+    }
+
+    #[test]
+    fn hashed_offsets_are_as_uniform_as_drawn_ones() {
+        common::seed_rng(5);
+        let drawn: Vec<_> = (0..50_000)
+            .map(|s| SampleStrategy::Random.offset(s, 1))
+            .collect();
+        let hashed: Vec<_> = (0..500)
+            .flat_map(|pixel| SampleStrategy::Random.offsets(pixel, 0..100, 100))
+            .collect();
+
+        // Uniform on [0, 1): mean 1/2, variance 1/12
+        for (mean, variance) in [stats(&drawn), stats(&hashed)] {
+            assert!((mean - 0.5).abs() < 0.005, "mean {}", mean);
+            assert!(
+                (variance - 1.0 / 12.0).abs() < 0.002,
+                "variance {}",
+                variance
+            );
+        }
+    }
+
+    #[test]
+    fn offsets_do_not_depend_on_how_the_samples_are_split() {
+        let all = SampleStrategy::Halton.offsets(9, 0..16, 16);
+        let mut split = SampleStrategy::Halton.offsets(9, 0..5, 16);
+        split.extend(SampleStrategy::Halton.offsets(9, 5..16, 16));
+        assert_eq!(all, split);
+    }
+
+    // Timing rather than a check, so it only runs when asked for:
+    //   cargo test --release --lib -- --ignored --nocapture offsets_timing
+    #[test]
+    #[ignore]
+    fn offsets_timing() {
+        use std::hint::black_box;
+        use std::time::Instant;
+
+        let (pixels, count) = (200_000u64, 64);
+        for strategy in [SampleStrategy::Random, SampleStrategy::Stratified] {
+            common::seed_rng(1);
+            let start = Instant::now();
+            let mut sum = 0.0;
+            for _ in 0..pixels {
+                for index in 0..count {
+                    let (u, v) = strategy.offset(index, count);
+                    sum += u + v;
+                }
+            }
+            black_box(sum);
+            let drawn = start.elapsed();
+
+            let start = Instant::now();
+            let mut sum = 0.0;
+            for pixel in 0..pixels {
+                for (u, v) in strategy.offsets(pixel, 0..count, count) {
+                    sum += u + v;
+                }
+            }
+            black_box(sum);
+            let hashed = start.elapsed();
+
+            let per_sample =
+                |d: std::time::Duration| d.as_secs_f64() * 1e9 / (pixels as f64 * count as f64);
+            println!(
+                "{:?}: two draws {:.2} ns/sample, hashed {:.2} ns/sample",
+                strategy,
+                per_sample(drawn),
+                per_sample(hashed)
+            );
+        }
+    }
+}
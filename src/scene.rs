@@ -0,0 +1,99 @@
+use crate::background::{Background, GradientSky};
+use crate::camera::Camera;
+use crate::common;
+use crate::hittable_list::HittableList;
+use crate::ray::Ray;
+use crate::vec3::Point3;
+
+// Rounding errors of a hit point stay below this many times its coordinates: doubles are
+// exact to about 2e-16 of their value, and the intersection math loses a few digits more.
+// With the default ray epsilon this only matters beyond about 1e11 units from the origin.
+const RELATIVE_RAY_EPSILON: f64 = 1e-14;
+
+// Everything needed to render an image: what's in it, where it's seen from,
+// and the image/quality settings
+pub struct Scene {
+    pub world: HittableList,
+    // Emissive objects that are also in `world`, sampled directly when shading
+    pub lights: HittableList,
+    pub camera: Camera,
+    pub background: Box<dyn Background>,
+    pub image_width: i32,
+    // Must match the aspect ratio the camera was built with
+    pub aspect_ratio: f64,
+    pub samples_per_pixel: i32,
+    // Maximum number of bounces per path
+    pub max_depth: i32,
+    // How far (in units of the ray's direction) a hit must be from where the ray starts,
+    // so rays leaving a surface don't hit that same surface again through rounding errors
+    // ("shadow acne"). Far from the origin, where rounding errors get bigger, it grows
+    // with the distance (see t_min). It is separate from the 1e-8 limits in the flat
+    // shapes (Square, Disk, ...), which skip rays running parallel to the surface.
+    pub ray_epsilon: f64,
+    // How far (in scene units) any ray looks for something to hit. Whatever is further
+    // away is left out as if it wasn't there, and the ray sees the background instead.
+    // Infinite by default; a finite one avoids precision trouble with very distant
+    // geometry and bounds the distances fog has to cover.
+    pub far_clip: f64,
+}
+
+impl Scene {
+    // Scene with the default settings: no lights, gradient sky, 800 pixels wide at 3:2,
+    // 500 samples per pixel, up to 50 bounces, a ray epsilon of 0.001 and no far clip
+    pub fn new(world: HittableList, camera: Camera) -> Scene {
+        Scene {
+            world,
+            lights: HittableList::new(),
+            camera,
+            background: Box::new(GradientSky::default()),
+            image_width: 800,
+            aspect_ratio: 3.0 / 2.0,
+            samples_per_pixel: 500,
+            max_depth: 50,
+            ray_epsilon: 0.001,
+            far_clip: common::INFINITY,
+        }
+    }
+
+    // Smallest t a hit of a ray starting at `origin` may have: the ray epsilon, or more
+    // where the coordinates are so large that rounding errors reach that far
+    pub fn t_min(ray_epsilon: f64, origin: Point3) -> f64 {
+        let largest = f64::max(
+            origin.x().abs(),
+            f64::max(origin.y().abs(), origin.z().abs()),
+        );
+        f64::max(ray_epsilon, RELATIVE_RAY_EPSILON * largest)
+    }
+
+    // Largest t a hit of the ray `r` may have: t is in units of the ray's direction, which
+    // needn't be a unit vector, while the far clip is a distance
+    pub fn t_max(far_clip: f64, r: &Ray) -> f64 {
+        far_clip / r.direction().length()
+    }
+
+    pub fn image_height(&self) -> i32 {
+        (self.image_width as f64 / self.aspect_ratio) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_scene_has_the_default_settings() {
+        let camera = Camera::builder().aspect_ratio(2.0).build();
+        let mut scene = Scene::new(HittableList::new(), camera);
+        assert!(scene.world.is_empty());
+        assert!(scene.lights.is_empty());
+        assert_eq!(scene.image_width, 800);
+        assert_eq!(scene.samples_per_pixel, 500);
+        assert_eq!(scene.max_depth, 50);
+        assert_eq!(scene.ray_epsilon, 0.001);
+        assert_eq!(scene.far_clip, f64::INFINITY);
+        assert_eq!(scene.image_height(), 533);
+
+        scene.aspect_ratio = 2.0;
+        assert_eq!(scene.image_height(), 400);
+    }
+}
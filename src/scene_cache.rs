@@ -0,0 +1,132 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_json::Value;
+
+// Renders of scene files kept on disk (--cache-dir), so rendering a scene again when
+// nothing about it changed loads the earlier result instead. Each render is stored as a
+// checkpoint named after the hash of the scene and the settings it was rendered with.
+
+// Hash identifying a render of the scene `scene` with `settings` (a description of the
+// render settings that change the image): the scene as written back out (compact, keys
+// sorted), so its layout in the file and the order of the keys don't matter but every
+// value does, the contents of the files it names with "path" keys (environment maps), and
+// the settings.
+pub fn scene_hash(scene: &Value, settings: &str) -> u64 {
+    let mut hash = Fnv::new();
+    hash.write(scene.to_string().as_bytes());
+    for path in named_files(scene) {
+        // A file that can't be read hashes as empty, loading the scene reports it
+        hash.write(&fs::read(path).unwrap_or_default());
+    }
+    hash.write(settings.as_bytes());
+    hash.0
+}
+
+// The same for the scene file at `path`
+pub fn scene_file_hash(path: &str, settings: &str) -> io::Result<u64> {
+    let scene: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    Ok(scene_hash(&scene, settings))
+}
+
+// Where the render with the given hash is kept in `cache_dir`
+pub fn cache_path(cache_dir: &str, hash: u64) -> String {
+    Path::new(cache_dir)
+        .join(format!("{:016x}.checkpoint", hash))
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Every string under a "path" key, anywhere in the scene
+fn named_files(value: &Value) -> Vec<&str> {
+    match value {
+        Value::Array(items) => items.iter().flat_map(named_files).collect(),
+        Value::Object(fields) => fields
+            .iter()
+            .flat_map(|(key, value)| match (key.as_str(), value) {
+                ("path", Value::String(path)) => vec![path.as_str()],
+                _ => named_files(value),
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// 64 bit FNV-1a: simple, and the same on every platform and Rust version, unlike the
+// standard library's hashers, so the cache stays valid from one build to the next
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+        // Mark the end, so moving bytes from one part to the next changes the hash
+        self.0 ^= 0xff;
+        self.0 = self.0.wrapping_mul(0x100000001b3);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn scene(radius: f64) -> Value {
+        json!({
+            "objects": [
+                { "type": "sphere", "center": [0, 1, 0], "radius": radius,
+                  "material": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } }
+            ]
+        })
+    }
+
+    #[test]
+    fn same_scene_hashes_the_same_and_a_changed_radius_does_not() {
+        assert_eq!(
+            scene_hash(&scene(1.0), "80x60"),
+            scene_hash(&scene(1.0), "80x60")
+        );
+        assert_ne!(
+            scene_hash(&scene(1.0), "80x60"),
+            scene_hash(&scene(1.5), "80x60")
+        );
+        assert_ne!(
+            scene_hash(&scene(1.0), "80x60"),
+            scene_hash(&scene(1.0), "160x120")
+        );
+
+        // The same scene written differently is still the same scene
+        let reordered: Value = serde_json::from_str(
+            r#"{ "objects": [ { "radius": 1.0, "type": "sphere", "center": [0, 1, 0],
+                 "material": { "albedo": [0.5, 0.5, 0.5], "type": "lambertian" } } ] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            scene_hash(&reordered, "80x60"),
+            scene_hash(&scene(1.0), "80x60")
+        );
+    }
+
+    #[test]
+    fn files_the_scene_names_count_too() {
+        let path = std::env::temp_dir().join(format!("scene-cache-{}.ppm", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        let scene = json!({ "background": { "type": "environment", "path": path_str } });
+
+        fs::write(&path, b"first").unwrap();
+        let first = scene_hash(&scene, "");
+        fs::write(&path, b"second").unwrap();
+        let second = scene_hash(&scene, "");
+        fs::remove_file(&path).unwrap();
+        assert_ne!(first, second);
+
+        assert!(cache_path("cache", 0xab).ends_with("00000000000000ab.checkpoint"));
+    }
+}
@@ -0,0 +1,558 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::background::{Background, EnvironmentMap, GradientSky, SolidBackground};
+use crate::camera::{Camera, ShutterCurve};
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::material::{Material, MaterialParams};
+use crate::scene::Scene;
+use crate::shapes::{
+    Capsule, Cube, Cylinder, Ellipsoid, Plane, Rectangle, RectangularBox, Rotate, RotateY, Sphere,
+    Square, Translate, Visibility,
+};
+use crate::vec3::{self, Vec3};
+
+// Read a scene from a JSON description instead of building it in code:
+//
+// {
+//   "image": { "width": 400, "aspect_ratio": 1.5, "samples_per_pixel": 100, "max_depth": 50 },
+//   "camera": { "look_from": [0, 2, 5], "look_at": [0, 1, 0], "vfov": 43 },
+//   "background": { "type": "solid", "color": [0, 0, 0] },
+//   "materials": { "ground": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+//   "objects": [
+//     { "type": "plane", "point": [0, 0, 0], "normal": [0, 1, 0], "material": "ground" },
+//     { "type": "sphere", "center": [0, 1, 0], "radius": 1,
+//       "material": { "type": "dielectric", "refraction_index": 1.5 } }
+//   ]
+// }
+//
+// Every section and setting is optional and falls back to the Scene::new / Camera::builder
+// defaults. An object's material is either the name of an entry in "materials" or a
+// material written out in place. Any object can be turned around the Y axis with
+// "rotate_y" (degrees) or around any axis with "rotate" ({"axis": [x, y, z], "angle": a}),
+// and moved with "translate" ([x, y, z]). "primary_visible": false hides an object from the
+// camera (it still casts shadows and shows in reflections), "cast_shadows": false keeps it
+// from blocking the light. Spheres, squares and rectangles with a diffuse_light material
+// are also added to the scene's lights so they get sampled directly, other shapes only
+// light what bounces into them. A "rectangle" is given by its "center", two edge
+// directions "u_axis" and "v_axis" (it faces along their cross product) and the
+// "width" and "height" of the edges along them.
+//
+// Besides the settings above, "image" takes "ray_epsilon" and "far_clip" (see Scene).
+pub fn load_scene(path: &str) -> io::Result<Scene> {
+    let contents = fs::read_to_string(path)?;
+    parse_scene(&contents)
+}
+
+pub fn parse_scene(contents: &str) -> io::Result<Scene> {
+    let root = serde_json::from_str::<Value>(contents)
+        .map_err(|e| invalid_data("scene", &e.to_string()))?;
+    if !matches!(root, Value::Object(_)) {
+        return Err(invalid_data("scene", "expected an object"));
+    }
+
+    let mut materials = HashMap::new();
+    if let Some(section) = root.get("materials") {
+        let Value::Object(entries) = section else {
+            return Err(invalid_data("materials", "expected an object"));
+        };
+        for (name, description) in entries {
+            let context = format!("materials.{}", name);
+            materials.insert(name.clone(), built(material(description, &context)?));
+        }
+    }
+
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+    if let Some(section) = root.get("objects") {
+        let objects = section
+            .as_array()
+            .ok_or_else(|| invalid_data("objects", "expected an array"))?;
+        for (i, description) in objects.iter().enumerate() {
+            let context = format!("objects[{}]", i);
+            let (mat, emissive) = match description.get("material") {
+                Some(Value::String(name)) => materials.get(name).cloned().ok_or_else(|| {
+                    invalid_data(&context, &format!("unknown material '{}'", name))
+                })?,
+                Some(inline) => built(material(inline, &format!("{}.material", context))?),
+                None => return Err(invalid_data(&context, "missing 'material'")),
+            };
+
+            world.add(object(description, mat.clone(), &context)?);
+            // Emitters that can't be sampled would be given the wrong density, they're
+            // left to be found by bouncing
+            if emissive {
+                let light = object(description, mat, &context)?;
+                if light.can_sample_light() {
+                    lights.add(light);
+                }
+            }
+        }
+    }
+
+    let image = root.get("image");
+    let image_setting = |key: &str| -> io::Result<Option<f64>> {
+        image
+            .map(|image| optional_number(image, key, "image"))
+            .transpose()
+            .map(Option::flatten)
+    };
+    // Counts like the width must be whole and at least 1, as on the command line
+    let image_count = |key: &str| -> io::Result<Option<i32>> {
+        match image_setting(key)? {
+            Some(n) if n >= 1.0 && n.fract() == 0.0 && n <= i32::MAX as f64 => Ok(Some(n as i32)),
+            Some(n) => Err(invalid_data(
+                "image",
+                &format!("'{}' must be a positive whole number, got {}", key, n),
+            )),
+            None => Ok(None),
+        }
+    };
+    let aspect_ratio = image_setting("aspect_ratio")?;
+
+    let camera = camera(root.get("camera"), aspect_ratio)?;
+    let mut scene = Scene::new(world, camera);
+    scene.lights = lights;
+    if let Some(aspect_ratio) = aspect_ratio {
+        scene.aspect_ratio = aspect_ratio;
+    }
+    if let Some(width) = image_count("width")? {
+        scene.image_width = width;
+    }
+    if let Some(samples) = image_count("samples_per_pixel")? {
+        scene.samples_per_pixel = samples;
+    }
+    if let Some(max_depth) = image_count("max_depth")? {
+        scene.max_depth = max_depth;
+    }
+    if let Some(ray_epsilon) = image_setting("ray_epsilon")? {
+        scene.ray_epsilon = ray_epsilon;
+    }
+    if let Some(far_clip) = image_setting("far_clip")? {
+        scene.far_clip = far_clip;
+    }
+    if let Some(background) = root.get("background") {
+        scene.background = self::background(background)?;
+    }
+
+    Ok(scene)
+}
+
+fn material(description: &Value, context: &str) -> io::Result<MaterialParams> {
+    let params = match type_name(description, context)? {
+        "lambertian" => MaterialParams::Lambertian {
+            albedo: vector(description, "albedo", context)?,
+        },
+        "metal" => MaterialParams::Metal {
+            albedo: vector(description, "albedo", context)?,
+            fuzz: optional_number(description, "fuzz", context)?.unwrap_or(0.0),
+        },
+        "dielectric" => MaterialParams::Dielectric {
+            refraction_index: number(description, "refraction_index", context)?,
+        },
+        "diffuse_light" => MaterialParams::DiffuseLight {
+            emit: vector(description, "emit", context)?,
+        },
+        other => {
+            return Err(invalid_data(
+                context,
+                &format!("unknown material type '{}'", other),
+            ))
+        }
+    };
+    Ok(params)
+}
+
+// The material and whether it emits light
+fn built(params: MaterialParams) -> (Arc<dyn Material>, bool) {
+    (params.build(), params.is_emissive())
+}
+
+fn object(
+    description: &Value,
+    mat: Arc<dyn Material>,
+    context: &str,
+) -> io::Result<Box<dyn Hittable>> {
+    let d = description;
+    let mut object: Box<dyn Hittable> = match type_name(d, context)? {
+        "sphere" => Box::new(Sphere::new(
+            vector(d, "center", context)?,
+            number(d, "radius", context)?,
+            mat,
+        )),
+        "capsule" => Box::new(Capsule::new(
+            vector(d, "a", context)?,
+            vector(d, "b", context)?,
+            number(d, "radius", context)?,
+            mat,
+        )),
+        "cube" => Box::new(Cube::new(
+            vector(d, "min", context)?,
+            vector(d, "max", context)?,
+            mat,
+        )),
+        "box" => Box::new(RectangularBox::new(
+            vector(d, "min", context)?,
+            vector(d, "max", context)?,
+            mat,
+        )),
+        "cylinder" => Box::new(Cylinder {
+            capped: optional_bool(d, "capped", context)?.unwrap_or(true),
+            ..Cylinder::new(
+                vector(d, "base_center", context)?,
+                optional_vector(d, "axis", context)?.unwrap_or(Vec3::new(0.0, 1.0, 0.0)),
+                number(d, "radius", context)?,
+                number(d, "height", context)?,
+                mat,
+            )
+        }),
+        "square" => Box::new(Square::new(
+            vector(d, "center", context)?,
+            optional_vector(d, "normal", context)?.unwrap_or(Vec3::new(0.0, 1.0, 0.0)),
+            number(d, "size", context)?,
+            mat,
+        )),
+        "rectangle" => {
+            let u_axis = vector(d, "u_axis", context)?;
+            let v_axis = vector(d, "v_axis", context)?;
+            if vec3::cross(u_axis, v_axis).near_zero() {
+                return Err(invalid_data(
+                    context,
+                    "'u_axis' and 'v_axis' must point in different directions",
+                ));
+            }
+            Box::new(Rectangle::new(
+                vector(d, "center", context)?,
+                u_axis,
+                v_axis,
+                number(d, "width", context)? / 2.0,
+                number(d, "height", context)? / 2.0,
+                mat,
+            ))
+        }
+        "ellipsoid" => Box::new(Ellipsoid::new(
+            vector(d, "center", context)?,
+            vector(d, "radii", context)?,
+            mat,
+        )),
+        "plane" => Box::new(Plane::new(
+            vector(d, "point", context)?,
+            optional_vector(d, "normal", context)?.unwrap_or(Vec3::new(0.0, 1.0, 0.0)),
+            mat,
+        )),
+        other => {
+            return Err(invalid_data(
+                context,
+                &format!("unknown object type '{}'", other),
+            ))
+        }
+    };
+
+    // Optional placement on top of the shape's own coordinates: turned first, then moved
+    if let Some(angle) = optional_number(d, "rotate_y", context)? {
+        object = Box::new(RotateY::new(object, angle));
+    }
+    if let Some(rotate) = d.get("rotate") {
+        let context = format!("{}.rotate", context);
+        object = Box::new(Rotate::new(
+            object,
+            vector(rotate, "axis", &context)?,
+            number(rotate, "angle", &context)?,
+        ));
+    }
+    if let Some(offset) = optional_vector(d, "translate", context)? {
+        object = Box::new(Translate::new(object, offset));
+    }
+
+    let primary_visible = optional_bool(d, "primary_visible", context)?.unwrap_or(true);
+    let cast_shadows = optional_bool(d, "cast_shadows", context)?.unwrap_or(true);
+    if !primary_visible || !cast_shadows {
+        object = Box::new(Visibility::new(object, primary_visible, cast_shadows));
+    }
+    Ok(object)
+}
+
+fn camera(description: Option<&Value>, aspect_ratio: Option<f64>) -> io::Result<Camera> {
+    let mut builder = Camera::builder().aspect_ratio(aspect_ratio.unwrap_or(3.0 / 2.0));
+    let Some(d) = description else {
+        return Ok(builder.build());
+    };
+    let context = "camera";
+
+    if let Some(lookfrom) = optional_vector(d, "look_from", context)? {
+        builder = builder.look_from(lookfrom);
+    }
+    if let Some(lookat) = optional_vector(d, "look_at", context)? {
+        builder = builder.look_at(lookat);
+    }
+    if let Some(vup) = optional_vector(d, "vup", context)? {
+        builder = builder.vup(vup);
+    }
+    if let Some(vfov) = optional_number(d, "vfov", context)? {
+        builder = builder.vfov(vfov);
+    }
+    if let Some(aperture) = optional_number(d, "aperture", context)? {
+        builder = builder.aperture(aperture);
+    }
+    if let Some(blades) = optional_number(d, "aperture_blades", context)? {
+        if blades < 0.0 || blades.fract() != 0.0 {
+            return Err(invalid_data(
+                context,
+                "'aperture_blades' must be a whole number",
+            ));
+        }
+        builder = builder.aperture_blades(blades as u32);
+    }
+    if let Some(focus_dist) = optional_number(d, "focus_dist", context)? {
+        builder = builder.focus_dist(focus_dist);
+    }
+    match d.get("projection").map(|p| p.as_str()) {
+        None | Some(Some("perspective")) => {}
+        Some(Some("equirectangular")) => builder = builder.equirectangular(),
+        Some(Some("fisheye")) => {
+            let fov = optional_number(d, "fisheye_fov", context)?.unwrap_or(180.0);
+            builder = builder.fisheye(fov);
+        }
+        Some(Some(other)) => {
+            return Err(invalid_data(
+                context,
+                &format!("unknown projection '{}'", other),
+            ))
+        }
+        Some(None) => return Err(invalid_data(context, "'projection' must be a string")),
+    }
+    if let Some(viewport_height) = optional_number(d, "orthographic", context)? {
+        builder = builder.orthographic(viewport_height);
+    }
+    if let Some(shutter) = optional_vector2(d, "shutter", context)? {
+        builder = builder.shutter(shutter.0, shutter.1);
+    }
+    if let Some(curve) = d.get("shutter_curve") {
+        let curve = curve
+            .as_str()
+            .and_then(ShutterCurve::from_name)
+            .ok_or_else(|| {
+                invalid_data(context, "'shutter_curve' must be \"box\" or \"triangle\"")
+            })?;
+        builder = builder.shutter_curve(curve);
+    }
+    Ok(builder.build())
+}
+
+fn background(description: &Value) -> io::Result<Box<dyn Background>> {
+    let context = "background";
+    let background: Box<dyn Background> = match type_name(description, context)? {
+        "gradient" => {
+            let default = GradientSky::default();
+            Box::new(GradientSky::new(
+                optional_vector(description, "top", context)?.unwrap_or(default.top),
+                optional_vector(description, "bottom", context)?.unwrap_or(default.bottom),
+            ))
+        }
+        "solid" => Box::new(SolidBackground(vector(description, "color", context)?)),
+        "environment" => {
+            let path = description
+                .get("path")
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid_data(context, "'path' must be a string"))?;
+            Box::new(EnvironmentMap::load(path).map_err(|e| invalid_data(context, &e.to_string()))?)
+        }
+        other => {
+            return Err(invalid_data(
+                context,
+                &format!("unknown background type '{}'", other),
+            ))
+        }
+    };
+    Ok(background)
+}
+
+fn type_name<'a>(description: &'a Value, context: &str) -> io::Result<&'a str> {
+    description
+        .get("type")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid_data(context, "missing 'type'"))
+}
+
+fn number(description: &Value, key: &str, context: &str) -> io::Result<f64> {
+    optional_number(description, key, context)?
+        .ok_or_else(|| invalid_data(context, &format!("missing '{}'", key)))
+}
+
+fn optional_number(description: &Value, key: &str, context: &str) -> io::Result<Option<f64>> {
+    match description.get(key) {
+        None => Ok(None),
+        Some(value) => value
+            .as_f64()
+            .map(Some)
+            .ok_or_else(|| invalid_data(context, &format!("'{}' must be a number", key))),
+    }
+}
+
+fn optional_bool(description: &Value, key: &str, context: &str) -> io::Result<Option<bool>> {
+    match description.get(key) {
+        None => Ok(None),
+        Some(value) => value
+            .as_bool()
+            .map(Some)
+            .ok_or_else(|| invalid_data(context, &format!("'{}' must be true or false", key))),
+    }
+}
+
+fn vector(description: &Value, key: &str, context: &str) -> io::Result<Vec3> {
+    optional_vector(description, key, context)?
+        .ok_or_else(|| invalid_data(context, &format!("missing '{}'", key)))
+}
+
+// [x, y, z] for points, directions and colors
+fn optional_vector(description: &Value, key: &str, context: &str) -> io::Result<Option<Vec3>> {
+    let Some(value) = description.get(key) else {
+        return Ok(None);
+    };
+    match numbers(value).as_deref() {
+        Some(&[x, y, z]) => Ok(Some(Vec3::new(x, y, z))),
+        _ => Err(invalid_data(
+            context,
+            &format!("'{}' must be an array of three numbers", key),
+        )),
+    }
+}
+
+fn optional_vector2(
+    description: &Value,
+    key: &str,
+    context: &str,
+) -> io::Result<Option<(f64, f64)>> {
+    let Some(value) = description.get(key) else {
+        return Ok(None);
+    };
+    match numbers(value).as_deref() {
+        Some(&[a, b]) => Ok(Some((a, b))),
+        _ => Err(invalid_data(
+            context,
+            &format!("'{}' must be an array of two numbers", key),
+        )),
+    }
+}
+
+fn numbers(value: &Value) -> Option<Vec<f64>> {
+    value.as_array()?.iter().map(Value::as_f64).collect()
+}
+
+fn invalid_data(context: &str, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {}", context, message),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../scenes/glass-and-light.json");
+
+    #[test]
+    fn scene_survives_writing_out_and_reading_back() {
+        let description: Value = serde_json::from_str(EXAMPLE).unwrap();
+        let written = serde_json::to_string_pretty(&description).unwrap();
+        let original = parse_scene(EXAMPLE).unwrap();
+        let read_back = parse_scene(&written).unwrap();
+
+        assert!(!original.world.is_empty());
+        assert_eq!(read_back.world.len(), original.world.len());
+        assert_eq!(read_back.lights.len(), original.lights.len());
+        assert_eq!(read_back.image_width, original.image_width);
+        assert_eq!(read_back.samples_per_pixel, original.samples_per_pixel);
+    }
+
+    #[test]
+    fn broken_scene_files_are_errors() {
+        for contents in [
+            "{",
+            "[]",
+            r#"{ "objects": [{ "type": "sphere", "center": [0, 0], "radius": 1,
+                "material": { "type": "lambertian", "albedo": [1, 1, 1] } }] }"#,
+            r#"{ "objects": [{ "type": "teapot", "material": "missing" }] }"#,
+            r#"{ "objects": [{ "type": "rectangle", "center": [0, 0, 0], "u_axis": [1, 0, 0],
+                "v_axis": [-2, 0, 0], "width": 1, "height": 1,
+                "material": { "type": "lambertian", "albedo": [1, 1, 1] } }] }"#,
+            r#"{ "background": { "type": "solid" } }"#,
+            r#"{ "background": { "type": "environment", "path": "no/such/sky.ppm" } }"#,
+        ] {
+            let error = parse_scene(contents).err().expect(contents);
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn only_lights_that_can_be_sampled_are_registered() {
+        let scene = parse_scene(
+            r#"{
+                "materials": { "lamp": { "type": "diffuse_light", "emit": [4, 4, 4] } },
+                "objects": [
+                    { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "lamp",
+                      "translate": [0, 2, 0] },
+                    { "type": "square", "center": [0, 3, 0], "normal": [0, -1, 0], "size": 1,
+                      "material": "lamp", "rotate_y": 30 },
+                    { "type": "rectangle", "center": [0, 3, 2], "u_axis": [1, 0, 0],
+                      "v_axis": [0, 0, 1], "width": 2, "height": 0.5, "material": "lamp" },
+                    { "type": "cube", "min": [-1, 0, -1], "max": [1, 1, 1], "material": "lamp" },
+                    { "type": "sphere", "center": [3, 0, 0], "radius": 1,
+                      "material": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(scene.world.len(), 5);
+        // The cube still glows, but isn't sampled
+        assert_eq!(scene.lights.len(), 3);
+        assert!(scene.lights.can_sample_light());
+    }
+
+    #[test]
+    fn image_counts_must_be_positive() {
+        for setting in [
+            r#""width": 0"#,
+            r#""width": -20"#,
+            r#""samples_per_pixel": 0"#,
+            r#""samples_per_pixel": 2.5"#,
+            r#""max_depth": -1"#,
+            r#""width": 1e12"#,
+        ] {
+            let error = parse_scene(&format!(r#"{{ "image": {{ {} }} }}"#, setting))
+                .err()
+                .unwrap_or_else(|| panic!("{} was accepted", setting));
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+
+        let scene =
+            parse_scene(r#"{ "image": { "width": 64, "samples_per_pixel": 1, "max_depth": 3 } }"#)
+                .unwrap();
+        assert_eq!(scene.image_width, 64);
+        assert_eq!(scene.samples_per_pixel, 1);
+        assert_eq!(scene.max_depth, 3);
+    }
+
+    #[test]
+    fn lights_without_shadows_are_not_registered() {
+        let scene = parse_scene(
+            r#"{
+                "objects": [
+                    { "type": "sphere", "center": [0, 2, 0], "radius": 1, "cast_shadows": false,
+                      "material": { "type": "diffuse_light", "emit": [4, 4, 4] } },
+                    { "type": "sphere", "center": [0, 5, 0], "radius": 1, "primary_visible": false,
+                      "material": { "type": "diffuse_light", "emit": [4, 4, 4] } }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(scene.world.len(), 2);
+        assert_eq!(scene.lights.len(), 1);
+    }
+}
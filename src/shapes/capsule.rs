@@ -0,0 +1,147 @@
+use std::sync::Arc;
+
+use crate::aabb::{self, Aabb};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::shapes::Sphere;
+use crate::vec3::{self, Point3, Vec3};
+
+// All points within `radius` of the segment between `a` and `b`: a cylinder with a
+// hemisphere on each end. When a and b coincide it is simply a sphere.
+pub struct Capsule {
+    a: Point3,
+    b: Point3,
+    radius: f64,
+    mat: Arc<dyn Material>,
+}
+
+impl Capsule {
+    pub fn new(a: Point3, b: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Capsule {
+            a,
+            b,
+            radius,
+            mat: material,
+        }
+    }
+}
+
+// Roots of a * t^2 + 2 * half_b * t + c = 0, nearest first
+fn roots(a: f64, half_b: f64, c: f64) -> Option<[f64; 2]> {
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 || a.abs() < 1e-12 {
+        return None;
+    }
+    let sqrt_d = discriminant.sqrt();
+    Some([(-half_b - sqrt_d) / a, (-half_b + sqrt_d) / a])
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let axis = self.b - self.a;
+        let length = axis.length();
+        let mut closest_so_far = t_max;
+        let mut hit_normal = None;
+
+        // Body: a ray hits the tube where its distance to the axis line is the radius,
+        // which only depends on the parts of the ray perpendicular to the axis
+        if length > 0.0 {
+            let axis_dir = axis / length;
+            let oc = r.origin() - self.a;
+            let d_perp = r.direction() - axis_dir * vec3::dot(r.direction(), axis_dir);
+            let oc_perp = oc - axis_dir * vec3::dot(oc, axis_dir);
+
+            let a = d_perp.length_squared();
+            let half_b = vec3::dot(d_perp, oc_perp);
+            let c = oc_perp.length_squared() - self.radius * self.radius;
+            for t in roots(a, half_b, c).into_iter().flatten() {
+                if t <= t_min || t >= closest_so_far {
+                    continue;
+                }
+                let p = r.at(t);
+                let h = vec3::dot(p - self.a, axis_dir);
+                // past the ends of the segment the caps take over
+                if h < 0.0 || h > length {
+                    continue;
+                }
+                closest_so_far = t;
+                hit_normal = Some((p - self.a - axis_dir * h) / self.radius);
+                break;
+            }
+        }
+
+        // Caps: the half of the sphere around each end point that lies beyond the segment
+        for (center, side) in [(self.a, -1.0), (self.b, 1.0)] {
+            let oc = r.origin() - center;
+            let a = r.direction().length_squared();
+            let half_b = vec3::dot(oc, r.direction());
+            let c = oc.length_squared() - self.radius * self.radius;
+            for t in roots(a, half_b, c).into_iter().flatten() {
+                if t <= t_min || t >= closest_so_far {
+                    continue;
+                }
+                let p = r.at(t);
+                if side * vec3::dot(p - center, axis) < 0.0 {
+                    continue;
+                }
+                closest_so_far = t;
+                hit_normal = Some((p - center) / self.radius);
+                break;
+            }
+        }
+
+        let Some(outward_normal) = hit_normal else {
+            return false;
+        };
+        rec.t = closest_so_far;
+        rec.p = r.at(closest_so_far);
+        rec.set_face_normal(r, outward_normal);
+        // Texture coordinates by normal direction, the same as a sphere's
+        (rec.u, rec.v) = Sphere::get_sphere_uv(outward_normal);
+        rec.mat = Some(self.mat.clone());
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(aabb::surrounding_box(
+            Aabb::new(self.a - r, self.a + r),
+            Aabb::new(self.b - r, self.b + r),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn rounded_ends_have_sphere_normals() {
+        // Upright from y = 0 to y = 2, radius 0.5
+        let b = Point3::new(0.0, 2.0, 0.0);
+        let capsule = Capsule::new(
+            Point3::new(0.0, 0.0, 0.0),
+            b,
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+
+        // Straight down onto the top end, at several distances from the axis
+        for (x, z) in [(0.0, 0.0), (0.3, 0.2), (-0.1, 0.45), (0.2, -0.3)] {
+            let r = Ray::new(Point3::new(x, 5.0, z), Vec3::new(0.0, -1.0, 0.0), 0.0);
+            let mut rec = HitRecord::new();
+            assert!(capsule.hit(&r, 0.001, f64::INFINITY, &mut rec));
+            assert!(rec.p.y() > 2.0);
+            // On the end's sphere, with the normal pointing away from its center
+            assert!(((rec.p - b).length() - 0.5).abs() < 1e-9);
+            assert!(
+                (rec.normal - (rec.p - b) / 0.5).length() < 1e-9,
+                "{}",
+                rec.normal
+            );
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::{self, Aabb},
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+    vec3::{self, dot, Point3, Vec3},
+};
+
+use super::cylinder::{angle_u, cap_box};
+
+// A finite cone with its tip at `apex`, opening along `axis` until it reaches
+// `radius` at distance `height`, optionally closed by a flat base cap
+pub struct Cone {
+    pub apex: Point3,
+    pub axis: Vec3, // Normalized axis vector (direction from apex to base)
+    pub radius: f64,
+    pub height: f64,
+    pub capped: bool,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(
+        apex: Point3,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            apex,
+            axis: vec3::unit_vector(axis),
+            radius,
+            height,
+            capped: true,
+            material,
+        }
+    }
+
+    // Same cone without the base cap
+    pub fn open(
+        apex: Point3,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            capped: false,
+            ..Cone::new(apex, axis, radius, height, material)
+        }
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let axis = self.axis;
+        // the lateral surface is |p - apex|^2 = (1 + k^2) * (dot(p - apex, axis))^2
+        let k = self.radius / self.height;
+        let m = 1.0 + k * k;
+
+        let oc = r.origin() - self.apex;
+        let d_dot_axis = dot(r.direction(), axis);
+        let oc_dot_axis = dot(oc, axis);
+
+        let a = r.direction().length_squared() - m * d_dot_axis * d_dot_axis;
+        let half_b = dot(oc, r.direction()) - m * d_dot_axis * oc_dot_axis;
+        let c = oc.length_squared() - m * oc_dot_axis * oc_dot_axis;
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+
+        // Lateral surface intersection
+        let discriminant = half_b * half_b - a * c;
+        if discriminant >= 0.0 && a.abs() > 1e-12 {
+            let sqrt_d = discriminant.sqrt();
+            let mut roots = [(-half_b - sqrt_d) / a, (-half_b + sqrt_d) / a];
+            roots.sort_by(|x, y| x.total_cmp(y));
+            for t in roots {
+                if t < t_min || t > closest_so_far {
+                    continue;
+                }
+                let p = r.at(t);
+                // reject the mirrored cone on the other side of the apex and anything past the base
+                let h = dot(p - self.apex, axis);
+                if h < 0.0 || h > self.height {
+                    continue;
+                }
+                closest_so_far = t;
+                rec.t = t;
+                rec.p = p;
+                // gradient of the cone equation, pointing away from the axis
+                // (it vanishes exactly at the tip, where we just point back along the axis)
+                let gradient = p - self.apex - axis * (m * h);
+                let outward_normal = if gradient.near_zero() {
+                    -axis
+                } else {
+                    vec3::unit_vector(gradient)
+                };
+                rec.set_face_normal(r, outward_normal);
+                // Around the axis like Cylinder, and from the tip (0) down to the base (1)
+                rec.u = angle_u(axis, p - self.apex - axis * h);
+                rec.v = h / self.height;
+                rec.mat = Some(self.material.clone());
+                hit_anything = true;
+                break;
+            }
+        }
+
+        // Base cap intersection
+        if self.capped && d_dot_axis.abs() > 1e-8 {
+            let cap_center = self.apex + axis * self.height;
+            let t = dot(cap_center - r.origin(), axis) / d_dot_axis;
+            if t >= t_min && t <= closest_so_far {
+                let p = r.at(t);
+                if (p - cap_center).length_squared() <= self.radius * self.radius {
+                    rec.t = t;
+                    rec.p = p;
+                    rec.set_face_normal(r, axis);
+                    // Polar coordinates, like the caps of a Cylinder
+                    rec.u = angle_u(axis, p - cap_center);
+                    rec.v = (p - cap_center).length() / self.radius;
+                    rec.mat = Some(self.material.clone());
+                    hit_anything = true;
+                }
+            }
+        }
+
+        hit_anything
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let base_center = self.apex + self.axis * self.height;
+        Some(aabb::surrounding_box(
+            Aabb::new(self.apex, self.apex),
+            cap_box(base_center, self.axis, self.radius),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::shapes::sphere::Sphere;
+
+    #[test]
+    fn side_normal_points_out_and_away_from_the_axis() {
+        // Tip at y = 2, widening down to a radius of 1 at y = 0
+        let cone = Cone::new(
+            Point3::new(0.0, 2.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            1.0,
+            2.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        let r = Ray::new(Point3::new(5.0, 1.0, 0.0), Vec3::new(-1.0, 0.0, 0.0), 0.0);
+        let mut rec = HitRecord::new();
+        assert!(cone.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        // Halfway down it is half as wide
+        assert!((rec.p - Point3::new(0.5, 1.0, 0.0)).length() < 1e-9);
+        assert!(rec.is_front_face());
+        // The side rises 2 for every 1 it comes in, so the normal leans up by as much
+        let expected = vec3::unit_vector(Vec3::new(2.0, 1.0, 0.0));
+        assert!((rec.normal - expected).length() < 1e-9, "{}", rec.normal);
+    }
+
+    #[test]
+    fn side_and_cap_set_their_own_texture_coordinates() {
+        let gray: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        // Upright: tip at y = 2, base of radius 1 at y = 0
+        let cone = Cone::new(
+            Point3::new(0.0, 2.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            1.0,
+            2.0,
+            gray.clone(),
+        );
+        // Something farther along each ray is hit first and leaves its coordinates behind
+        let behind = Sphere::new(Point3::new(0.0, 0.0, 0.0), 20.0, gray);
+        let first_hit = |r: &Ray| {
+            let mut rec = HitRecord::new();
+            assert!(behind.hit(r, 0.001, f64::INFINITY, &mut rec));
+            assert!(cone.hit(r, 0.001, rec.t, &mut rec));
+            rec
+        };
+
+        // Side, halfway down, on +Z: the axis points down, so u turns from +X away from +Z
+        // and +Z is three quarters of the way round
+        let side = first_hit(&Ray::new(
+            Point3::new(0.0, 1.0, 5.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            0.0,
+        ));
+        assert!((side.p - Point3::new(0.0, 1.0, 0.5)).length() < 1e-9);
+        assert!((side.u - 0.75).abs() < 1e-9, "u = {}", side.u);
+        assert!((side.v - 0.5).abs() < 1e-9, "v = {}", side.v);
+
+        // Base cap from below, halfway out along +X
+        let cap = first_hit(&Ray::new(
+            Point3::new(0.5, -5.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            0.0,
+        ));
+        assert!(cap.p.y().abs() < 1e-9);
+        assert!(cap.u.abs() < 1e-9, "u = {}", cap.u);
+        assert!((cap.v - 0.5).abs() < 1e-9, "v = {}", cap.v);
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::color::Color;
+use crate::common;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::{Isotropic, Material};
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// A volume of constant density (fog, smoke) filling a convex boundary shape.
+// A ray travelling through it scatters at a random distance, the denser the sooner.
+pub struct ConstantMedium {
+    boundary: Box<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Box<dyn Hittable>, density: f64, albedo: Color) -> Self {
+        ConstantMedium::with_phase_function(boundary, density, Arc::new(Isotropic::new(albedo)))
+    }
+
+    pub fn with_phase_function(
+        boundary: Box<dyn Hittable>,
+        density: f64,
+        phase_function: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        // Find where the ray enters and leaves the boundary, on the whole line
+        let mut rec1 = HitRecord::new();
+        let mut rec2 = HitRecord::new();
+
+        if !self
+            .boundary
+            .hit(r, -common::INFINITY, common::INFINITY, &mut rec1)
+        {
+            return false;
+        }
+
+        if !self
+            .boundary
+            .hit(r, rec1.t + 0.0001, common::INFINITY, &mut rec2)
+        {
+            return false;
+        }
+
+        // Clip the segment inside the volume to the requested range
+        rec1.t = rec1.t.max(t_min);
+        rec2.t = rec2.t.min(t_max);
+        if rec1.t >= rec2.t {
+            return false;
+        }
+        rec1.t = rec1.t.max(0.0);
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
+        let hit_distance = self.neg_inv_density * common::random_double().ln();
+
+        // Passed all the way through without scattering
+        if hit_distance > distance_inside_boundary {
+            return false;
+        }
+
+        rec.t = rec1.t + hit_distance / ray_length;
+        rec.p = r.at(rec.t);
+
+        // The normal and face are meaningless inside a volume
+        rec.normal = Vec3::new(1.0, 0.0, 0.0);
+        rec.tangent = Vec3::default();
+        rec.bitangent = Vec3::default();
+        rec.front_face = true;
+        rec.mat = Some(self.phase_function.clone());
+
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::Cube;
+    use crate::vec3::Point3;
+
+    #[test]
+    fn dense_medium_scatters_nearly_every_ray_that_enters() {
+        common::seed_rng(1);
+        let boundary = Cube::centered(
+            Point3::new(0.0, 0.0, 0.0),
+            2.0,
+            Arc::new(Isotropic::new(Color::new(1.0, 1.0, 1.0))),
+        );
+        let fog = ConstantMedium::new(Box::new(boundary), 100.0, Color::new(1.0, 1.0, 1.0));
+
+        let mut scattered = 0;
+        for _ in 0..1000 {
+            let target = Point3::new(
+                common::random_double_range(-0.9, 0.9),
+                common::random_double_range(-0.9, 0.9),
+                0.0,
+            );
+            let origin = Point3::new(0.0, 0.0, 5.0);
+            let r = Ray::new(origin, target - origin, 0.0);
+            let mut rec = HitRecord::new();
+            if fog.hit(&r, 0.001, f64::INFINITY, &mut rec) {
+                scattered += 1;
+                // Inside the box, not far past the side it came in through
+                assert!(rec.p.z() <= 1.0 && rec.p.z() > 0.5, "{}", rec.p);
+            }
+        }
+        assert!(
+            scattered >= 990,
+            "only {} of 1000 rays scattered",
+            scattered
+        );
+    }
+}
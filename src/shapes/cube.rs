@@ -1,12 +1,14 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::{
+    aabb::Aabb,
     hittable::{HitRecord, Hittable},
     hittable_list::HittableList,
+    mat3::Mat3,
     material::Material,
     ray::Ray,
-    shapes::Square,
-    vec3::{Point3, Vec3},
+    shapes::{Rectangle, Rotate, Translate},
+    vec3::{self, Point3, Vec3},
 };
 
 pub struct Cube {
@@ -14,79 +16,14 @@ pub struct Cube {
 }
 
 impl Cube {
-    pub fn new(p_min: Point3, p_max: Point3, material: Rc<dyn Material>) -> Self {
-        let mut sides = HittableList::new();
-
-        // Calculate dimensions dynamically
-        let width = p_max.x() - p_min.x();
-        let height = p_max.y() - p_min.y();
-        let depth = p_max.z() - p_min.z();
-
-        // Calculate center positions for each face
-        let center_x = (p_min.x() + p_max.x()) / 2.0;
-        let center_y = (p_min.y() + p_max.y()) / 2.0;
-        let center_z = (p_min.z() + p_max.z()) / 2.0;
-
-        // Front face (positive Z)
-        let front_face = Box::new(Square::new(
-            Point3::new(center_x, center_y, p_max.z()),
-            Vec3::new(0.0, 0.0, 1.0),
-            width.max(height), // Use the larger dimension for the square
-            material.clone(),
-        ));
-
-        // Back face (negative Z)
-        let back_face = Box::new(Square::new(
-            Point3::new(center_x, center_y, p_min.z()),
-            Vec3::new(0.0, 0.0, -1.0), // Note: flipped normal
-            width.max(height),
-            material.clone(),
-        ));
-
-        // Top face (positive Y)
-        let top_face = Box::new(Square::new(
-            Point3::new(center_x, p_max.y(), center_z),
-            Vec3::new(0.0, 1.0, 0.0),
-            width.max(depth),
-            material.clone(),
-        ));
-
-        // Bottom face (negative Y)
-        let bottom_face = Box::new(Square::new(
-            Point3::new(center_x, p_min.y(), center_z),
-            Vec3::new(0.0, -1.0, 0.0), // Note: flipped normal
-            width.max(depth),
-            material.clone(),
-        ));
-
-        // Right face (positive X)
-        let right_face = Box::new(Square::new(
-            Point3::new(p_max.x(), center_y, center_z),
-            Vec3::new(1.0, 0.0, 0.0),
-            height.max(depth),
-            material.clone(),
-        ));
-
-        // Left face (negative X)
-        let left_face = Box::new(Square::new(
-            Point3::new(p_min.x(), center_y, center_z),
-            Vec3::new(-1.0, 0.0, 0.0), // Note: flipped normal
-            height.max(depth),
-            material,
-        ));
-
-        sides.add(front_face);
-        sides.add(back_face);
-        sides.add(top_face);
-        sides.add(bottom_face);
-        sides.add(right_face);
-        sides.add(left_face);
-
-        Cube { sides }
+    pub fn new(p_min: Point3, p_max: Point3, material: Arc<dyn Material>) -> Self {
+        Cube {
+            sides: box_sides(p_min, p_max, material),
+        }
     }
 
     // Helper constructors for common cube types
-    pub fn centered(center: Point3, size: f64, material: Rc<dyn Material>) -> Self {
+    pub fn centered(center: Point3, size: f64, material: Arc<dyn Material>) -> Self {
         let half_size = size / 2.0;
         let p_min = Point3::new(
             center.x() - half_size,
@@ -107,94 +44,202 @@ impl Cube {
         width: f64,
         height: f64,
         depth: f64,
-        material: Rc<dyn Material>,
+        material: Arc<dyn Material>,
     ) -> Self {
         let p_min = corner;
         let p_max = Point3::new(corner.x() + width, corner.y() + height, corner.z() + depth);
 
         Cube::new(p_min, p_max, material)
     }
+
+    // A cube turned by `rotation` (a rotation matrix) around its own center, for tilted
+    // boxes without wrapping them by hand. Its bounding box is the box around the turned
+    // corners, so it grows as the cube turns away from the axes.
+    pub fn rotated(
+        center: Point3,
+        size: f64,
+        rotation: Mat3,
+        material: Arc<dyn Material>,
+    ) -> Box<dyn Hittable> {
+        let cube = Cube::centered(Point3::new(0.0, 0.0, 0.0), size, material);
+        let turned = Rotate::from_matrix(Box::new(cube), rotation);
+        Box::new(Translate::new(Box::new(turned), center))
+    }
 }
 
 impl Hittable for Cube {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
         self.sides.hit(r, t_min, t_max, rec)
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.sides.bounding_box()
+    }
 }
 
-// Even more advanced: Rectangular Box (different dimensions for each axis)
+// Rectangular Box (different dimensions for each axis), built the same way as Cube
 pub struct RectangularBox {
     pub sides: HittableList,
 }
 
 impl RectangularBox {
-    pub fn new(p_min: Point3, p_max: Point3, material: Rc<dyn Material>) -> Self {
-        let mut sides = HittableList::new();
-
-        // Calculate actual dimensions
-        let width = (p_max.x() - p_min.x()).abs();
-        let height = (p_max.y() - p_min.y()).abs();
-        let depth = (p_max.z() - p_min.z()).abs();
-
-        // Calculate centers
-        let center_x = (p_min.x() + p_max.x()) / 2.0;
-        let center_y = (p_min.y() + p_max.y()) / 2.0;
-        let center_z = (p_min.z() + p_max.z()) / 2.0;
-
-        // Create faces with exact dimensions (requires rectangular square support)
-        // For now, we'll use the maximum dimension approach
-
-        // Front and back faces (YZ plane)
-        sides.add(Box::new(Square::new(
-            Point3::new(center_x, center_y, p_max.z()),
-            Vec3::new(0.0, 0.0, 1.0),
-            width.max(height),
-            material.clone(),
-        )));
+    pub fn new(p_min: Point3, p_max: Point3, material: Arc<dyn Material>) -> Self {
+        RectangularBox {
+            sides: box_sides(p_min, p_max, material),
+        }
+    }
+}
 
-        sides.add(Box::new(Square::new(
-            Point3::new(center_x, center_y, p_min.z()),
-            Vec3::new(0.0, 0.0, -1.0),
-            width.max(height),
-            material.clone(),
-        )));
+impl Hittable for RectangularBox {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        self.sides.hit(r, t_min, t_max, rec)
+    }
 
-        // Top and bottom faces (XZ plane)
-        sides.add(Box::new(Square::new(
-            Point3::new(center_x, p_max.y(), center_z),
-            Vec3::new(0.0, 1.0, 0.0),
-            width.max(depth),
-            material.clone(),
-        )));
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.sides.bounding_box()
+    }
+}
 
-        sides.add(Box::new(Square::new(
-            Point3::new(center_x, p_min.y(), center_z),
-            Vec3::new(0.0, -1.0, 0.0),
-            width.max(depth),
+// The six faces of the box between two opposite corners, each exactly as large as the box
+// side it covers. The axis order makes every normal point outwards and lays out the UVs
+// the same way on every face: seen from outside, u runs left to right and v bottom to top
+// (on the top and bottom faces "up" is towards -Z and +Z respectively), so a texture is
+// neither mirrored nor rotated from one face to the next.
+fn box_sides(p0: Point3, p1: Point3, material: Arc<dyn Material>) -> HittableList {
+    let p_min = vec3::min(p0, p1);
+    let p_max = vec3::max(p0, p1);
+    let center = 0.5 * (p_min + p_max);
+    let half = 0.5 * (p_max - p_min);
+
+    let x = Vec3::new(1.0, 0.0, 0.0);
+    let y = Vec3::new(0.0, 1.0, 0.0);
+    let z = Vec3::new(0.0, 0.0, 1.0);
+
+    // The face whose edges run along the given (signed, unit) axes; it sits on the side of
+    // the box that cross(u_axis, v_axis) points to and spans the box along both axes
+    let mut sides = HittableList::new();
+    let mut face = |u_axis: Vec3, v_axis: Vec3| {
+        let normal = vec3::cross(u_axis, v_axis);
+        let extent = |axis: Vec3| vec3::dot(half, axis).abs();
+        sides.add(Box::new(Rectangle::new(
+            center + extent(normal) * normal,
+            u_axis,
+            v_axis,
+            extent(u_axis),
+            extent(v_axis),
             material.clone(),
         )));
+    };
+
+    // Front and back
+    face(x, y);
+    face(-x, y);
+    // Right and left
+    face(-z, y);
+    face(z, y);
+    // Top and bottom
+    face(x, -z);
+    face(x, z);
+
+    sides
+}
 
-        // Left and right faces (XY plane)
-        sides.add(Box::new(Square::new(
-            Point3::new(p_max.x(), center_y, center_z),
-            Vec3::new(1.0, 0.0, 0.0),
-            height.max(depth),
-            material.clone(),
-        )));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn box_faces_reach_exactly_to_the_corners() {
+        let size = Vec3::new(1.0, 2.0, 3.0);
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let block = RectangularBox::new(Point3::new(0.0, 0.0, 0.0), size, material);
+
+        // Probe each face straight on, just inside and just outside each of its corners
+        for axis in 0..3 {
+            let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+            for side in [0.0, 1.0] {
+                let mut normal = [0.0; 3];
+                normal[axis] = 2.0 * side - 1.0;
+                let normal = Vec3::from(normal);
+
+                for (ca, cb) in [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)] {
+                    for (margin, should_hit) in [(0.01, true), (-0.01, false)] {
+                        // From the corner `margin` in towards the middle of the face
+                        let mut p = [0.0; 3];
+                        p[axis] = side * size[axis];
+                        p[a] = ca * size[a] + (1.0 - 2.0 * ca) * margin;
+                        p[b] = cb * size[b] + (1.0 - 2.0 * cb) * margin;
+                        let p = Point3::from(p);
+
+                        let r = Ray::new(p + 10.0 * normal, -normal, 0.0);
+                        let mut rec = HitRecord::new();
+                        let hit = block.hit(&r, 0.001, f64::INFINITY, &mut rec);
+                        assert_eq!(hit, should_hit, "probe at {}", p);
+                        if hit {
+                            assert!((rec.p - p).length() < 1e-9, "{} hit at {}", p, rec.p);
+                            assert!((rec.normal - normal).length() < 1e-9);
+                        }
+                    }
+                }
+            }
+        }
+    }
 
-        sides.add(Box::new(Square::new(
-            Point3::new(p_min.x(), center_y, center_z),
-            Vec3::new(-1.0, 0.0, 0.0),
-            height.max(depth),
+    #[test]
+    fn hit_near_a_corner_is_on_the_face_it_comes_in_through() {
+        // 1 x 3 x 1, from the origin
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let block = RectangularBox::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 3.0, 1.0),
             material,
-        )));
-
-        RectangularBox { sides }
+        );
+        let hit = |origin: Point3, target: Point3| {
+            let mut rec = HitRecord::new();
+            assert!(block.hit(
+                &Ray::new(origin, target - origin, 0.0),
+                0.001,
+                f64::INFINITY,
+                &mut rec
+            ));
+            rec
+        };
+
+        // Just inside the top right corner of the front face, from the front at an angle
+        // that would have reached the right face had the front been any smaller
+        let target = Point3::new(0.999, 2.999, 1.0);
+        let rec = hit(Point3::new(3.0, 3.5, 3.0), target);
+        assert!((rec.p - target).length() < 1e-9);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-12);
+        // Seen from the front u runs left to right and v bottom to top
+        assert!((rec.u - 0.999).abs() < 1e-9 && (rec.v - 2.999 / 3.0).abs() < 1e-9);
+
+        // And the same corner of the right face, from the right
+        let target = Point3::new(1.0, 2.999, 0.001);
+        let rec = hit(Point3::new(3.0, 3.5, -2.0), target);
+        assert!((rec.p - target).length() < 1e-9);
+        assert!((rec.normal - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-12);
+        assert!((rec.u - 0.999).abs() < 1e-9 && (rec.v - 2.999 / 3.0).abs() < 1e-9);
     }
-}
 
-impl Hittable for RectangularBox {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        self.sides.hit(r, t_min, t_max, rec)
+    #[test]
+    fn cube_turned_45_degrees_about_y_has_a_wider_box() {
+        let gray = || Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let center = Point3::new(1.0, 2.0, 3.0);
+        let straight = Cube::rotated(center, 2.0, Mat3::identity(), gray());
+        let turned = Cube::rotated(center, 2.0, Mat3::rotation_y(45f64.to_radians()), gray());
+        let (straight, turned) = (
+            straight.bounding_box().unwrap(),
+            turned.bounding_box().unwrap(),
+        );
+
+        // Corner to corner across the top is 2 * sqrt(2) wide, and the height is unchanged
+        let width = |b: &Aabb| b.max().x() - b.min().x();
+        assert!((width(&straight) - 2.0).abs() < 1e-3);
+        assert!((width(&turned) - 2.0 * f64::sqrt(2.0)).abs() < 1e-3);
+        assert!((turned.max().y() - straight.max().y()).abs() < 1e-3);
+        assert!((turned.min().x() + turned.max().x() - 2.0).abs() < 1e-9);
     }
 }
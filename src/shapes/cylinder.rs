@@ -1,6 +1,8 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::{
+    aabb::{self, Aabb},
+    common,
     hittable::Hittable,
     material::Material,
     vec3::{self, Point3, Vec3},
@@ -11,28 +13,51 @@ pub struct Disk {
     center: Point3,
     normal: Vec3,
     radius: f64,
-    material: Rc<dyn Material>,
+    inner_radius: f64, // Radius of the hole in the middle, 0 for a full disk
+    material: Arc<dyn Material>,
 }
 
-// A finite cylinder with two caps
+// A finite cylinder with two caps, or an open tube without them
 pub struct Cylinder {
     pub base_center: Point3, // Center of the base cap
     pub axis: Vec3,          // Normalized axis vector (direction from base to top)
     pub radius: f64,
     pub height: f64,
-    pub material: Rc<dyn Material>,
+    pub capped: bool, // false leaves both ends open, like a pipe
+    pub material: Arc<dyn Material>,
 }
 
 impl Cylinder {
-    pub fn new(base_center: Point3, axis: Vec3, radius: f64, height: f64, material: Rc<dyn Material>) -> Self {
+    pub fn new(
+        base_center: Point3,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
         Self {
             base_center,
             axis: vec3::unit_vector(axis),
             radius,
             height,
+            capped: true,
             material,
         }
     }
+
+    // The tube alone: rays going in through either end only hit its inside wall
+    pub fn open(
+        base_center: Point3,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            capped: false,
+            ..Self::new(base_center, axis, radius, height, material)
+        }
+    }
 }
 
 impl Hittable for Cylinder {
@@ -57,10 +82,12 @@ impl Hittable for Cylinder {
         let mut hit_anything = false;
         let mut closest_so_far = t_max;
 
-        // Tube intersection
+        // Tube intersection. Rays parallel to the axis (a == 0) never cross the wall.
         let discriminant = half_b * half_b - a * c;
-        if discriminant >= 0.0 {
+        if a > 1e-12 && discriminant >= 0.0 {
             let sqrt_d = discriminant.sqrt();
+            // The near root first; the far one only counts if the near one is out of range
+            // or beyond the ends of the tube (the ray then hits the inside of the wall)
             for &sign in &[-1.0, 1.0] {
                 let t = (-half_b + sign * sqrt_d) / a;
                 if t < t_min || t > closest_so_far {
@@ -76,12 +103,20 @@ impl Hittable for Cylinder {
                 rec.p = p;
                 let outward_normal = vec3::unit_vector(p - self.base_center - axis * v);
                 rec.set_face_normal(r, outward_normal);
+                // Around the axis, and up from the base: a label wraps around once
+                rec.u = angle_u(self.axis, outward_normal);
+                rec.v = v / self.height;
                 rec.mat = Some(self.material.clone());
                 hit_anything = true;
+                break;
             }
         }
-        // Cap intersection (bottom and top)
-        for &(cap_offset, cap_normal_sign) in &[(0.0, 1.0), (self.height, -1.0)] {
+        if !self.capped {
+            return hit_anything;
+        }
+        // Cap intersection (bottom and top), only where closer than the tube hit.
+        // The bottom cap faces away from the axis, the top one along it.
+        for &(cap_offset, cap_normal_sign) in &[(0.0, -1.0), (self.height, 1.0)] {
             let cap_center = self.base_center + axis * cap_offset;
             let denom = dot(r.direction(), axis);
             if denom.abs() > 1e-8 {
@@ -94,6 +129,9 @@ impl Hittable for Cylinder {
                         rec.p = p;
                         let outward_normal = axis * cap_normal_sign;
                         rec.set_face_normal(r, outward_normal);
+                        // Polar: around the axis like the tube, and out from the center
+                        rec.u = angle_u(self.axis, p - cap_center);
+                        rec.v = (p - cap_center).length() / self.radius;
                         rec.mat = Some(self.material.clone());
                         hit_anything = true;
                     }
@@ -102,23 +140,75 @@ impl Hittable for Cylinder {
         }
         hit_anything
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let top_center = self.base_center + self.axis * self.height;
+        // The tube is the sweep of the base circle, so enclosing both cap circles is enough
+        Some(aabb::surrounding_box(
+            cap_box(self.base_center, self.axis, self.radius),
+            cap_box(top_center, self.axis, self.radius),
+        ))
+    }
+}
+
+// Angle of `offset` (from the axis, at right angles to it) around the unit `axis`, mapped
+// to [0, 1]. It starts from +X (or +Z for an axis along X) and turns towards +Z for an
+// upright axis, like a sphere's u.
+pub(crate) fn angle_u(axis: Vec3, offset: Vec3) -> f64 {
+    let x_axis = Vec3::new(1.0, 0.0, 0.0);
+    let start = if vec3::dot(x_axis, axis).abs() > 0.9 {
+        Vec3::new(0.0, 0.0, 1.0)
+    } else {
+        x_axis
+    };
+    let reference = vec3::unit_vector(start - axis * vec3::dot(start, axis));
+    let side = vec3::cross(reference, axis);
+
+    let mut phi = f64::atan2(vec3::dot(offset, side), vec3::dot(offset, reference));
+    if phi < 0.0 {
+        phi += 2.0 * common::PI;
+    }
+    phi / (2.0 * common::PI)
+}
+
+// Box around a circle of the given radius lying in the plane with the given unit normal.
+// Along each world axis the circle extends radius * sin(angle between that axis and the normal)
+pub(crate) fn cap_box(center: Point3, normal: Vec3, radius: f64) -> Aabb {
+    let extent = |n: f64| radius * f64::sqrt(f64::max(0.0, 1.0 - n * n)) + 0.0001;
+    let e = Vec3::new(extent(normal.x()), extent(normal.y()), extent(normal.z()));
+    Aabb::new(center - e, center + e)
 }
 
 impl Disk {
-    pub fn new(center: Point3, normal: Vec3, radius: f64, mat: Rc<dyn Material>) -> Self {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, mat: Arc<dyn Material>) -> Self {
         Self {
             center,
             normal,
             radius,
+            inner_radius: 0.0,
             material: mat,
         }
     }
 
-    pub fn vertical(center: Point3, radius: f64, mat: Rc<dyn Material>) -> Self {
+    // A flat ring (washer) between inner_radius and radius, e.g. for planetary rings
+    pub fn ring(
+        center: Point3,
+        normal: Vec3,
+        inner_radius: f64,
+        radius: f64,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            inner_radius,
+            ..Disk::new(center, normal, radius, mat)
+        }
+    }
+
+    pub fn vertical(center: Point3, radius: f64, mat: Arc<dyn Material>) -> Self {
         Disk::new(center, Vec3::new(0.0, 0.0, 1.0), radius, mat)
     }
 
-    pub fn horizontal(center: Point3, radius: f64, mat: Rc<dyn Material>) -> Self {
+    pub fn horizontal(center: Point3, radius: f64, mat: Arc<dyn Material>) -> Self {
         Disk::new(center, Vec3::new(0.0, 1.0, 0.0), radius, mat)
     }
 }
@@ -150,18 +240,193 @@ impl Hittable for Disk {
         // check where does the ray hit
         let hit_point = r.at(t);
 
-        // check if the hit is inside the radius
-        if (hit_point - self.center).length_squared() >= self.radius * self.radius {
+        // check if the hit is inside the radius, and outside the hole of a ring
+        let distance_squared = (hit_point - self.center).length_squared();
+        if distance_squared >= self.radius * self.radius
+            || distance_squared < self.inner_radius * self.inner_radius
+        {
             return false;
         }
 
         rec.t = t;
         rec.p = hit_point;
         // Always set the normal to oppose the ray direction
-        let outward_normal = if intersection < 0.0 { self.normal } else { -self.normal };
+        let outward_normal = if intersection < 0.0 {
+            self.normal
+        } else {
+            -self.normal
+        };
         rec.set_face_normal(r, outward_normal);
+        // Polar coordinates, like the caps of a Cylinder
+        rec.u = angle_u(vec3::unit_vector(self.normal), hit_point - self.center);
+        rec.v = distance_squared.sqrt() / self.radius;
         rec.mat = Some(self.material.clone());
 
         true
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(cap_box(
+            self.center,
+            vec3::unit_vector(self.normal),
+            self.radius,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::hittable::HitRecord;
+    use crate::material::Lambertian;
+    use crate::ray::Ray;
+
+    fn gray() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    // Radius 1 from y = 0 to y = 2
+    fn upright(capped: bool) -> Cylinder {
+        let (base, axis) = (Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        if capped {
+            Cylinder::new(base, axis, 1.0, 2.0, gray())
+        } else {
+            Cylinder::open(base, axis, 1.0, 2.0, gray())
+        }
+    }
+
+    fn first_hit(object: &dyn Hittable, r: &Ray) -> Option<HitRecord> {
+        let mut rec = HitRecord::new();
+        object.hit(r, 0.001, f64::INFINITY, &mut rec).then_some(rec)
+    }
+
+    #[test]
+    fn open_cylinder_lets_rays_in_through_the_ends() {
+        // Straight down the axis: the top cap of a closed one, nothing of an open one
+        let down = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let rec = first_hit(&upright(true), &down).unwrap();
+        assert!((rec.p.y() - 2.0).abs() < 1e-9);
+        assert!(first_hit(&upright(false), &down).is_none());
+
+        // Slanted in through the top opening, on to the inside of the wall
+        let slanted = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.3, -1.0, 0.0), 0.0);
+        let rec = first_hit(&upright(false), &slanted).unwrap();
+        assert!((rec.p.x() - 1.0).abs() < 1e-9);
+        assert!((rec.p.y() - (5.0 - 1.0 / 0.3)).abs() < 1e-9);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn tilted_cylinder_box_encloses_both_caps() {
+        // Leaning 45 degrees towards +X, from the origin to (1, 1, 0). The caps lean the
+        // same way, so they reach sin(45) sideways in X and Y and the full radius in Z.
+        let cylinder = Cylinder::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            1.0,
+            f64::sqrt(2.0),
+            gray(),
+        );
+        let bbox = cylinder.bounding_box().unwrap();
+        let s = f64::sqrt(0.5);
+        assert!((bbox.min() - Point3::new(-s, -s, -1.0)).length() < 1e-3);
+        assert!((bbox.max() - Point3::new(1.0 + s, 1.0 + s, 1.0)).length() < 1e-3);
+
+        // Every point on the rim of both caps is inside it
+        for i in 0..32 {
+            let angle = 2.0 * common::PI * i as f64 / 32.0;
+            let across =
+                Vec3::new(-s, s, 0.0) * angle.cos() + Vec3::new(0.0, 0.0, 1.0) * angle.sin();
+            for center in [Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 0.0)] {
+                let p = center + across;
+                assert!(p.x() >= bbox.min().x() && p.x() <= bbox.max().x());
+                assert!(p.y() >= bbox.min().y() && p.y() <= bbox.max().y());
+                assert!(p.z() >= bbox.min().z() && p.z() <= bbox.max().z());
+            }
+        }
+    }
+
+    #[test]
+    fn closer_of_the_tube_and_a_cap_is_recorded() {
+        // In through the top cap at (0.9, 2, 0), then on to the inside of the tube
+        let r = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.3, -1.0, 0.0), 0.0);
+        let rec = first_hit(&upright(true), &r).unwrap();
+        assert!((rec.p - Point3::new(0.9, 2.0, 0.0)).length() < 1e-9);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+
+        // In through the side at (-1, 1, 0), then out through the bottom cap at the center
+        let r = Ray::new(Point3::new(-2.0, 2.0, 0.0), Vec3::new(1.0, -1.0, 0.0), 0.0);
+        let rec = first_hit(&upright(true), &r).unwrap();
+        assert!((rec.p - Point3::new(-1.0, 1.0, 0.0)).length() < 1e-9);
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn ray_through_the_hole_of_a_ring_misses() {
+        // Flat in the XZ plane, from radius 1 out to 2
+        let ring = Disk::ring(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            2.0,
+            gray(),
+        );
+        let down = |x: f64| Ray::new(Point3::new(x, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+
+        assert!(first_hit(&ring, &down(0.0)).is_none());
+        assert!(first_hit(&ring, &down(0.9)).is_none());
+        let rec = first_hit(&ring, &down(1.5)).unwrap();
+        assert!((rec.p - Point3::new(1.5, 0.0, 0.0)).length() < 1e-9);
+        assert!(first_hit(&ring, &down(2.1)).is_none());
+    }
+
+    #[test]
+    fn tube_and_cap_uvs_start_from_the_reference_angle() {
+        let cylinder = upright(true);
+        let uv = |origin: Point3, direction: Vec3| {
+            let rec = first_hit(&cylinder, &Ray::new(origin, direction, 0.0)).unwrap();
+            (rec.u, rec.v)
+        };
+
+        // Halfway up the tube at +X, then a quarter of the way round at +Z
+        let (u, v) = uv(Point3::new(5.0, 1.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(u.abs() < 1e-9 && (v - 0.5).abs() < 1e-9, "({}, {})", u, v);
+        let (u, v) = uv(Point3::new(0.0, 1.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(
+            (u - 0.25).abs() < 1e-9 && (v - 0.5).abs() < 1e-9,
+            "({}, {})",
+            u,
+            v
+        );
+
+        // Halfway out from the center of the top cap, towards +X
+        let (u, v) = uv(Point3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(u.abs() < 1e-9 && (v - 0.5).abs() < 1e-9, "({}, {})", u, v);
+    }
+
+    #[test]
+    fn ring_sets_its_own_texture_coordinates() {
+        // Ring from radius 1 to 2 lying flat at y = 5, above the cylinder's top cap
+        let ring = Disk::ring(
+            Point3::new(0.0, 5.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            1.0,
+            2.0,
+            gray(),
+        );
+        let floor = Disk::horizontal(Point3::new(0.0, 0.0, 0.0), 10.0, gray());
+        // Straight down at x = 1.5, z = 0 and at x = 0, z = 1.5: the farther floor is hit
+        // first and leaves its coordinates in the record
+        for (x, z, u) in [(1.5, 0.0, 0.0), (0.0, 1.5, 0.25)] {
+            let down = Ray::new(Point3::new(x, 10.0, z), Vec3::new(0.0, -1.0, 0.0), 0.0);
+            let mut rec = HitRecord::new();
+            assert!(floor.hit(&down, 0.001, f64::INFINITY, &mut rec));
+            assert!(ring.hit(&down, 0.001, rec.t, &mut rec));
+            assert!((rec.p.y() - 5.0).abs() < 1e-9);
+            assert!((rec.u - u).abs() < 1e-9, "u = {}", rec.u);
+            // v runs from the center to the outer edge, through the hole
+            assert!((rec.v - 0.75).abs() < 1e-9, "v = {}", rec.v);
+        }
+    }
 }
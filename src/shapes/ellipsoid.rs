@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::shapes::Sphere;
+use crate::vec3::{self, Point3, Vec3};
+
+// A sphere stretched by a different radius along each of the X, Y and Z axes
+pub struct Ellipsoid {
+    center: Point3,
+    radii: Vec3,
+    inv_radii: Vec3,
+    mat: Arc<dyn Material>,
+}
+
+impl Ellipsoid {
+    pub fn new(center: Point3, radii: Vec3, material: Arc<dyn Material>) -> Self {
+        Ellipsoid {
+            center,
+            radii,
+            inv_radii: Vec3::new(1.0 / radii.x(), 1.0 / radii.y(), 1.0 / radii.z()),
+            mat: material,
+        }
+    }
+}
+
+impl Hittable for Ellipsoid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        // Dividing by the radii turns the ellipsoid into the unit sphere at the origin. The
+        // ray stays a straight line with the same t along it, so the sphere test applies.
+        let oc = (r.origin() - self.center) * self.inv_radii;
+        let direction = r.direction() * self.inv_radii;
+        let a = direction.length_squared();
+        let half_b = vec3::dot(oc, direction);
+        let c = oc.length_squared() - 1.0;
+
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return false;
+        }
+
+        let sqrt_d = f64::sqrt(discriminant);
+        let mut root = (-half_b - sqrt_d) / a;
+        if root <= t_min || t_max <= root {
+            root = (-half_b + sqrt_d) / a;
+            if root <= t_min || t_max <= root {
+                return false;
+            }
+        }
+
+        rec.t = root;
+        rec.p = r.at(root);
+
+        // Normals transform with the inverse transpose of the scaling, which divides by the
+        // radii once more: the unit sphere normal is (p - center) / radii
+        let local_p = (rec.p - self.center) * self.inv_radii;
+        let outward_normal = vec3::unit_vector(local_p * self.inv_radii);
+        rec.set_face_normal(r, outward_normal);
+        (rec.u, rec.v) = Sphere::get_sphere_uv(local_p);
+        rec.mat = Some(self.mat.clone());
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            self.center - self.radii,
+            self.center + self.radii,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn ellipsoid_reaches_further_along_its_longer_radius() {
+        let ellipsoid = Ellipsoid::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 1.0, 1.0),
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        let first_hit = |origin: Point3| {
+            let r = Ray::new(origin, -origin, 0.0);
+            let mut rec = HitRecord::new();
+            assert!(ellipsoid.hit(&r, 0.001, f64::INFINITY, &mut rec));
+            rec
+        };
+
+        // From 5 away along X and along Y, towards the center
+        let along_x = first_hit(Point3::new(5.0, 0.0, 0.0));
+        let along_y = first_hit(Point3::new(0.0, 5.0, 0.0));
+        assert!((along_x.p - Point3::new(2.0, 0.0, 0.0)).length() < 1e-9);
+        assert!((along_y.p - Point3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+        assert!(along_x.t < along_y.t);
+        assert!((along_x.normal - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+    }
+}
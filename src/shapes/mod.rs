@@ -1,9 +1,61 @@
+pub mod capsule;
+pub mod cone;
+pub mod constant_medium;
 pub mod cube;
 pub mod cylinder;
+pub mod ellipsoid;
+pub mod moving_sphere;
+pub mod plane;
+pub mod rectangle;
 pub mod sphere;
 pub mod square;
+pub mod transform;
+pub mod triangle;
+pub mod visibility;
 
-pub use cube::Cube;
-pub use cylinder::Disk;
+pub use capsule::Capsule;
+pub use cone::Cone;
+pub use constant_medium::ConstantMedium;
+pub use cube::{Cube, RectangularBox};
+pub use cylinder::{Cylinder, Disk};
+pub use ellipsoid::Ellipsoid;
+pub use moving_sphere::MovingSphere;
+pub use plane::Plane;
+pub use rectangle::Rectangle;
 pub use sphere::Sphere;
 pub use square::Square;
+pub use transform::{Rotate, RotateY, Translate};
+pub use triangle::Triangle;
+pub use visibility::Visibility;
+
+/// Everything scene code needs to put objects together: the shapes, the materials, and the
+/// types their constructors take.
+///
+/// ```
+/// use ray_tracing::shapes::prelude::*;
+/// use std::sync::Arc;
+///
+/// let ground: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+/// let mut world = HittableList::new();
+/// world.add(Box::new(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), ground)));
+/// world.add(Box::new(Sphere::new(
+///     Point3::new(0.0, 1.0, 0.0),
+///     1.0,
+///     Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.1)),
+/// )));
+/// assert_eq!(world.len(), 2);
+/// ```
+pub mod prelude {
+    pub use super::{
+        Capsule, Cone, ConstantMedium, Cube, Cylinder, Disk, Ellipsoid, MovingSphere, Plane,
+        Rectangle, RectangularBox, Rotate, RotateY, Sphere, Square, Translate, Triangle,
+        Visibility,
+    };
+    pub use crate::color::Color;
+    pub use crate::hittable_list::HittableList;
+    pub use crate::material::{
+        BumpMapped, Dielectric, DiffuseLight, Glossy, Isotropic, Lambertian, Material, Metal,
+        NormalMapped, Phong, SpotLight,
+    };
+    pub use crate::vec3::{Point3, Vec3};
+}
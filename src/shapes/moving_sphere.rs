@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use crate::aabb::{self, Aabb};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::shapes::Sphere;
+use crate::vec3::{self, Point3, Vec3};
+
+// A sphere moving in a straight line from center0 at time0 to center1 at time1
+pub struct MovingSphere {
+    center0: Point3,
+    center1: Point3,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    mat: Arc<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: Point3,
+        center1: Point3,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            mat: material,
+        }
+    }
+
+    pub fn center(&self, time: f64) -> Point3 {
+        self.center0
+            + ((time - self.time0) / (self.time1 - self.time0)) * (self.center1 - self.center0)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        // same as Sphere, with the center taken at the moment of the ray
+        let center = self.center(r.time());
+        let oc = r.origin() - center;
+        let a = vec3::dot(r.direction(), r.direction());
+        let half_b = vec3::dot(oc, r.direction());
+        let c = vec3::dot(oc, oc) - self.radius * self.radius;
+
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return false;
+        }
+
+        let sqrt_d = f64::sqrt(discriminant);
+
+        // find the nearest root that lies in the nearest range
+        let mut root = (-half_b - sqrt_d) / a;
+        if root <= t_min || t_max <= root {
+            root = (-half_b + sqrt_d) / a;
+            if root <= t_min || t_max <= root {
+                return false;
+            }
+        }
+
+        rec.t = root;
+        rec.p = r.at(rec.t);
+        let outwards_normal = (rec.p - center) / self.radius;
+        rec.set_face_normal(r, outwards_normal);
+        (rec.u, rec.v) = Sphere::get_sphere_uv(outwards_normal);
+        (rec.tangent, rec.bitangent) = Sphere::get_sphere_tangents(outwards_normal);
+        rec.mat = Some(self.mat.clone());
+        true
+    }
+
+    // Box around the whole path travelled during the shutter interval
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        let box0 = Aabb::new(self.center(self.time0) - r, self.center(self.time0) + r);
+        let box1 = Aabb::new(self.center(self.time1) - r, self.center(self.time1) + r);
+        Some(aabb::surrounding_box(box0, box1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn center_at_the_middle_of_the_shutter_is_halfway() {
+        let sphere = MovingSphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 4.0, -6.0),
+            1.0,
+            3.0,
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        assert!((sphere.center(2.0) - Point3::new(1.0, 2.0, -3.0)).length() < 1e-12);
+
+        // And a ray at that time finds it there
+        let r = Ray::new(Point3::new(1.0, 2.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 2.0);
+        let mut rec = HitRecord::new();
+        assert!(sphere.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.p - Point3::new(1.0, 2.0, -2.5)).length() < 1e-9);
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::onb::OrthoNormalBasis;
+use crate::ray::Ray;
+use crate::vec3::{self, Point3, Vec3};
+
+// An infinite plane through `point`, unlike Square it has no bounds at all. Its texture
+// coordinates measure distance from `point` along two axes in the plane and wrap every
+// unit, so an image texture tiles across it.
+pub struct Plane {
+    point: Point3,
+    normal: Vec3,
+    u_axis: Vec3,
+    v_axis: Vec3,
+    mat: Arc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, material: Arc<dyn Material>) -> Self {
+        let basis = OrthoNormalBasis::build_from_w(normal);
+        Plane {
+            point,
+            normal: basis.w(),
+            u_axis: basis.u(),
+            v_axis: basis.v(),
+            mat: material,
+        }
+    }
+
+    // Helper function: a ground plane at height y (facing up)
+    pub fn horizontal(y: f64, material: Arc<dyn Material>) -> Self {
+        Plane::new(Point3::new(0.0, y, 0.0), Vec3::new(0.0, 1.0, 0.0), material)
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let ray_dot_normal = vec3::dot(r.direction(), self.normal);
+
+        // If ray is parallel to the plane, no intersection
+        if ray_dot_normal.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = vec3::dot(self.point - r.origin(), self.normal) / ray_dot_normal;
+        if t <= t_min || t >= t_max {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = r.at(t);
+        // set_face_normal flips the normal for rays from below so both sides render
+        rec.set_face_normal(r, self.normal);
+        let offset = rec.p - self.point;
+        rec.u = vec3::dot(offset, self.u_axis).rem_euclid(1.0);
+        rec.v = vec3::dot(offset, self.v_axis).rem_euclid(1.0);
+        rec.tangent = self.u_axis;
+        rec.bitangent = self.v_axis;
+        rec.mat = Some(self.mat.clone());
+
+        true
+    }
+
+    // Infinite, so it can't be boxed (and can't go in a BvhNode)
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::shapes::sphere::Sphere;
+
+    #[test]
+    fn ray_straight_down_hits_the_ground_at_its_height() {
+        let ground = Plane::horizontal(0.0, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        // Far off to the side, where a Square would have ended long ago
+        let r = Ray::new(Point3::new(1e6, 3.0, -1e6), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let mut rec = HitRecord::new();
+        assert!(ground.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.t - 3.0).abs() < 1e-9);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-12);
+
+        // Going up it never gets there
+        let up = Ray::new(Point3::new(0.0, 3.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        assert!(!ground.hit(&up, 0.001, f64::INFINITY, &mut rec));
+    }
+
+    #[test]
+    fn hit_sets_its_own_texture_coordinates() {
+        let ground = Plane::horizontal(0.0, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        let down =
+            |x: f64, z: f64| Ray::new(Point3::new(x, 1.0, z), Vec3::new(0.0, -1.0, 0.0), 0.0);
+
+        // A sphere under the ground is found first and fills in its own coordinates, then
+        // the nearer plane hit has to replace them
+        let below = Sphere::new(Point3::new(0.0, -5.0, 0.0), 1.0, ground.mat.clone());
+        let mut rec = HitRecord::new();
+        assert!(below.hit(&down(0.0, 0.0), 0.001, f64::INFINITY, &mut rec));
+        assert_ne!((rec.u, rec.v), (0.0, 0.0));
+        assert!(ground.hit(&down(0.0, 0.0), 0.001, rec.t, &mut rec));
+        assert_eq!((rec.u, rec.v), (0.0, 0.0));
+
+        // A quarter step along each axis of the plane, and the same a whole unit further on
+        let (u_axis, v_axis) = (rec.tangent, rec.bitangent);
+        assert!(vec3::dot(u_axis, v_axis).abs() < 1e-12);
+        assert!(vec3::dot(u_axis, rec.normal).abs() < 1e-12);
+        for shift in [0.0, 1.0, -3.0] {
+            let p = (0.25 + shift) * u_axis + (0.75 + shift) * v_axis;
+            assert!(ground.hit(&down(p.x(), p.z()), 0.001, f64::INFINITY, &mut rec));
+            assert!((rec.u - 0.25).abs() < 1e-9 && (rec.v - 0.75).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::aabb::{self, Aabb};
+use crate::common;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{self, Point3, Vec3};
+use std::sync::Arc;
+
+// A flat rectangle, with edges of any length along two axes at right angles (Square is
+// the one with equal edges)
+pub struct Rectangle {
+    center: Point3,
+    normal: Vec3,
+    u_axis: Vec3,  // First edge direction
+    v_axis: Vec3,  // Second edge direction
+    u_extent: f64, // Half of the length along u_axis
+    v_extent: f64, // Half of the length along v_axis
+    mat: Arc<dyn Material>,
+}
+
+impl Rectangle {
+    // The normal is cross(u_axis, v_axis), so the order of the axes picks the front side.
+    // v_axis is straightened to be at right angles to u_axis, so the extents (and the area
+    // light sampling relies on) stay those of a rectangle rather than a parallelogram.
+    pub fn new(
+        center: Point3,
+        u_axis: Vec3,
+        v_axis: Vec3,
+        u_extent: f64,
+        v_extent: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        let u_axis = vec3::unit_vector(u_axis);
+        let v_axis = vec3::unit_vector(v_axis - u_axis * vec3::dot(v_axis, u_axis));
+        assert!(!v_axis.near_zero(), "rectangle axes are parallel");
+
+        Rectangle {
+            center,
+            normal: vec3::unit_vector(vec3::cross(u_axis, v_axis)),
+            u_axis,
+            v_axis,
+            u_extent,
+            v_extent,
+            mat: material,
+        }
+    }
+}
+
+impl Hittable for Rectangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let ray_dot_normal = vec3::dot(r.direction(), self.normal);
+
+        // If ray is parallel to the plane, no intersection
+        if ray_dot_normal.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = vec3::dot(self.center - r.origin(), self.normal) / ray_dot_normal;
+        if t <= t_min || t >= t_max {
+            return false;
+        }
+
+        let hit_point = r.at(t);
+        let center_to_hit = hit_point - self.center;
+
+        // Project onto the rectangle's local axes and check each against its own extent
+        let u_coord = vec3::dot(center_to_hit, self.u_axis);
+        let v_coord = vec3::dot(center_to_hit, self.v_axis);
+        if u_coord.abs() > self.u_extent || v_coord.abs() > self.v_extent {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = hit_point;
+        rec.set_face_normal(r, self.normal);
+        // Rescale the local coordinates from [-extent, extent] to [0, 1]
+        rec.u = 0.5 * (u_coord / self.u_extent + 1.0);
+        rec.v = 0.5 * (v_coord / self.v_extent + 1.0);
+        rec.tangent = self.u_axis;
+        rec.bitangent = self.v_axis;
+        rec.mat = Some(self.mat.clone());
+
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let half_u = self.u_axis * self.u_extent;
+        let half_v = self.v_axis * self.v_extent;
+
+        // Box around the four corners, padded so an axis-aligned rectangle doesn't give a flat box
+        let pad = Vec3::new(0.0001, 0.0001, 0.0001);
+        let mut output_box = Aabb::new(self.center, self.center);
+        for corner in [
+            self.center - half_u - half_v,
+            self.center - half_u + half_v,
+            self.center + half_u - half_v,
+            self.center + half_u + half_v,
+        ] {
+            output_box = aabb::surrounding_box(output_box, Aabb::new(corner - pad, corner + pad));
+        }
+
+        Some(output_box)
+    }
+
+    // Rectangles (and squares) can be sampled as area lights
+    fn can_sample_light(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let mut rec = HitRecord::new();
+        if !self.hit(
+            &Ray::new(origin, direction, 0.0),
+            0.001,
+            common::INFINITY,
+            &mut rec,
+        ) {
+            return 0.0;
+        }
+
+        // Uniform density over the area, converted to density over solid angle
+        let area = 4.0 * self.u_extent * self.v_extent;
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (vec3::dot(direction, self.normal) / direction.length()).abs();
+
+        distance_squared / (cosine * area)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let point = self.center
+            + common::random_double_range(-self.u_extent, self.u_extent) * self.u_axis
+            + common::random_double_range(-self.v_extent, self.v_extent) * self.v_axis;
+        point - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::DiffuseLight;
+
+    #[test]
+    fn slanted_second_axis_is_straightened() {
+        // 2 x 1 light on the ceiling at y = 3, given a v axis leaning along u
+        let light = Rectangle::new(
+            Point3::new(0.0, 3.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 0.0, 1.0),
+            1.0,
+            0.5,
+            Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+        );
+        let b = light.bounding_box().unwrap();
+        assert!((b.max() - Point3::new(0.5, 3.0, 1.0)).length() < 1e-3);
+        assert!((b.min() - Point3::new(-0.5, 3.0, -1.0)).length() < 1e-3);
+
+        // With the area of the 2 x 1 rectangle the density integrates to one
+        let origin = Point3::new(0.5, 0.0, -0.3);
+        common::seed_rng(4);
+        let n = 400_000;
+        let integral = (0..n)
+            .map(|_| light.pdf_value(origin, vec3::random_unit_vector()))
+            .sum::<f64>()
+            * 4.0
+            * common::PI
+            / n as f64;
+        assert!((integral - 1.0).abs() < 0.03, "{}", integral);
+    }
+}
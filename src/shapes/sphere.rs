@@ -1,24 +1,111 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
+use crate::aabb::Aabb;
+use crate::common;
 use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
 use crate::material::Material;
+use crate::onb::OrthoNormalBasis;
 use crate::ray::Ray;
-use crate::vec3::{self, Point3};
+use crate::shapes::Triangle;
+use crate::vec3::{self, Point3, Vec3};
 
 pub struct Sphere {
     center: Point3,
     radius: f64,
-    mat: Rc<dyn Material>,
+    mat: Arc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: Point3, radius: f64, material: Rc<dyn Material>) -> Self {
+    // A negative radius gives the same sphere with its normals pointing inwards, which
+    // makes the inside of a glass sphere inside another one a hollow bubble
+    pub fn new(center: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
         Self {
-            center: center,
-            radius: radius,
+            center,
+            radius,
             mat: material,
         }
     }
+
+    pub fn center(&self) -> Point3 {
+        self.center
+    }
+
+    // Negative for a sphere turned inside out, see new
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    // p: a point on the unit sphere centered at the origin
+    // u: angle around the Y axis starting from +X, mapped to [0, 1]
+    // v: angle from -Y up to +Y, mapped to [0, 1]
+    pub(crate) fn get_sphere_uv(p: Point3) -> (f64, f64) {
+        let theta = f64::acos(-p.y());
+        let mut phi = f64::atan2(p.z(), p.x());
+        if phi < 0.0 {
+            phi += 2.0 * common::PI;
+        }
+
+        (phi / (2.0 * common::PI), theta / common::PI)
+    }
+
+    // The sphere as a mesh of triangles (a UV sphere), with `lat_segments` bands from pole to
+    // pole and `lon_segments` slices around the Y axis. The vertex normals are the sphere's
+    // own, so it shades smoothly. The bands at the poles are fans of single triangles, the
+    // others are split into two triangles per slice: 2 * lon * (lat - 1) triangles in all.
+    pub fn to_mesh(&self, lat_segments: usize, lon_segments: usize) -> HittableList {
+        let (lat, lon) = (lat_segments.max(2), lon_segments.max(3));
+        let radius = self.radius.abs();
+        // Direction from the center, theta from -Y to +Y like get_sphere_uv
+        let direction = |i: usize, j: usize| {
+            let theta = common::PI * i as f64 / lat as f64;
+            let phi = 2.0 * common::PI * j as f64 / lon as f64;
+            Vec3::new(
+                theta.sin() * phi.cos(),
+                -theta.cos(),
+                theta.sin() * phi.sin(),
+            )
+        };
+
+        let mut mesh = HittableList::new();
+        let mut add = |a: Vec3, b: Vec3, c: Vec3| {
+            // A negative radius turns the normals, and the faces, inwards
+            let (b, c) = if self.radius < 0.0 { (c, b) } else { (b, c) };
+            let normals = if self.radius < 0.0 {
+                [-a, -b, -c]
+            } else {
+                [a, b, c]
+            };
+            mesh.add(Box::new(Triangle::with_normals(
+                self.center + radius * a,
+                self.center + radius * b,
+                self.center + radius * c,
+                normals,
+                self.mat.clone(),
+            )));
+        };
+        for i in 0..lat {
+            for j in 0..lon {
+                let (a, b) = (direction(i, j), direction(i, j + 1));
+                let (c, d) = (direction(i + 1, j), direction(i + 1, j + 1));
+                // At the poles a and b (or c and d) are the same point
+                if i > 0 {
+                    add(a, c, b);
+                }
+                if i < lat - 1 {
+                    add(b, c, d);
+                }
+            }
+        }
+        mesh
+    }
+
+    // Directions of growing u (around the Y axis) and v (towards +Y) at the point p of the
+    // unit sphere. Both are zero at the poles, where u is undefined.
+    pub(crate) fn get_sphere_tangents(p: Point3) -> (Vec3, Vec3) {
+        let tangent = vec3::unit_vector(Vec3::new(-p.z(), 0.0, p.x()));
+        (tangent, vec3::cross(tangent, p))
+    }
 }
 
 impl Hittable for Sphere {
@@ -51,10 +138,167 @@ impl Hittable for Sphere {
         rec.t = root;
         rec.p = r.at(rec.t);
 
-        // convert into a unit vector by dividing by the radius
+        // convert into a unit vector by dividing by the radius; the sign of the radius
+        // decides which way the normal points, the surface coordinates don't depend on it
         let outwards_normal = (rec.p - self.center) / self.radius;
+        let from_center = (rec.p - self.center) / self.radius.abs();
         rec.set_face_normal(r, outwards_normal);
+        (rec.u, rec.v) = Self::get_sphere_uv(from_center);
+        (rec.tangent, rec.bitangent) = Self::get_sphere_tangents(from_center);
         rec.mat = Some(self.mat.clone());
         true
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let radius = self.radius.abs();
+        let r = Vec3::new(radius, radius, radius);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+
+    fn centroid(&self) -> Option<Point3> {
+        Some(self.center)
+    }
+
+    fn can_sample_light(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let mut rec = HitRecord::new();
+        if !self.hit(
+            &Ray::new(origin, direction, 0.0),
+            0.001,
+            common::INFINITY,
+            &mut rec,
+        ) {
+            return 0.0;
+        }
+
+        // Uniform over the cone of directions the sphere covers, as seen from origin
+        let distance_squared = (self.center - origin).length_squared();
+        let cos_theta_max = f64::sqrt(f64::max(
+            0.0,
+            1.0 - self.radius * self.radius / distance_squared,
+        ));
+        let solid_angle = 2.0 * common::PI * (1.0 - cos_theta_max);
+
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let direction = self.center - origin;
+        let distance_squared = direction.length_squared();
+        let uvw = OrthoNormalBasis::build_from_w(direction);
+        uvw.local(random_to_sphere(self.radius, distance_squared))
+    }
+}
+
+// Random direction around +Z inside the cone covered by a sphere of the given radius
+// whose center is distance_squared away
+fn random_to_sphere(radius: f64, distance_squared: f64) -> Vec3 {
+    let r1 = common::random_double();
+    let r2 = common::random_double();
+    let cos_theta_max = f64::sqrt(f64::max(0.0, 1.0 - radius * radius / distance_squared));
+    let z = 1.0 + r2 * (cos_theta_max - 1.0);
+
+    let phi = 2.0 * common::PI * r1;
+    let x = f64::cos(phi) * f64::sqrt(1.0 - z * z);
+    let y = f64::sin(phi) * f64::sqrt(1.0 - z * z);
+
+    Vec3::new(x, y, z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    fn unit_sphere() -> Sphere {
+        Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn hit_on_the_x_pole_has_uv_at_the_seam_halfway_up() {
+        let r = Ray::new(Point3::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0), 0.0);
+        let mut rec = HitRecord::new();
+        assert!(unit_sphere().hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert!(rec.u.abs() < 1e-9, "u = {}", rec.u);
+        assert!((rec.v - 0.5).abs() < 1e-9, "v = {}", rec.v);
+    }
+
+    #[test]
+    fn sphere_box_is_the_center_plus_minus_the_radius() {
+        let sphere = Sphere::new(
+            Point3::new(1.0, -2.0, 3.0),
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        let bbox = sphere.bounding_box().unwrap();
+        assert!((bbox.min() - Point3::new(0.5, -2.5, 2.5)).length() < 1e-12);
+        assert!((bbox.max() - Point3::new(1.5, -1.5, 3.5)).length() < 1e-12);
+    }
+
+    #[test]
+    fn negative_radius_turns_the_normals_towards_the_center() {
+        let hollow = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            -1.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+
+        // From the center out, the inside is the front and the normal points back in
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let mut rec = HitRecord::new();
+        assert!(hollow.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.p - Point3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+        assert!(rec.is_front_face());
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+
+        // Coming in from outside, the ray meets the back of that surface
+        let r = Ray::new(Point3::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0), 0.0);
+        let mut rec = HitRecord::new();
+        assert!(hollow.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert!(!rec.is_front_face());
+    }
+
+    #[test]
+    fn tessellated_sphere_hits_close_to_the_real_one() {
+        let sphere = unit_sphere();
+        let mesh = sphere.to_mesh(8, 16);
+        assert_eq!(mesh.len(), 2 * 16 * 7);
+
+        // From all around, towards the center: the mesh is inscribed in the sphere, at
+        // worst cos(PI / 8) * cos(PI / 16) of the radius inside it
+        common::seed_rng(2);
+        for _ in 0..200 {
+            let from = 5.0 * vec3::random_unit_vector();
+            let r = Ray::new(from, -from, 0.0);
+            let (mut exact, mut approx) = (HitRecord::new(), HitRecord::new());
+            assert!(sphere.hit(&r, 0.001, f64::INFINITY, &mut exact));
+            assert!(mesh.hit(&r, 0.001, f64::INFINITY, &mut approx));
+            let distance = approx.p.length();
+            assert!((0.9..=1.0 + 1e-9).contains(&distance), "{}", distance);
+            assert!(vec3::dot(approx.normal, exact.normal) > 0.98);
+        }
+    }
+
+    #[test]
+    fn centroid_is_the_center() {
+        let sphere = Sphere::new(
+            Point3::new(1.0, -2.0, 3.0),
+            -0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        assert_eq!(
+            <[f64; 3]>::from(sphere.centroid().unwrap()),
+            [1.0, -2.0, 3.0]
+        );
+        assert_eq!(<[f64; 3]>::from(sphere.center()), [1.0, -2.0, 3.0]);
+        assert_eq!(sphere.radius(), -0.5);
+    }
 }
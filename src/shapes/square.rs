@@ -1,20 +1,19 @@
+use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::vec3::{self, Point3, Vec3};
-use std::rc::Rc;
+use std::sync::Arc;
 
+use super::rectangle::Rectangle;
+
+// A Rectangle with both edges the same length, whose axes are picked from the normal
 pub struct Square {
-    center: Point3,
-    normal: Vec3,
-    u_axis: Vec3, // First edge direction
-    v_axis: Vec3, // Second edge direction
-    size: f64,
-    mat: Rc<dyn Material>,
+    rect: Rectangle,
 }
 
 impl Square {
-    pub fn new(center: Point3, normal: Vec3, size: f64, material: Rc<dyn Material>) -> Self {
+    pub fn new(center: Point3, normal: Vec3, size: f64, material: Arc<dyn Material>) -> Self {
         let unit_normal = vec3::unit_vector(normal);
 
         // Create perpendicular axes for the square
@@ -25,71 +24,90 @@ impl Square {
             Vec3::new(1.0, 0.0, 0.0)
         };
 
+        // cross(u_axis, v_axis) gives back the normal, which is the side Rectangle faces
         let u_axis = vec3::unit_vector(vec3::cross(unit_normal, temp));
         let v_axis = vec3::cross(unit_normal, u_axis);
 
         Square {
-            center,
-            normal: unit_normal,
-            u_axis,
-            v_axis,
-            size,
-            mat: material,
+            rect: Rectangle::new(center, u_axis, v_axis, size / 2.0, size / 2.0, material),
         }
     }
 
     // Helper function: create a horizontal square (facing up)
-    pub fn horizontal(center: Point3, size: f64, material: Rc<dyn Material>) -> Self {
+    pub fn horizontal(center: Point3, size: f64, material: Arc<dyn Material>) -> Self {
         Square::new(center, Vec3::new(0.0, 1.0, 0.0), size, material)
     }
 
     // Helper function: create a vertical square (facing toward camera)
-    pub fn vertical(center: Point3, size: f64, material: Rc<dyn Material>) -> Self {
+    pub fn vertical(center: Point3, size: f64, material: Arc<dyn Material>) -> Self {
         Square::new(center, Vec3::new(0.0, 0.0, 1.0), size, material)
     }
 }
 
 impl Hittable for Square {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        // Step 1: Check if ray hits the infinite plane
-        let ray_dot_normal = vec3::dot(r.direction(), self.normal);
+        self.rect.hit(r, t_min, t_max, rec)
+    }
 
-        // If ray is parallel to the plane, no intersection
-        if ray_dot_normal.abs() < 1e-8 {
-            return false;
-        }
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.rect.bounding_box()
+    }
 
-        // Calculate t value for plane intersection
-        let center_to_origin = self.center - r.origin();
-        let t = vec3::dot(center_to_origin, self.normal) / ray_dot_normal;
+    fn can_sample_light(&self) -> bool {
+        self.rect.can_sample_light()
+    }
 
-        // Check if intersection is within our t range
-        if t <= t_min || t >= t_max {
-            return false;
-        }
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.rect.pdf_value(origin, direction)
+    }
 
-        // Step 2: Find the intersection point
-        let hit_point = r.at(t);
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.rect.random(origin)
+    }
+}
 
-        // Step 3: Check if hit point is inside the square bounds
-        let center_to_hit = hit_point - self.center;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::common;
+    use crate::material::DiffuseLight;
+
+    // A 2 x 2 light on the ceiling at y = 3, sampled from the floor a bit to its side
+    fn ceiling_light() -> (Square, Point3) {
+        let light = Square::new(
+            Point3::new(0.0, 3.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            2.0,
+            Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+        );
+        (light, Point3::new(0.5, 0.0, -0.3))
+    }
 
-        // Project onto the square's local coordinate system
-        let u_coord = vec3::dot(center_to_hit, self.u_axis);
-        let v_coord = vec3::dot(center_to_hit, self.v_axis);
+    #[test]
+    fn light_pdf_integrates_to_one_over_all_directions() {
+        let (light, origin) = ceiling_light();
+        common::seed_rng(4);
+        // Uniform directions have density 1 / (4 PI), so pdf_value * 4 PI averages to the
+        // integral of the pdf over the sphere
+        let n = 400_000;
+        let integral = (0..n)
+            .map(|_| light.pdf_value(origin, vec3::random_unit_vector()))
+            .sum::<f64>()
+            * 4.0
+            * common::PI
+            / n as f64;
+        assert!((integral - 1.0).abs() < 0.03, "{}", integral);
+    }
 
-        // Check bounds
-        let half_size = self.size / 2.0;
-        if u_coord.abs() > half_size || v_coord.abs() > half_size {
-            return false;
+    #[test]
+    fn sampled_directions_all_lead_to_the_light() {
+        let (light, origin) = ceiling_light();
+        common::seed_rng(4);
+        for _ in 0..1000 {
+            let direction = light.random(origin);
+            assert!(light.pdf_value(origin, direction) > 0.0);
         }
-
-        // We have a hit! Fill the hit record
-        rec.t = t;
-        rec.p = hit_point;
-        rec.set_face_normal(r, self.normal);
-        rec.mat = Some(self.mat.clone());
-
-        true
+        assert_eq!(light.pdf_value(origin, Vec3::new(0.0, -1.0, 0.0)), 0.0);
     }
 }
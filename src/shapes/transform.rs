@@ -0,0 +1,312 @@
+use crate::aabb::Aabb;
+use crate::common;
+use crate::hittable::{HitRecord, Hittable};
+use crate::mat3::Mat3;
+use crate::ray::Ray;
+use crate::vec3::{self, Point3, Vec3};
+
+// Instances: wrappers that place an existing object somewhere else without rebuilding it.
+// Instead of moving the object, the ray is moved the opposite way into the object's own
+// space, and the hit found there is moved back into the world.
+
+// The wrapped object shifted by `offset`
+pub struct Translate {
+    object: Box<dyn Hittable>,
+    offset: Vec3,
+}
+
+impl Translate {
+    pub fn new(object: Box<dyn Hittable>, offset: Vec3) -> Self {
+        Translate { object, offset }
+    }
+}
+
+impl Hittable for Translate {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let moved = Ray::new(r.origin() - self.offset, r.direction(), r.time()).with_kind(r.kind());
+        if !self.object.hit(&moved, t_min, t_max, rec) {
+            return false;
+        }
+
+        rec.p += self.offset;
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let b = self.object.bounding_box()?;
+        Some(Aabb::new(b.min() + self.offset, b.max() + self.offset))
+    }
+
+    fn centroid(&self) -> Option<Point3> {
+        Some(self.object.centroid()? + self.offset)
+    }
+
+    // A moved light is sampled from the origin moved the opposite way, directions don't
+    // change
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.object.pdf_value(origin - self.offset, direction)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.object.random(origin - self.offset)
+    }
+
+    fn can_sample_light(&self) -> bool {
+        self.object.can_sample_light()
+    }
+}
+
+// The wrapped object turned around the Y axis by `angle` degrees
+// (counterclockwise when looking down from +Y)
+pub struct RotateY {
+    rotate: Rotate,
+}
+
+impl RotateY {
+    pub fn new(object: Box<dyn Hittable>, angle: f64) -> Self {
+        let matrix = Mat3::rotation_y(common::degrees_to_radians(angle));
+        RotateY {
+            rotate: Rotate::from_matrix(object, matrix),
+        }
+    }
+}
+
+impl Hittable for RotateY {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        self.rotate.hit(r, t_min, t_max, rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.rotate.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.rotate.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.rotate.random(origin)
+    }
+
+    fn can_sample_light(&self) -> bool {
+        self.rotate.can_sample_light()
+    }
+}
+
+// The wrapped object turned by `angle` degrees around `axis` (through the origin),
+// counterclockwise when looking at the origin from the tip of the axis
+pub struct Rotate {
+    object: Box<dyn Hittable>,
+    // Object space to world space, and back (a rotation's inverse is its transpose)
+    to_world: Mat3,
+    to_object: Mat3,
+    bbox: Option<Aabb>,
+}
+
+impl Rotate {
+    pub fn new(object: Box<dyn Hittable>, axis: Vec3, angle: f64) -> Self {
+        let matrix = Mat3::from_axis_angle(axis, common::degrees_to_radians(angle));
+        Rotate::from_matrix(object, matrix)
+    }
+
+    // `rotation` must be a rotation matrix (orthonormal, determinant 1)
+    pub fn from_matrix(object: Box<dyn Hittable>, rotation: Mat3) -> Self {
+        let bbox = object
+            .bounding_box()
+            .map(|b| rotated_box(b, |v| rotation * v));
+        Rotate {
+            object,
+            to_world: rotation,
+            to_object: rotation.transpose(),
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Rotate {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let rotated = Ray::new(
+            self.to_object * r.origin(),
+            self.to_object * r.direction(),
+            r.time(),
+        )
+        .with_kind(r.kind());
+        if !self.object.hit(&rotated, t_min, t_max, rec) {
+            return false;
+        }
+
+        // The rotation is orthonormal, so normals turn the same way as points and
+        // keep facing against the ray
+        rec.p = self.to_world * rec.p;
+        rec.normal = self.to_world * rec.normal;
+        rec.tangent = self.to_world * rec.tangent;
+        rec.bitangent = self.to_world * rec.bitangent;
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+
+    // A turned light is sampled in its own space, the direction found there turned back.
+    // Rotations keep solid angles, so the density stays the same.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.object
+            .pdf_value(self.to_object * origin, self.to_object * direction)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.to_world * self.object.random(self.to_object * origin)
+    }
+
+    fn can_sample_light(&self) -> bool {
+        self.object.can_sample_light()
+    }
+}
+
+// The box around the eight corners of `b` after `rotate`
+fn rotated_box(b: Aabb, rotate: impl Fn(Vec3) -> Vec3) -> Aabb {
+    let mut corners = (0..8).map(|i| {
+        let pick = |bit: usize, lo: f64, hi: f64| if i & bit == 0 { lo } else { hi };
+        rotate(Point3::new(
+            pick(1, b.min().x(), b.max().x()),
+            pick(2, b.min().y(), b.max().y()),
+            pick(4, b.min().z(), b.max().z()),
+        ))
+    });
+    let first = corners.next().unwrap();
+    let (min, max) = corners.fold((first, first), |(min, max), c| {
+        (vec3::min(min, c), vec3::max(max, c))
+    });
+    Aabb::new(min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::color::Color;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::shapes::{Cube, Sphere, Square};
+
+    fn gray() -> Arc<Lambertian> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    fn light() -> Arc<DiffuseLight> {
+        Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0)))
+    }
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn translated_sphere_is_hit_at_the_offset() {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, gray());
+        let moved = Translate::new(Box::new(sphere), Vec3::new(3.0, 1.0, 0.0));
+
+        let mut rec = HitRecord::new();
+        let r = Ray::new(Point3::new(3.0, 1.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(moved.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert!(close(rec.p, Point3::new(3.0, 1.0, 1.0)));
+        assert!(close(rec.normal, Vec3::new(0.0, 0.0, 1.0)));
+        assert!((rec.t - 4.0).abs() < 1e-9);
+
+        // Where the sphere was before there's nothing left
+        let r = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(!moved.hit(&r, 0.001, f64::INFINITY, &mut rec));
+    }
+
+    #[test]
+    fn turned_cube_has_a_wider_box() {
+        let cube = || Box::new(Cube::centered(Point3::new(0.0, 0.0, 0.0), 2.0, gray()));
+        let plain = cube().bounding_box().unwrap();
+        let turned = RotateY::new(cube(), 45.0).bounding_box().unwrap();
+
+        let half_diagonal = f64::sqrt(2.0);
+        assert!(turned.max().x() > plain.max().x() + 0.4);
+        assert!((turned.max().x() - half_diagonal).abs() < 1e-3);
+        assert!((turned.min().z() + half_diagonal).abs() < 1e-3);
+        // Turning around Y leaves the height alone
+        assert!((turned.max().y() - plain.max().y()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn moved_and_turned_lights_are_sampled_where_they_end_up() {
+        // Both end up as a sphere of radius 0.5 at (0, 0, -2)
+        let moved = Translate::new(
+            Box::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 0.5, light())),
+            Vec3::new(0.0, 0.0, -2.0),
+        );
+        let turned = RotateY::new(
+            Box::new(Sphere::new(Point3::new(2.0, 0.0, 0.0), 0.5, light())),
+            90.0,
+        );
+        let in_place = Sphere::new(Point3::new(0.0, 0.0, -2.0), 0.5, light());
+        let origin = Point3::new(0.0, 0.0, 0.0);
+
+        common::seed_rng(1);
+        for light in [&moved as &dyn Hittable, &turned] {
+            for _ in 0..100 {
+                let direction = light.random(origin);
+                assert!(-direction.z() > 0.9 * direction.length());
+                let pdf = light.pdf_value(origin, direction);
+                assert!(pdf > 0.0);
+                assert!((pdf - in_place.pdf_value(origin, direction)).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn square_turned_about_z_faces_the_other_way() {
+        // Facing up, turned a quarter counterclockwise seen from +Z: facing -X
+        let square = Square::horizontal(Point3::new(0.0, 0.0, 0.0), 2.0, gray());
+        let turned = Rotate::new(Box::new(square), Vec3::new(0.0, 0.0, 1.0), 90.0);
+
+        let mut rec = HitRecord::new();
+        let from_minus_x = Ray::new(Point3::new(-5.0, 0.2, 0.3), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(turned.hit(&from_minus_x, 0.001, f64::INFINITY, &mut rec));
+        assert!(rec.front_face);
+        assert!(close(rec.normal, Vec3::new(-1.0, 0.0, 0.0)));
+        assert!(close(rec.p, Point3::new(0.0, 0.2, 0.3)));
+
+        let from_plus_x = Ray::new(Point3::new(5.0, 0.2, 0.3), Vec3::new(-1.0, 0.0, 0.0), 0.0);
+        assert!(turned.hit(&from_plus_x, 0.001, f64::INFINITY, &mut rec));
+        assert!(!rec.front_face);
+
+        // Rays along the square's old normal now graze it
+        let from_above = Ray::new(Point3::new(0.5, 5.0, 0.5), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(!turned.hit(&from_above, 0.001, f64::INFINITY, &mut rec));
+    }
+
+    #[test]
+    fn turned_light_is_sampled_where_it_ends_up() {
+        // A light at +X turned a quarter about Z ends up at +Y
+        let sphere = Sphere::new(Point3::new(2.0, 0.0, 0.0), 0.5, light());
+        let turned = Rotate::new(Box::new(sphere), Vec3::new(0.0, 0.0, 1.0), 90.0);
+        let in_place = Sphere::new(Point3::new(0.0, 2.0, 0.0), 0.5, light());
+        let origin = Point3::new(0.0, 0.0, 0.0);
+
+        common::seed_rng(1);
+        for _ in 0..100 {
+            let direction = turned.random(origin);
+            assert!(direction.y() > 0.9 * direction.length());
+            let pdf = turned.pdf_value(origin, direction);
+            assert!(pdf > 0.0);
+            assert!((pdf - in_place.pdf_value(origin, direction)).abs() < 1e-9);
+        }
+        assert_eq!(turned.pdf_value(origin, Vec3::new(1.0, 0.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn moved_sphere_keeps_its_centroid_at_its_center() {
+        let sphere = Sphere::new(Point3::new(1.0, 0.0, 0.0), 0.5, gray());
+        let moved = Translate::new(Box::new(sphere), Vec3::new(0.0, 2.0, -1.0));
+        assert!(close(
+            moved.centroid().unwrap(),
+            Point3::new(1.0, 2.0, -1.0)
+        ));
+    }
+}
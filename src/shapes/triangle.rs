@@ -0,0 +1,136 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{self, Point3, Vec3};
+
+pub struct Triangle {
+    v0: Point3,
+    v1: Point3,
+    v2: Point3,
+    normal: Vec3,
+    // Normals at v0, v1 and v2 for smooth shading, blended across the face
+    vertex_normals: Option<[Vec3; 3]>,
+    mat: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, material: Arc<dyn Material>) -> Self {
+        // Counter-clockwise vertices give a normal pointing towards the viewer
+        let normal = vec3::unit_vector(vec3::cross(v1 - v0, v2 - v0));
+
+        Self {
+            v0,
+            v1,
+            v2,
+            normal,
+            vertex_normals: None,
+            mat: material,
+        }
+    }
+
+    // A triangle shaded as part of a curved surface, with the surface's normal at each
+    // vertex (pointing to the same side as the counter-clockwise face normal). Only the
+    // shading is smooth, the outline is still a flat triangle.
+    pub fn with_normals(
+        v0: Point3,
+        v1: Point3,
+        v2: Point3,
+        normals: [Vec3; 3],
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            vertex_normals: Some(normals.map(vec3::unit_vector)),
+            ..Self::new(v0, v1, v2, material)
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    // Möller–Trumbore: solve origin + t * direction = (1 - u - v) * v0 + u * v1 + v * v2
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let edge1 = self.v1 - self.v0;
+        let edge2 = self.v2 - self.v0;
+
+        let p_vec = vec3::cross(r.direction(), edge2);
+        let det = vec3::dot(edge1, p_vec);
+
+        // If the determinant is near 0 the ray is parallel to the triangle
+        if det.abs() < 1e-8 {
+            return false;
+        }
+        let inv_det = 1.0 / det;
+
+        let t_vec = r.origin() - self.v0;
+        let u = vec3::dot(t_vec, p_vec) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return false;
+        }
+
+        let q_vec = vec3::cross(t_vec, edge1);
+        let v = vec3::dot(r.direction(), q_vec) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return false;
+        }
+
+        let t = vec3::dot(edge2, q_vec) * inv_det;
+        if t <= t_min || t >= t_max {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.set_face_normal(r, self.normal);
+        if let Some([n0, n1, n2]) = self.vertex_normals {
+            // The face decides which side was hit, the blended normal how it is shaded
+            let shading_normal = vec3::unit_vector((1.0 - u - v) * n0 + u * n1 + v * n2);
+            rec.normal = if rec.front_face {
+                shading_normal
+            } else {
+                -shading_normal
+            };
+        }
+        // Barycentric coordinates double as texture coordinates
+        rec.u = u;
+        rec.v = v;
+        rec.mat = Some(self.mat.clone());
+
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let min = vec3::min(vec3::min(self.v0, self.v1), self.v2);
+        let max = vec3::max(vec3::max(self.v0, self.v1), self.v2);
+
+        // Pad so an axis-aligned triangle doesn't give a flat box
+        let pad = Vec3::new(0.0001, 0.0001, 0.0001);
+        Some(Aabb::new(min - pad, max + pad))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn ray_at_the_centroid_hits_it_facing_the_ray() {
+        let triangle = Triangle::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(3.0, 0.0, 0.0),
+            Point3::new(0.0, 3.0, 0.0),
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        let r = Ray::new(Point3::new(1.0, 1.0, 2.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let mut rec = HitRecord::new();
+        assert!(triangle.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.t - 2.0).abs() < 1e-9);
+        assert!((rec.p - Point3::new(1.0, 1.0, 0.0)).length() < 1e-9);
+        // Counter-clockwise seen from +Z, so the normal points back up the ray
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+        assert!(rec.is_front_face());
+    }
+}
@@ -0,0 +1,156 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::{Ray, RayKind};
+use crate::vec3::{Point3, Vec3};
+
+// The wrapped object, hidden from some kinds of rays: with `primary_visible` off the camera
+// looks straight through it, though it still shows in reflections and still casts shadows
+// (a shadow catcher, or a stand-in for something composited in later); with `cast_shadows`
+// off it doesn't block the light reaching other surfaces.
+pub struct Visibility {
+    object: Box<dyn Hittable>,
+    pub primary_visible: bool,
+    pub cast_shadows: bool,
+}
+
+impl Visibility {
+    pub fn new(object: Box<dyn Hittable>, primary_visible: bool, cast_shadows: bool) -> Self {
+        Visibility {
+            object,
+            primary_visible,
+            cast_shadows,
+        }
+    }
+
+    // Not seen by the camera, but casting shadows and showing in reflections
+    pub fn shadow_only(object: Box<dyn Hittable>) -> Self {
+        Visibility::new(object, false, true)
+    }
+
+    // Seen as usual, but letting the light through to other surfaces
+    pub fn without_shadows(object: Box<dyn Hittable>) -> Self {
+        Visibility::new(object, true, false)
+    }
+}
+
+impl Hittable for Visibility {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let visible = match r.kind() {
+            RayKind::Camera => self.primary_visible,
+            RayKind::Shadow => self.cast_shadows,
+            RayKind::Bounce => true,
+        };
+        visible && self.object.hit(r, t_min, t_max, rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn centroid(&self) -> Option<Point3> {
+        self.object.centroid()
+    }
+
+    // A light hidden from the camera can still be sampled
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.object.random(origin)
+    }
+
+    // Not one that lets shadow rays through though: they would never reach it, and its
+    // light would be missing from what sampling it should have found
+    fn can_sample_light(&self) -> bool {
+        self.cast_shadows && self.object.can_sample_light()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::background::SolidBackground;
+    use crate::color::Color;
+    use crate::common;
+    use crate::hittable_list::HittableList;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::render_mode::RenderMode;
+    use crate::shapes::{Plane, Sphere, Square};
+    use crate::tracer::Tracer;
+
+    fn lamp() -> Sphere {
+        Sphere::new(
+            Point3::new(0.0, 5.0, 0.0),
+            0.5,
+            Arc::new(DiffuseLight::new(Color::new(20.0, 20.0, 20.0))),
+        )
+    }
+
+    // Average brightness of the floor right under the lamp, seen from straight above
+    fn floor_brightness(with_blocker: bool) -> f64 {
+        let gray = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut world = HittableList::new();
+        world.add(Box::new(Plane::horizontal(0.0, gray.clone())));
+        world.add(Box::new(lamp()));
+        if with_blocker {
+            let blocker = Square::horizontal(Point3::new(0.0, 1.0, 0.0), 2.0, gray);
+            world.add(Box::new(Visibility::shadow_only(Box::new(blocker))));
+        }
+        let mut lights = HittableList::new();
+        lights.add(Box::new(lamp()));
+        let background = SolidBackground(Color::new(0.0, 0.0, 0.0));
+        let tracer = Tracer {
+            world: &world,
+            lights: &lights,
+            background: &background,
+            max_depth: 4,
+            ray_epsilon: 0.001,
+            far_clip: f64::INFINITY,
+            mode: RenderMode::Beauty,
+            occlusion_distance: 1.0,
+        };
+
+        common::seed_rng(1);
+        let r = Ray::new(Point3::new(0.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0)
+            .with_kind(RayKind::Camera);
+        let samples = 200;
+        let total = (0..samples).fold(Color::new(0.0, 0.0, 0.0), |sum, _| {
+            sum + tracer.ray_color(&r)
+        });
+        total.x() / samples as f64
+    }
+
+    #[test]
+    fn shadow_only_object_darkens_the_floor_unseen() {
+        let lit = floor_brightness(false);
+        let shadowed = floor_brightness(true);
+        assert!(lit > 0.1);
+        assert!(shadowed < 0.1 * lit, "{} vs {}", shadowed, lit);
+
+        // The camera sees the floor through it, anything else runs into it
+        let blocker = Visibility::shadow_only(Box::new(Square::horizontal(
+            Point3::new(0.0, 1.0, 0.0),
+            2.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        let down = Ray::new(Point3::new(0.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let mut rec = HitRecord::new();
+        assert!(!blocker.hit(
+            &down.with_kind(RayKind::Camera),
+            0.001,
+            f64::INFINITY,
+            &mut rec
+        ));
+        assert!(blocker.hit(&down, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.p.y() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn light_without_shadows_is_not_sampled() {
+        assert!(Visibility::shadow_only(Box::new(lamp())).can_sample_light());
+        assert!(!Visibility::without_shadows(Box::new(lamp())).can_sample_light());
+    }
+}
@@ -0,0 +1,329 @@
+use crate::color::{self, Color};
+use crate::common;
+use crate::perlin::Perlin;
+use crate::png;
+use crate::vec3::Point3;
+use std::fs;
+use std::sync::Arc;
+
+pub trait Texture: Send + Sync {
+    // Color of the surface at the (u, v) surface coordinates / hit point p
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+}
+
+pub struct SolidColor {
+    color_value: Color,
+}
+
+impl SolidColor {
+    pub fn new(c: Color) -> SolidColor {
+        SolidColor { color_value: c }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.color_value
+    }
+}
+
+// 3D checker pattern alternating between two textures
+pub struct CheckerTexture {
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+    scale: f64,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> CheckerTexture {
+        CheckerTexture { even, odd, scale }
+    }
+
+    pub fn from_colors(scale: f64, even: Color, odd: Color) -> CheckerTexture {
+        CheckerTexture::new(
+            scale,
+            Arc::new(SolidColor::new(even)),
+            Arc::new(SolidColor::new(odd)),
+        )
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let sines = f64::sin(self.scale * p.x())
+            * f64::sin(self.scale * p.y())
+            * f64::sin(self.scale * p.z());
+
+        if sines < 0.0 {
+            self.odd.value(u, v, p)
+        } else {
+            self.even.value(u, v, p)
+        }
+    }
+}
+
+// Texture backed by an image file, PNG or PPM (P3 ascii / P6 binary). The formats are
+// decoded here so no image crate is needed; JPEG isn't supported. `open` reports a file
+// that can't be loaded, `new` renders it solid magenta instead so the problem is obvious
+// in the output without aborting the render.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    // A picture: its bytes are gamma-encoded colors like the images written here, and are
+    // decoded back to linear colors (see color::from_srgb_bytes)
+    pub fn open(path: &str) -> Result<ImageTexture, String> {
+        let to_byte = |c: f64| (c * 255.0).round() as u8;
+        ImageTexture::load(path, |c| {
+            color::from_srgb_bytes([to_byte(c.x()), to_byte(c.y()), to_byte(c.z())])
+        })
+    }
+
+    // Data stored as an image, like normal maps, height maps and roughness: the values are
+    // taken as they are, only scaled to [0, 1]
+    pub fn open_linear(path: &str) -> Result<ImageTexture, String> {
+        ImageTexture::load(path, |c| c)
+    }
+
+    // Like open, but a file that can't be loaded gives the magenta texture
+    pub fn new(path: &str) -> ImageTexture {
+        ImageTexture::open(path).unwrap_or_else(|_| ImageTexture::missing())
+    }
+
+    // Like open_linear, but a file that can't be loaded gives the magenta texture
+    pub fn linear(path: &str) -> ImageTexture {
+        ImageTexture::open_linear(path).unwrap_or_else(|_| ImageTexture::missing())
+    }
+
+    fn missing() -> ImageTexture {
+        ImageTexture {
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+        }
+    }
+
+    fn load(path: &str, decode: impl Fn(Color) -> Color) -> Result<ImageTexture, String> {
+        let data = fs::read(path).map_err(|e| format!("could not read '{}': {}", path, e))?;
+        let decoded = if data.starts_with(png::SIGNATURE) {
+            png::decode(&data)
+        } else {
+            parse_ppm(&data)
+        };
+        let (width, height, pixels) =
+            decoded.map_err(|e| format!("could not load image '{}': {}", path, e))?;
+        Ok(ImageTexture {
+            width,
+            height,
+            pixels: pixels.into_iter().map(decode).collect(),
+        })
+    }
+
+    // Width and height in pixels, (0, 0) if the image couldn't be loaded
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        // No texture data: return magenta as a debugging aid
+        if self.pixels.is_empty() {
+            return Color::new(1.0, 0.0, 1.0);
+        }
+
+        // Clamp input texture coordinates to [0, 1] and flip V to image coordinates
+        let u = common::clamp(u, 0.0, 1.0);
+        let v = 1.0 - common::clamp(v, 0.0, 1.0);
+
+        let i = usize::min((u * self.width as f64) as usize, self.width - 1);
+        let j = usize::min((v * self.height as f64) as usize, self.height - 1);
+
+        self.pixels[j * self.width + i]
+    }
+}
+
+// Decode a P3 or P6 PPM into (width, height, pixels) with colors scaled to [0, 1]
+pub(crate) fn parse_ppm(data: &[u8]) -> Result<(usize, usize, Vec<Color>), String> {
+    let mut pos = 0;
+    let mut next_token = || -> Result<String, String> {
+        loop {
+            // Skip whitespace and '#' comments between header tokens
+            while pos < data.len() && data[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if pos < data.len() && data[pos] == b'#' {
+                while pos < data.len() && data[pos] != b'\n' {
+                    pos += 1;
+                }
+                continue;
+            }
+            break;
+        }
+        let start = pos;
+        while pos < data.len() && !data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err("unexpected end of file".to_string());
+        }
+        Ok(String::from_utf8_lossy(&data[start..pos]).into_owned())
+    };
+
+    let magic = next_token()?;
+    let mut number = || -> Result<usize, String> {
+        let token = next_token()?;
+        token
+            .parse::<usize>()
+            .map_err(|_| format!("invalid number '{}'", token))
+    };
+    let width = number()?;
+    let height = number()?;
+    let max_value = number()?;
+    if max_value == 0 || max_value > 255 {
+        return Err(format!("unsupported max color value {}", max_value));
+    }
+    let scale = 1.0 / max_value as f64;
+
+    // A made up header can ask for more samples than there are numbers
+    let sample_count = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or_else(|| format!("image size {}x{} is too large", width, height))?;
+
+    let samples: Vec<usize> = match magic.as_str() {
+        "P3" => (0..sample_count)
+            .map(|_| number())
+            .collect::<Result<_, _>>()?,
+        "P6" => {
+            // A single whitespace byte separates the header from the binary data
+            let start = pos + 1;
+            let end = start
+                .checked_add(sample_count)
+                .filter(|&end| end <= data.len())
+                .ok_or_else(|| "truncated pixel data".to_string())?;
+            data[start..end].iter().map(|&b| b as usize).collect()
+        }
+        _ => return Err(format!("unsupported format '{}'", magic)),
+    };
+
+    let pixels = samples
+        .chunks(3)
+        .map(|c| {
+            Color::new(
+                c[0] as f64 * scale,
+                c[1] as f64 * scale,
+                c[2] as f64 * scale,
+            )
+        })
+        .collect();
+
+    Ok((width, height, pixels))
+}
+
+// Marble-like grayscale pattern driven by Perlin turbulence
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64) -> NoiseTexture {
+        NoiseTexture {
+            noise: Perlin::new(),
+            scale,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        Color::new(0.5, 0.5, 0.5)
+            * (1.0 + f64::sin(self.scale * p.z() + 10.0 * self.noise.turb(p, 7)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/2x2.ppm");
+    const PNG_FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/2x2.png");
+
+    fn same(a: Color, b: Color) -> bool {
+        (a - b).length() < 1e-12
+    }
+
+    #[test]
+    fn image_texture_corners() {
+        for path in [FIXTURE, PNG_FIXTURE] {
+            check_corners(&ImageTexture::open(path).unwrap());
+        }
+    }
+
+    // The fixtures hold red, green / blue, mid gray; the PNG rows are filtered with the
+    // Sub and Paeth filters
+    fn check_corners(texture: &ImageTexture) {
+        assert_eq!(texture.size(), (2, 2));
+        let at = |u: f64, v: f64| texture.value(u, v, &Point3::new(0.0, 0.0, 0.0));
+
+        // v runs up the image, the first row of the file is the top
+        assert!(same(at(0.0, 1.0), Color::new(1.0, 0.0, 0.0)));
+        assert!(same(at(1.0, 1.0), Color::new(0.0, 1.0, 0.0)));
+        assert!(same(at(0.0, 0.0), Color::new(0.0, 0.0, 1.0)));
+        // Mid gray is decoded to linear, and written back out as the same bytes
+        let gray = at(1.0, 0.0);
+        assert!(same(gray, color::from_srgb_bytes([128, 128, 128])));
+        assert_eq!(color::to_srgb_bytes(gray), [128, 128, 128]);
+    }
+
+    #[test]
+    fn linear_image_texture_keeps_the_values() {
+        let texture = ImageTexture::linear(FIXTURE);
+        let gray = texture.value(1.0, 0.0, &Point3::new(0.0, 0.0, 0.0));
+        assert!((gray.x() - 128.0 / 255.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn missing_image_is_an_error_or_magenta() {
+        let error = ImageTexture::open("no/such/texture.png").err().unwrap();
+        assert!(error.contains("no/such/texture.png"));
+        assert!(ImageTexture::open_linear("no/such/texture.png").is_err());
+
+        let texture = ImageTexture::new("no/such/texture.png");
+        assert_eq!(texture.size(), (0, 0));
+        let c = texture.value(0.5, 0.5, &Point3::new(0.0, 0.0, 0.0));
+        assert!(same(c, Color::new(1.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn oversized_header_is_an_error() {
+        for data in [
+            &b"P6\n18446744073709551615 2\n255\n\0\0\0"[..],
+            &b"P3\n4294967296 4294967296\n255\n0 0 0"[..],
+            &b"P6\n2 2\n255\n\0\0\0"[..],
+        ] {
+            assert!(parse_ppm(data).is_err());
+        }
+    }
+
+    #[test]
+    fn checker_alternates_one_cell_along_x() {
+        // With a scale of pi the cells are 1 wide
+        let checker = CheckerTexture::from_colors(
+            common::PI,
+            Color::new(1.0, 1.0, 1.0),
+            Color::new(0.0, 0.0, 0.0),
+        );
+        let p = Point3::new(0.5, 0.5, 0.5);
+        let next = Point3::new(1.5, 0.5, 0.5);
+        assert!(same(checker.value(0.0, 0.0, &p), Color::new(1.0, 1.0, 1.0)));
+        assert!(same(
+            checker.value(0.0, 0.0, &next),
+            Color::new(0.0, 0.0, 0.0)
+        ));
+    }
+}
@@ -0,0 +1,62 @@
+// A rectangle of pixels rendered as one piece of work. Pixels next to each other on screen
+// mostly see the same objects, so rendering them together keeps those in the cache, and
+// separate tiles can go to separate threads.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tile {
+    // Top left pixel, counted from the top left of the image
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    // The pixels of the tile, row by row from the top
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.y..self.y + self.height)
+            .flat_map(move |y| (self.x..self.x + self.width).map(move |x| (x, y)))
+    }
+}
+
+// Cover a width x height image with tiles of tile_size x tile_size, row by row from the
+// top left. Tiles on the right and bottom edges are cut down to fit the image.
+pub fn tiles(width: usize, height: usize, tile_size: usize) -> Vec<Tile> {
+    assert!(tile_size > 0, "tiles need a size");
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(tile_size) {
+        for x in (0..width).step_by(tile_size) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: tile_size.min(width - x),
+                height: tile_size.min(height - y),
+            });
+        }
+    }
+    tiles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seventy_pixels_square_is_three_by_three_tiles_of_32() {
+        let grid = tiles(70, 70, 32);
+        assert_eq!(grid.len(), 9);
+        // Edge tiles are cut down to the 6 pixels left over
+        let sizes: Vec<_> = grid.iter().map(|t| (t.x, t.y, t.width, t.height)).collect();
+        assert_eq!(
+            &sizes[..3],
+            [(0, 0, 32, 32), (32, 0, 32, 32), (64, 0, 6, 32)]
+        );
+        assert_eq!(sizes[8], (64, 64, 6, 6));
+
+        // Every pixel once
+        let mut covered = vec![0; 70 * 70];
+        for (x, y) in grid.iter().flat_map(|t| t.pixels()) {
+            covered[y * 70 + x] += 1;
+        }
+        assert!(covered.iter().all(|&n| n == 1));
+    }
+}
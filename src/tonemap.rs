@@ -0,0 +1,78 @@
+use crate::color::Color;
+
+// How linear (averaged) colors above 1.0 are squeezed into displayable range
+// before gamma correction
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ToneMap {
+    // Leave the color alone, anything above 1.0 is clipped when written
+    None,
+    Reinhard,
+    Aces,
+}
+
+impl ToneMap {
+    pub fn from_name(name: &str) -> Option<ToneMap> {
+        match name {
+            "none" => Some(ToneMap::None),
+            "reinhard" => Some(ToneMap::Reinhard),
+            "aces" => Some(ToneMap::Aces),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, c: Color) -> Color {
+        match self {
+            ToneMap::None => c,
+            ToneMap::Reinhard => reinhard(c),
+            ToneMap::Aces => aces(c),
+        }
+    }
+}
+
+// c / (1 + c) per channel: maps [0, inf) to [0, 1) and keeps brightness ordering
+pub fn reinhard(c: Color) -> Color {
+    Color::new(
+        c.x() / (1.0 + c.x()),
+        c.y() / (1.0 + c.y()),
+        c.z() / (1.0 + c.z()),
+    )
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+pub fn aces(c: Color) -> Color {
+    let curve = |x: f64| {
+        const A: f64 = 2.51;
+        const B: f64 = 0.03;
+        const C: f64 = 2.43;
+        const D: f64 = 0.59;
+        const E: f64 = 0.14;
+        let x = x.max(0.0);
+        ((x * (A * x + B)) / (x * (C * x + D) + E)).clamp(0.0, 1.0)
+    };
+
+    Color::new(curve(c.x()), curve(c.y()), curve(c.z()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reinhard_keeps_bright_colors_below_one_and_in_order() {
+        let mut previous = -1.0;
+        for brightness in [0.0, 0.5, 1.0, 4.0, 100.0, 1e6] {
+            let mapped = reinhard(Color::new(brightness, brightness, brightness)).x();
+            assert!(
+                (0.0..1.0).contains(&mapped),
+                "{} became {}",
+                brightness,
+                mapped
+            );
+            assert!(mapped > previous);
+            previous = mapped;
+        }
+        assert!(
+            (reinhard(Color::new(1.0, 3.0, 0.0)) - Color::new(0.5, 0.75, 0.0)).length() < 1e-12
+        );
+    }
+}
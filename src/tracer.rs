@@ -0,0 +1,667 @@
+// The path tracer itself: the color a camera ray sees, and a plain renderer built on it
+
+use crate::background::Background;
+use crate::color::{self, Color};
+use crate::common;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::pdf::{BackgroundPdf, HittablePdf, MixturePdf, Pdf};
+use crate::ray::{Ray, RayKind};
+use crate::render_mode::{self, RenderMode};
+use crate::sampling::SampleStrategy;
+use crate::scene::Scene;
+use crate::vec3::{Point3, Vec3};
+
+// Bounces that always continue before Russian roulette may end a path
+const ROULETTE_MIN_BOUNCES: i32 = 3;
+
+// Power heuristic weight for a sample drawn with density `pdf` when the same light
+// could also have been reached through a strategy with density `other_pdf`
+fn mis_weight(pdf: f64, other_pdf: f64) -> f64 {
+    pdf * pdf / (pdf * pdf + other_pdf * other_pdf)
+}
+
+// What a path needs to know about the scene while it bounces around
+pub struct Tracer<'a> {
+    // The scene's objects, or the same objects sorted into a BVH
+    pub world: &'a dyn Hittable,
+    pub lights: &'a HittableList,
+    pub background: &'a dyn Background,
+    pub max_depth: i32,
+    pub ray_epsilon: f64,
+    pub far_clip: f64,
+    pub mode: RenderMode,
+    pub occlusion_distance: f64,
+}
+
+impl Tracer<'_> {
+    // Next event estimation: light arriving at a diffuse hit straight from a point picked on
+    // one of the lights (or a direction picked towards a bright part of the background),
+    // weighted against finding the same light by bouncing (MIS)
+    fn sample_lights(&self, r: &Ray, rec: &HitRecord, attenuation: Color) -> Color {
+        let mat = rec.mat.as_ref().unwrap();
+        let (direction, light_pdf) = self.with_light_pdf(rec.p, |pdf| {
+            let direction = pdf.generate();
+            (direction, pdf.value(direction))
+        });
+        let to_light = Ray::new(rec.p, direction, r.time()).with_kind(RayKind::Shadow);
+        let bsdf_pdf = mat.scattering_pdf(r, rec, &to_light);
+        if light_pdf <= 0.0 || bsdf_pdf <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        // Shadow ray: the light only counts if it's the first thing in that direction. A ray
+        // that gets away sees the background, which counts here only if it is sampled here
+        // too (the others are found by bouncing alone).
+        let mut light_rec = HitRecord::new();
+        let emitted = if self.world.hit(
+            &to_light,
+            self.t_min(&to_light),
+            self.t_max(&to_light),
+            &mut light_rec,
+        ) {
+            light_rec.mat.as_ref().unwrap().emitted(
+                light_rec.u,
+                light_rec.v,
+                &light_rec.p,
+                to_light.direction(),
+            )
+        } else if self.background.can_sample_light() {
+            self.background.sample(to_light.direction())
+        } else {
+            return Color::new(0.0, 0.0, 0.0);
+        };
+
+        // For a diffuse surface attenuation * scattering_pdf is the BRDF times the cosine term
+        mis_weight(light_pdf, bsdf_pdf) * attenuation * bsdf_pdf * emitted / light_pdf
+    }
+
+    // Calls `f` with the density sample_lights picks directions from `origin` with. With both
+    // lights and a background that can be sampled, each is picked half the time. The
+    // densities live on the stack, which is why they're lent to `f` instead of returned.
+    fn with_light_pdf<R>(&self, origin: Point3, f: impl FnOnce(&dyn Pdf) -> R) -> R {
+        let lights = HittablePdf::new(self.lights, origin);
+        let background = BackgroundPdf::new(self.background);
+        match (!self.lights.is_empty(), self.background.can_sample_light()) {
+            (true, true) => f(&MixturePdf::new(&background, &lights)),
+            (false, true) => f(&background),
+            _ => f(&lights),
+        }
+    }
+
+    // Density of sample_lights picking `direction` from `origin`
+    fn light_pdf(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.with_light_pdf(origin, |pdf| pdf.value(direction))
+    }
+
+    fn t_min(&self, r: &Ray) -> f64 {
+        Scene::t_min(self.ray_epsilon, r.origin())
+    }
+
+    fn t_max(&self, r: &Ray) -> f64 {
+        Scene::t_max(self.far_clip, r)
+    }
+
+    // Color seen along the camera ray `r`
+    pub fn ray_color(&self, r: &Ray) -> Color {
+        self.follow_path(r, None)
+    }
+
+    // The same, also writing down each step of the path into `log`, to see where a pixel's
+    // color comes from
+    pub fn ray_color_logged(&self, r: &Ray, log: &mut Vec<Bounce>) -> Color {
+        self.follow_path(r, Some(log))
+    }
+
+    // Follows the ray from bounce to bounce in a loop (rather than recursing, which would
+    // need a stack frame per bounce). `accumulated` is the light found so far and `weight`
+    // how much of the light found at the current bounce reaches the camera: the product of
+    // the attenuations so far, with Russian roulette's boosts.
+    fn follow_path(&self, r: &Ray, mut log: Option<&mut Vec<Bounce>>) -> Color {
+        let mut accumulated = Color::new(0.0, 0.0, 0.0);
+        let mut weight = Color::new(1.0, 1.0, 1.0);
+        // The density the previous diffuse bounce picked the ray's direction with (None for
+        // camera rays and mirror-like bounces), needed to weight light found by the ray
+        // against the light sampled at that bounce
+        let mut bsdf_pdf: Option<f64> = None;
+        let mut ray = *r;
+
+        // Past the bounce limit no more light is gathered
+        for bounce in 0..self.max_depth {
+            let mut rec = HitRecord::new();
+            let hit = self
+                .world
+                .hit(&ray, self.t_min(&ray), self.t_max(&ray), &mut rec);
+            // The step as written down into the log, only made when there is one
+            let step = |light: Color, attenuation: Option<Color>| Bounce {
+                ray,
+                hit: hit.then(|| (rec.p, rec.mat.as_ref().unwrap().name())),
+                light,
+                attenuation,
+            };
+
+            // The auxiliary passes stop at the first hit and leave misses black
+            if self.mode != RenderMode::Beauty {
+                log_step(&mut log, || step(Color::new(0.0, 0.0, 0.0), None));
+                return match (hit, self.mode) {
+                    (false, _) => Color::new(0.0, 0.0, 0.0),
+                    (true, RenderMode::Occlusion) => {
+                        let open = render_mode::occlusion(
+                            self.world,
+                            &ray,
+                            &rec,
+                            self.ray_epsilon,
+                            self.occlusion_distance,
+                        );
+                        // Squared like the other passes, see first_hit_color
+                        Color::new(1.0, 1.0, 1.0) * (open * open)
+                    }
+                    (true, _) => self.mode.first_hit_color(&ray, &rec),
+                };
+            }
+
+            // Nothing hit, the ray sees the background, weighted against sampling it directly
+            // if that is done
+            if !hit {
+                let mut background = self.background.sample(ray.direction());
+                if let (Some(bsdf_pdf), true) = (bsdf_pdf, self.background.can_sample_light()) {
+                    let light_pdf = self.light_pdf(ray.origin(), ray.direction());
+                    background = mis_weight(bsdf_pdf, light_pdf) * background;
+                }
+                log_step(&mut log, || step(background, None));
+                return accumulated + weight * background;
+            }
+
+            let mat = rec.mat.as_ref().unwrap();
+            let mut emitted = mat.emitted(rec.u, rec.v, &rec.p, ray.direction());
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                let light_pdf = self.light_pdf(ray.origin(), ray.direction());
+                emitted = mis_weight(bsdf_pdf, light_pdf) * emitted;
+            }
+
+            let mut attenuation = Color::default();
+            let mut scattered = Ray::default();
+            if !mat.scatter(&ray, &rec, &mut attenuation, &mut scattered) {
+                log_step(&mut log, || step(emitted, None));
+                return accumulated + weight * emitted;
+            }
+
+            // Diffuse surfaces also look straight at the lights
+            let scatter_pdf = mat.scattering_pdf(&ray, &rec, &scattered);
+            let diffuse = scatter_pdf > 0.0;
+            let direct = if diffuse {
+                self.sample_lights(&ray, &rec, attenuation)
+            } else {
+                Color::new(0.0, 0.0, 0.0)
+            };
+            accumulated += weight * (emitted + direct);
+            log_step(&mut log, || step(emitted + direct, Some(attenuation)));
+
+            // Russian roulette: paths that can only add little light are ended at random,
+            // and the survivors are boosted by 1/p so the average stays the same. The
+            // chance comes from the weight including the earlier boosts, so a survivor's
+            // weight goes back up to about 1 instead of its chances shrinking bounce after
+            // bounce (which makes rare paths with enormous weights, i.e. fireflies).
+            weight *= attenuation;
+            if bounce >= ROULETTE_MIN_BOUNCES {
+                let p = f64::min(f64::max(weight.x(), f64::max(weight.y(), weight.z())), 1.0);
+                if common::random_double() >= p {
+                    break;
+                }
+                weight /= p;
+            }
+
+            bsdf_pdf = if diffuse { Some(scatter_pdf) } else { None };
+            ray = scattered;
+        }
+
+        accumulated
+    }
+}
+
+// One step of a path, as written down by Tracer::ray_color_logged
+#[derive(Clone, Copy)]
+pub struct Bounce {
+    pub ray: Ray,
+    // Where the ray hit and the name of the material there, None if it left the scene
+    pub hit: Option<(Point3, &'static str)>,
+    // Light found at this step: the background for a ray that left the scene, otherwise
+    // what the surface gives off plus the light sampled directly from there. As it arrives
+    // here, the attenuations of the steps before still have to be applied.
+    pub light: Color,
+    // Share of the light arriving along the next ray that the surface passes on, None
+    // where the path ends
+    pub attenuation: Option<Color>,
+}
+
+fn log_step(log: &mut Option<&mut Vec<Bounce>>, step: impl FnOnce() -> Bounce) {
+    if let Some(log) = log {
+        log.push(step());
+    }
+}
+
+// Render `scene` into `width` x `height` pixels of 8 bit red, green, blue and alpha (always
+// opaque), row by row from the top and gamma-corrected like the PPM output: what a canvas
+// in a browser takes. Nothing is printed or written to files and everything runs on the
+// calling thread, so this also works in WebAssembly (built without the native feature).
+// The image should have the aspect ratio the scene's camera was built with; the samples
+// per pixel and the other render settings come from the scene. The random numbers come
+// from the calling thread's generator, so seeding it with common::seed_rng first gives
+// the same image every time.
+pub fn render_to_rgba(scene: &Scene, width: usize, height: usize) -> Vec<u8> {
+    let tracer = Tracer {
+        world: &scene.world,
+        lights: &scene.lights,
+        background: scene.background.as_ref(),
+        max_depth: scene.max_depth,
+        ray_epsilon: scene.ray_epsilon,
+        far_clip: scene.far_clip,
+        mode: RenderMode::Beauty,
+        occlusion_distance: 1.0,
+    };
+    let samples = scene.samples_per_pixel;
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        // The camera counts rows from the bottom
+        let j = height - 1 - y;
+        for i in 0..width {
+            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+            for s in 0..samples {
+                let (du, dv) = SampleStrategy::Stratified.offset(s, samples);
+                let u = (i as f64 + du) / width.saturating_sub(1).max(1) as f64;
+                let v = (j as f64 + dv) / height.saturating_sub(1).max(1) as f64;
+                // Samples the camera has no ray for count as black, and so do broken ones
+                if let Some(r) = scene.camera.get_ray(u, v) {
+                    let sample = tracer.ray_color(&r);
+                    if sample.is_finite() {
+                        pixel_color += sample;
+                    }
+                }
+            }
+            let [r, g, b] = color::to_srgb_bytes(color::average(pixel_color, samples));
+            rgba.extend_from_slice(&[r, g, b, 255]);
+        }
+    }
+    rgba
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::background::SolidBackground;
+    use crate::camera::Camera;
+    use crate::material::{DiffuseLight, Lambertian, Material, Metal};
+    use crate::shapes::{Plane, Sphere, Square};
+
+    #[test]
+    fn ray_bouncing_off_into_the_sky_leaves_two_steps() {
+        let mut world = HittableList::new();
+        world.add(Box::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.0)),
+        )));
+        let lights = HittableList::new();
+        let background = SolidBackground(Color::new(0.5, 0.5, 0.5));
+        let tracer = Tracer {
+            world: &world,
+            lights: &lights,
+            background: &background,
+            max_depth: 10,
+            ray_epsilon: 0.001,
+            far_clip: f64::INFINITY,
+            mode: RenderMode::Beauty,
+            occlusion_distance: 1.0,
+        };
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0)
+            .with_kind(RayKind::Camera);
+        let mut log = Vec::new();
+        let color = tracer.ray_color_logged(&r, &mut log);
+
+        assert_eq!(log.len(), 2);
+        let (p, material) = log[0].hit.unwrap();
+        assert!((p - Point3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+        assert_eq!(material, "metal");
+        assert!(log[0].attenuation.is_some());
+        // Straight back out, where it sees the sky
+        assert!(log[1].hit.is_none());
+        assert!(log[1].ray.direction().z() > 0.0);
+        assert!(log[1].attenuation.is_none());
+        assert!((color - Color::new(0.4, 0.4, 0.4)).length() < 1e-9);
+        // Logging doesn't change the color
+        assert!((tracer.ray_color(&r) - color).length() < 1e-12);
+    }
+
+    // Walls that glow with a brightness of 1 and send half the light arriving on to
+    // somewhere random (without being diffuse as far as light sampling is concerned), so
+    // a path inside them never gets out and adds up a known amount of light
+    struct GlowingWall(f64);
+
+    impl Material for GlowingWall {
+        fn scatter(
+            &self,
+            r_in: &Ray,
+            rec: &HitRecord,
+            attenuation: &mut Color,
+            scattered: &mut Ray,
+        ) -> bool {
+            let direction = rec.normal + crate::vec3::random_unit_vector();
+            *scattered = Ray::new(rec.p, direction, r_in.time());
+            *attenuation = Color::new(self.0, self.0, self.0);
+            true
+        }
+
+        fn emitted(&self, _u: f64, _v: f64, _p: &Point3, _direction: Vec3) -> Color {
+            Color::new(1.0, 1.0, 1.0)
+        }
+    }
+
+    #[test]
+    fn russian_roulette_keeps_the_average() {
+        common::seed_rng(1);
+        let albedo = 0.8;
+        let mut world = HittableList::new();
+        world.add(Box::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(GlowingWall(albedo)),
+        )));
+        let lights = HittableList::new();
+        let background = SolidBackground(Color::new(0.0, 0.0, 0.0));
+        let tracer = Tracer {
+            world: &world,
+            lights: &lights,
+            background: &background,
+            max_depth: 12,
+            ray_epsilon: 0.001,
+            far_clip: f64::INFINITY,
+            mode: RenderMode::Beauty,
+            occlusion_distance: 1.0,
+        };
+
+        // Without roulette every path would add up 1 + 0.8 + 0.8^2 + ... over 12 bounces
+        let expected = (1.0 - albedo.powi(12)) / (1.0 - albedo);
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), 0.0)
+            .with_kind(RayKind::Camera);
+        let n = 20000;
+        let mut log = Vec::new();
+        let (mut sum, mut sum_squared, mut ended_early) = (0.0, 0.0, 0);
+        for _ in 0..n {
+            log.clear();
+            let sample = tracer.ray_color_logged(&r, &mut log).x();
+            sum += sample;
+            sum_squared += sample * sample;
+            if log.len() < 12 {
+                ended_early += 1;
+            }
+        }
+        let mean = sum / n as f64;
+        let standard_error = ((sum_squared / n as f64 - mean * mean) / n as f64).sqrt();
+
+        assert!(
+            ended_early > n / 10,
+            "only {} paths were ended",
+            ended_early
+        );
+        assert!(
+            (mean - expected).abs() < 4.0 * standard_error,
+            "mean {} vs {} (standard error {})",
+            mean,
+            expected,
+            standard_error
+        );
+    }
+
+    // Mean and variance of `n` samples of the red channel of what `r` sees
+    fn mean_and_variance(tracer: &Tracer, r: &Ray, n: usize) -> (f64, f64) {
+        let samples: Vec<f64> = (0..n).map(|_| tracer.ray_color(r).x()).collect();
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        let variance = samples.iter().map(|s| (s - mean) * (s - mean)).sum::<f64>() / n as f64;
+        (mean, variance)
+    }
+
+    #[test]
+    fn sampling_the_lights_gives_the_same_light_with_less_noise() {
+        // A gray floor under a small round lamp, in the dark
+        let mut world = HittableList::new();
+        world.add(Box::new(Square::horizontal(
+            Point3::new(0.0, 0.0, 0.0),
+            100.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        let lamp = || {
+            Sphere::new(
+                Point3::new(0.0, 2.0, 0.0),
+                0.3,
+                Arc::new(DiffuseLight::new(Color::new(10.0, 10.0, 10.0))),
+            )
+        };
+        world.add(Box::new(lamp()));
+        let mut lights = HittableList::new();
+        lights.add(Box::new(lamp()));
+        let no_lights = HittableList::new();
+        let background = SolidBackground(Color::new(0.0, 0.0, 0.0));
+        let tracer = |lights| Tracer {
+            world: &world,
+            lights,
+            background: &background,
+            max_depth: 5,
+            ray_epsilon: 0.001,
+            far_clip: f64::INFINITY,
+            mode: RenderMode::Beauty,
+            occlusion_distance: 1.0,
+        };
+
+        // Looking down at the floor next to the lamp
+        let r = Ray::new(Point3::new(0.0, 1.0, 3.0), Vec3::new(0.0, -1.0, -1.0), 0.0)
+            .with_kind(RayKind::Camera);
+        common::seed_rng(1);
+        let n = 20000;
+        let (mis_mean, mis_variance) = mean_and_variance(&tracer(&lights), &r, n);
+        let (bounce_mean, bounce_variance) = mean_and_variance(&tracer(&no_lights), &r, n);
+
+        // Both are unbiased, so they agree within the noise
+        let standard_error = ((mis_variance + bounce_variance) / n as f64).sqrt();
+        assert!(
+            (mis_mean - bounce_mean).abs() < 4.0 * standard_error,
+            "{} with light sampling, {} without (standard error {})",
+            mis_mean,
+            bounce_mean,
+            standard_error
+        );
+        assert!(
+            mis_variance * 4.0 < bounce_variance,
+            "variance {} with light sampling, {} without",
+            mis_variance,
+            bounce_variance
+        );
+    }
+
+    #[test]
+    fn sphere_on_a_plane_has_no_black_speckles() {
+        common::seed_rng(8);
+        let gray: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)));
+        let mut world = HittableList::new();
+        world.add(Box::new(Sphere::new(
+            Point3::new(0.0, 1.0, 0.0),
+            1.0,
+            gray.clone(),
+        )));
+        world.add(Box::new(Plane::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            gray,
+        )));
+        let lights = HittableList::new();
+        let background = SolidBackground(Color::new(1.0, 1.0, 1.0));
+        let tracer = Tracer {
+            world: &world,
+            lights: &lights,
+            background: &background,
+            max_depth: 50,
+            ray_epsilon: 0.001,
+            far_clip: f64::INFINITY,
+            mode: RenderMode::Beauty,
+            occlusion_distance: 1.0,
+        };
+
+        // Straight down on the top of the sphere, which mostly sees the sky. A surface
+        // hitting itself again on the way out would darken a sample to nothing.
+        let n = 2000;
+        let black = (0..n)
+            .filter(|_| {
+                let x = common::random_double_range(-0.3, 0.3);
+                let z = common::random_double_range(-0.3, 0.3);
+                let r = Ray::new(Point3::new(x, 5.0, z), Vec3::new(0.0, -1.0, 0.0), 0.0)
+                    .with_kind(RayKind::Camera);
+                tracer.ray_color(&r).length() == 0.0
+            })
+            .count();
+        assert!(black * 100 < n, "{} black samples of {}", black, n);
+    }
+
+    // ray_color as it was before paths were followed in a loop: recursing once per bounce,
+    // without lights to sample or Russian roulette
+    fn recursive_ray_color(tracer: &Tracer, r: &Ray, depth: i32) -> Color {
+        if depth <= 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let mut rec = HitRecord::new();
+        if !tracer
+            .world
+            .hit(r, tracer.t_min(r), tracer.t_max(r), &mut rec)
+        {
+            return tracer.background.sample(r.direction());
+        }
+        let mat = rec.mat.as_ref().unwrap();
+        let emitted = mat.emitted(rec.u, rec.v, &rec.p, r.direction());
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::default();
+        if !mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
+            return emitted;
+        }
+        emitted + attenuation * recursive_ray_color(tracer, &scattered, depth - 1)
+    }
+
+    #[test]
+    fn loop_gives_the_same_colors_as_recursion() {
+        let mut world = HittableList::new();
+        world.add(Box::new(Sphere::new(
+            Point3::new(-1.0, 0.0, 0.0),
+            0.8,
+            Arc::new(Lambertian::new(Color::new(0.7, 0.3, 0.3))),
+        )));
+        world.add(Box::new(Sphere::new(
+            Point3::new(1.0, 0.0, 0.0),
+            0.8,
+            Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.3)),
+        )));
+        world.add(Box::new(Sphere::new(
+            Point3::new(0.0, 2.0, 0.0),
+            0.5,
+            Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+        )));
+        world.add(Box::new(Plane::new(
+            Point3::new(0.0, -0.8, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        let lights = HittableList::new();
+        let background = SolidBackground(Color::new(0.2, 0.3, 0.5));
+        // Few enough bounces that roulette never starts
+        let tracer = Tracer {
+            world: &world,
+            lights: &lights,
+            background: &background,
+            max_depth: ROULETTE_MIN_BOUNCES,
+            ray_epsilon: 0.001,
+            far_clip: f64::INFINITY,
+            mode: RenderMode::Beauty,
+            occlusion_distance: 1.0,
+        };
+
+        // Same random numbers for both, so every single path comes out the same
+        let mut lit = 0;
+        for i in 0..400 {
+            let target = Point3::new(
+                (i % 20) as f64 / 5.0 - 2.0,
+                (i / 20) as f64 / 8.0 - 1.0,
+                0.0,
+            );
+            let origin = Point3::new(0.0, 0.5, 5.0);
+            let r = Ray::new(origin, target - origin, 0.0).with_kind(RayKind::Camera);
+            common::seed_rng(i);
+            let looped = tracer.ray_color(&r);
+            common::seed_rng(i);
+            let recursed = recursive_ray_color(&tracer, &r, tracer.max_depth);
+            assert!((looped - recursed).length() < 1e-12, "ray {}", i);
+            if looped.length() > 1.0 {
+                lit += 1;
+            }
+        }
+        // Some of the paths found the light
+        assert!(lit > 0);
+    }
+
+    #[test]
+    fn objects_past_the_far_clip_are_not_seen() {
+        // Two identical lights, one 5 away to the left and one 50 away to the right
+        let mut world = HittableList::new();
+        for center in [Point3::new(-5.0, 0.0, 0.0), Point3::new(50.0, 0.0, 0.0)] {
+            world.add(Box::new(Sphere::new(
+                center,
+                1.0,
+                Arc::new(DiffuseLight::new(Color::new(3.0, 3.0, 3.0))),
+            )));
+        }
+        let lights = HittableList::new();
+        let background = SolidBackground(Color::new(0.0, 0.0, 0.0));
+        let tracer = Tracer {
+            world: &world,
+            lights: &lights,
+            background: &background,
+            max_depth: 5,
+            ray_epsilon: 0.001,
+            far_clip: 20.0,
+            mode: RenderMode::Beauty,
+            occlusion_distance: 1.0,
+        };
+
+        // Directions needn't be unit vectors, the clip is a distance all the same
+        let look = |x: f64| {
+            let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(x, 0.0, 0.0), 0.0)
+                .with_kind(RayKind::Camera);
+            tracer.ray_color(&r)
+        };
+        assert!((look(-1.0) - Color::new(3.0, 3.0, 3.0)).length() < 1e-12);
+        assert!((look(-10.0) - Color::new(3.0, 3.0, 3.0)).length() < 1e-12);
+        assert_eq!(look(1.0).length(), 0.0);
+        assert_eq!(look(0.1).length(), 0.0);
+    }
+
+    #[test]
+    fn rgba_buffer_has_four_opaque_bytes_per_pixel() {
+        let mut world = HittableList::new();
+        world.add(Box::new(Sphere::new(
+            Point3::new(0.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        let camera = Camera::builder().aspect_ratio(1.5).build();
+        let mut scene = Scene::new(world, camera);
+        scene.aspect_ratio = 1.5;
+        scene.samples_per_pixel = 4;
+
+        common::seed_rng(16);
+        let rgba = render_to_rgba(&scene, 6, 4);
+        assert_eq!(rgba.len(), 6 * 4 * 4);
+        assert!(rgba.chunks(4).all(|pixel| pixel[3] == 255));
+        // The same seed gives the same picture
+        common::seed_rng(16);
+        assert_eq!(render_to_rgba(&scene, 6, 4), rgba);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+
+// Share of the pixels below the value that maps to the top of the heatmap, so a few
+// fireflies don't leave everything else black
+const HEATMAP_PERCENTILE: f64 = 0.99;
+
+// Variance of every pixel's average, per channel, from the sums of its `samples` samples
+// and of their squares: how much the pixel would still change from one render to the next
+// with a different seed. It shrinks with more samples, and falls fastest where the image is
+// already smooth.
+pub fn variance(sums: &Framebuffer, squares: &Framebuffer, samples: i32) -> Framebuffer {
+    assert!(
+        (sums.width, sums.height) == (squares.width, squares.height),
+        "sums and squares differ in size"
+    );
+    let n = samples.max(1) as f64;
+    let mut variance = Framebuffer::new(sums.width, sums.height);
+    for y in 0..sums.height {
+        for x in 0..sums.width {
+            let (sum, square) = (sums.get(x, y), squares.get(x, y));
+            // Unbiased spread of the samples, divided by n once more for the average
+            let channel = |s: f64, q: f64| ((q - s * s / n) / (n - 1.0).max(1.0) / n).max(0.0);
+            variance.set(
+                x,
+                y,
+                Color::new(
+                    channel(sum.x(), square.x()),
+                    channel(sum.y(), square.y()),
+                    channel(sum.z(), square.z()),
+                ),
+            );
+        }
+    }
+    variance
+}
+
+// The variance as a heatmap from black (no noise) through red and yellow to white (the
+// noisiest pixels), for spotting where more samples are needed. The colors come out
+// squared, like the auxiliary render passes, so the gamma step shows the ramp as is.
+pub fn heatmap(variance: &Framebuffer) -> Framebuffer {
+    let level = |c: Color| (c.x() + c.y() + c.z()) / 3.0;
+
+    let mut levels: Vec<f64> = (0..variance.height)
+        .flat_map(|y| (0..variance.width).map(move |x| (x, y)))
+        .map(|(x, y)| level(variance.get(x, y)))
+        .collect();
+    levels.sort_by(f64::total_cmp);
+    let top = levels
+        .get(((levels.len() as f64 * HEATMAP_PERCENTILE) as usize).min(levels.len().max(1) - 1))
+        .copied()
+        .filter(|&top| top > 0.0)
+        .unwrap_or(1.0);
+
+    variance.map(|c| {
+        let t = (level(c) / top).min(1.0);
+        let ramp = |start: f64| (3.0 * t - start).clamp(0.0, 1.0).powi(2);
+        Color::new(ramp(0.0), ramp(1.0), ramp(2.0))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_pixels_are_quiet_and_edges_noisy() {
+        // Pixel 0 lies on a flat gray patch, pixel 1 on a black and white edge: half its
+        // samples see each side
+        let samples = 16;
+        let mut sums = Framebuffer::new(2, 1);
+        let mut squares = Framebuffer::new(2, 1);
+        let mut add = |x: usize, value: f64| {
+            let sample = Color::new(value, value, value);
+            sums.set(x, 0, sums.get(x, 0) + sample);
+            squares.set(x, 0, squares.get(x, 0) + sample * sample);
+        };
+        for s in 0..samples {
+            add(0, 0.5);
+            add(1, if s % 2 == 0 { 0.0 } else { 1.0 });
+        }
+
+        let variance = variance(&sums, &squares, samples);
+        let flat = variance.get(0, 0);
+        let edge = variance.get(1, 0);
+        assert!(flat.x().abs() < 1e-12);
+        // Samples of 0 and 1 spread by 0.25 * 16 / 15, the average by 1 / 16 of that
+        assert!((edge.x() - 0.25 / 15.0).abs() < 1e-12);
+
+        let heatmap = heatmap(&variance);
+        assert!(heatmap.get(0, 0).x() < heatmap.get(1, 0).x());
+    }
+}
@@ -1,9 +1,17 @@
-use std::fmt::{Display, Formatter, Result};
-use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub};
+use std::fmt::{Debug, Display, Formatter, Result};
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign,
+};
 
 use crate::common;
 
+// With the serde feature a Vec3 (and so a Color) is written as [x, y, z]
 #[derive(Copy, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "[f64; 3]", into = "[f64; 3]")
+)]
 pub struct Vec3 {
     e: [f64; 3],
 }
@@ -49,6 +57,11 @@ impl Vec3 {
         self.e[0] * self.e[0] + self.e[1] * self.e[1] + self.e[2] * self.e[2]
     }
 
+    // False if any component is NaN or infinite
+    pub fn is_finite(&self) -> bool {
+        self.e.iter().all(|c| c.is_finite())
+    }
+
     pub fn near_zero(&self) -> bool {
         const EPS: f64 = 1.0e-8;
         // Return true if the vector is close to zero in all dimensions
@@ -59,10 +72,48 @@ impl Vec3 {
 // Type alias
 pub type Point3 = Vec3;
 
-// Output formatting
+impl From<[f64; 3]> for Vec3 {
+    fn from(e: [f64; 3]) -> Vec3 {
+        Vec3 { e }
+    }
+}
+
+impl From<Vec3> for [f64; 3] {
+    fn from(v: Vec3) -> [f64; 3] {
+        v.e
+    }
+}
+
+// Output formatting: (x, y, z)
 impl Display for Vec3 {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "{} {} {}", self.e[0], self.e[1], self.e[2])
+        write!(f, "({}, {}, {})", self.e[0], self.e[1], self.e[2])
+    }
+}
+
+// Vec3(x, y, z)
+impl Debug for Vec3 {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_tuple("Vec3")
+            .field(&self.e[0])
+            .field(&self.e[1])
+            .field(&self.e[2])
+            .finish()
+    }
+}
+
+// v[0], v[1], v[2] for x, y, z
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, i: usize) -> &f64 {
+        &self.e[i]
+    }
+}
+
+impl IndexMut<usize> for Vec3 {
+    fn index_mut(&mut self, i: usize) -> &mut f64 {
+        &mut self.e[i]
     }
 }
 
@@ -82,6 +133,20 @@ impl AddAssign for Vec3 {
     }
 }
 
+// Vec3 -= Vec3
+impl SubAssign for Vec3 {
+    fn sub_assign(&mut self, v: Vec3) {
+        *self = *self - v;
+    }
+}
+
+// Vec3 *= Vec3
+impl MulAssign for Vec3 {
+    fn mul_assign(&mut self, v: Vec3) {
+        *self = *self * v;
+    }
+}
+
 // Vec3 *= f64
 impl MulAssign<f64> for Vec3 {
     fn mul_assign(&mut self, t: f64) {
@@ -162,8 +227,38 @@ pub fn cross(u: Vec3, v: Vec3) -> Vec3 {
     )
 }
 
+// v scaled to length 1. A zero vector has no direction and comes back as the zero vector
+// instead of NaNs, callers that need a real direction should check near_zero first.
 pub fn unit_vector(v: Vec3) -> Vec3 {
-    v / v.length()
+    let length = v.length();
+    if length == 0.0 {
+        return v;
+    }
+    v / length
+}
+
+// Component-wise minimum
+pub fn min(a: Vec3, b: Vec3) -> Vec3 {
+    Vec3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()))
+}
+
+// Component-wise maximum
+pub fn max(a: Vec3, b: Vec3) -> Vec3 {
+    Vec3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()))
+}
+
+// Clamp every component to [lo, hi]
+pub fn clamp(v: Vec3, lo: f64, hi: f64) -> Vec3 {
+    Vec3::new(
+        common::clamp(v.x(), lo, hi),
+        common::clamp(v.y(), lo, hi),
+        common::clamp(v.z(), lo, hi),
+    )
+}
+
+// Linear interpolation, a at t = 0 and b at t = 1
+pub fn lerp(a: Vec3, b: Vec3, t: f64) -> Vec3 {
+    (1.0 - t) * a + t * b
 }
 
 pub fn random_in_unit_sphere() -> Vec3 {
@@ -176,6 +271,49 @@ pub fn random_in_unit_sphere() -> Vec3 {
     }
 }
 
+// Random point in the half of the unit sphere on the side `normal` points to, e.g. for
+// sampling directions uniformly over a surface's hemisphere
+pub fn random_in_hemisphere(normal: Vec3) -> Vec3 {
+    let in_unit_sphere = random_in_unit_sphere();
+    if dot(in_unit_sphere, normal) < 0.0 {
+        -in_unit_sphere
+    } else {
+        in_unit_sphere
+    }
+}
+
+// Uniform random point in the regular polygon with `sides` corners on the unit circle
+// (the first one on +X), in the XY plane
+pub fn random_in_unit_polygon(sides: u32) -> Vec3 {
+    // All the triangles between the center and two neighboring corners are the same size,
+    // so pick one of them and then a point in it
+    let corner = |k: f64| {
+        let angle = 2.0 * common::PI * k / sides as f64;
+        Vec3::new(angle.cos(), angle.sin(), 0.0)
+    };
+    let k = common::random_int_range(0, sides as i32 - 1) as f64;
+    let (a, b) = (corner(k), corner(k + 1.0));
+
+    // sqrt spreads the points evenly over the triangle instead of bunching at the center
+    let r = common::random_double().sqrt();
+    let s = common::random_double();
+    r * ((1.0 - s) * a + s * b)
+}
+
+// Random direction around +Z with a density of cos(theta) / pi, theta being the angle
+// from +Z: a uniform point on the unit disk lifted up onto the hemisphere
+pub fn random_cosine_direction() -> Vec3 {
+    let r1 = common::random_double();
+    let r2 = common::random_double();
+
+    let phi = 2.0 * common::PI * r1;
+    let x = phi.cos() * r2.sqrt();
+    let y = phi.sin() * r2.sqrt();
+    let z = (1.0 - r2).sqrt();
+
+    Vec3::new(x, y, z)
+}
+
 pub fn random_unit_vector() -> Vec3 {
     unit_vector(random_in_unit_sphere())
 }
@@ -194,13 +332,162 @@ pub fn random_in_unit_disk() -> Vec3 {
     }
 }
 
+// Mirror v around the surface normal n
 pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {
     v - 2.0 * dot(v, n) * n
 }
 
+// Refract the unit vector uv through a surface with normal n (Snell's law).
+// The result is split into the part perpendicular to n and the part parallel to it:
+//   r_out_perp     = etai_over_etat * (uv + cos_theta * n)
+//   r_out_parallel = -sqrt(|1 - |r_out_perp|^2|) * n
 pub fn refract(uv: Vec3, n: Vec3, etai_over_etat: f64) -> Vec3 {
     let cos_theta = f64::min(dot(-uv, n), 1.0);
     let r_out_perp = etai_over_etat * (uv + cos_theta * n);
     let r_out_parallel = -f64::sqrt(f64::abs(1.0 - r_out_perp.length_squared())) * n;
     r_out_perp + r_out_parallel
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Unit direction going down at `degrees` from the normal +Y, leaning towards +X
+    fn incoming(degrees: f64) -> Vec3 {
+        let angle = degrees.to_radians();
+        Vec3::new(angle.sin(), -angle.cos(), 0.0)
+    }
+
+    #[test]
+    fn refract_without_index_change_goes_straight_on() {
+        let uv = incoming(45.0);
+        let out = refract(uv, Vec3::new(0.0, 1.0, 0.0), 1.0);
+        assert!((out - uv).length() < 1e-12, "{} became {}", uv, out);
+    }
+
+    #[test]
+    fn refract_into_glass_follows_snells_law() {
+        // From air into glass of index 1.5: sin(out) = sin(in) / 1.5
+        let out = refract(incoming(45.0), Vec3::new(0.0, 1.0, 0.0), 1.0 / 1.5);
+        let sin_out = out.x() / out.length();
+        let expected = 45.0_f64.to_radians().sin() / 1.5;
+        assert!((sin_out - expected).abs() < 1e-9, "{sin_out} vs {expected}");
+        assert!(out.y() < 0.0);
+        assert!((out.length() - 1.0).abs() < 1e-9);
+    }
+
+    fn same(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-12
+    }
+
+    #[test]
+    fn min_and_max_pick_each_component_separately() {
+        // a is bigger in x and z, b in y
+        let a = Vec3::new(3.0, -1.0, 5.0);
+        let b = Vec3::new(2.0, 4.0, -6.0);
+        assert!(same(min(a, b), Vec3::new(2.0, -1.0, -6.0)));
+        assert!(same(max(a, b), Vec3::new(3.0, 4.0, 5.0)));
+        assert!(same(min(b, a), min(a, b)));
+        assert!(same(max(b, a), max(a, b)));
+    }
+
+    #[test]
+    fn lerp_goes_from_a_to_b() {
+        // The x and z components shrink on the way, y grows
+        let a = Vec3::new(4.0, -2.0, 1.0);
+        let b = Vec3::new(-2.0, 6.0, 0.5);
+        assert!(same(lerp(a, b, 0.0), a));
+        assert!(same(lerp(a, b, 1.0), b));
+        assert!(same(lerp(a, b, 0.5), Vec3::new(1.0, 2.0, 0.75)));
+    }
+
+    #[test]
+    fn clamp_limits_each_component() {
+        let v = Vec3::new(-0.5, 0.25, 7.0);
+        assert!(same(clamp(v, 0.0, 1.0), Vec3::new(0.0, 0.25, 1.0)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn vec3_survives_a_json_round_trip() {
+        // Values without a short decimal form, which must still come back bit for bit
+        let v = Vec3::new(0.1 + 0.2, -1.0 / 3.0, 1e-300);
+        let json = serde_json::to_string(&v).unwrap();
+        assert_eq!(json, format!("[{:?},{:?},{:?}]", v.x(), v.y(), v.z()));
+        let back: Vec3 = serde_json::from_str(&json).unwrap();
+        assert_eq!(<[f64; 3]>::from(back), <[f64; 3]>::from(v));
+    }
+
+    #[test]
+    fn unit_vector_of_zero_is_zero_not_nan() {
+        let v = unit_vector(Vec3::new(0.0, 0.0, 0.0));
+        assert!(v.is_finite());
+        assert_eq!(v.length(), 0.0);
+        assert!(v.near_zero());
+    }
+
+    #[test]
+    fn is_finite_catches_infinity_and_nan() {
+        assert!(Vec3::new(1.0, -2.0, 3.0).is_finite());
+        assert!(!Vec3::new(1.0, f64::INFINITY, 3.0).is_finite());
+        assert!(!Vec3::new(f64::NEG_INFINITY, 0.0, 0.0).is_finite());
+        assert!(!Vec3::new(0.0, 0.0, f64::NAN).is_finite());
+    }
+
+    #[test]
+    fn indexing_matches_the_named_components() {
+        let mut v = Vec3::new(1.5, -2.0, 3.25);
+        assert_eq!(v[0], v.x());
+        assert_eq!(v[1], v.y());
+        assert_eq!(v[2], v.z());
+        v[1] = 7.0;
+        assert_eq!(v.y(), 7.0);
+    }
+
+    #[test]
+    fn negation_flips_every_component() {
+        let v = -Vec3::new(1.5, -2.0, 0.0);
+        assert_eq!(<[f64; 3]>::from(v), [-1.5, 2.0, -0.0]);
+    }
+
+    #[test]
+    fn display_prints_the_components_in_parentheses() {
+        assert_eq!(Vec3::new(1.0, -2.5, 0.125).to_string(), "(1, -2.5, 0.125)");
+    }
+
+    #[test]
+    fn cosine_directions_follow_the_cosine() {
+        common::seed_rng(6);
+        let n = 100_000;
+        let cosines: Vec<f64> = (0..n)
+            .map(|_| {
+                let d = random_cosine_direction();
+                assert!((d.length() - 1.0).abs() < 1e-9 && d.z() >= 0.0);
+                d.z()
+            })
+            .collect();
+
+        // With density cos / PI the cosine itself is spread like the square root of a
+        // uniform number: a quarter below 1/2, and 2/3 on average
+        let below_half = cosines.iter().filter(|&&c| c < 0.5).count() as f64 / n as f64;
+        let mean = cosines.iter().sum::<f64>() / n as f64;
+        assert!((below_half - 0.25).abs() < 0.01, "{}", below_half);
+        assert!((mean - 2.0 / 3.0).abs() < 0.01, "{}", mean);
+    }
+
+    #[test]
+    fn hemisphere_samples_stay_on_the_normals_side() {
+        common::seed_rng(13);
+        for normal in [
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            unit_vector(Vec3::new(1.0, -2.0, 0.5)),
+        ] {
+            for _ in 0..2000 {
+                let sample = random_in_hemisphere(normal);
+                assert!(dot(sample, normal) >= 0.0);
+                assert!(sample.length() <= 1.0);
+            }
+        }
+    }
+}